@@ -18,6 +18,7 @@ use scribe::buffer::Position;
 
 
 const INSERTS: usize = 1000;
+const APPENDS: usize = 100000;
 
 
 struct BenchResult {
@@ -35,6 +36,9 @@ fn main() {
     insert_gapbuffer(data, &mut results);
     insert_scribe(data, &mut results);
     insert_gap_buffer(data, &mut results);
+    insert_piece_table(data, &mut results);
+    append_gap_buffer(data, &mut results);
+    append_piece_table(data, &mut results);
 
     print_results(results);
 }
@@ -91,7 +95,7 @@ fn insert_scribe(data: &str, results: &mut Vec<BenchResult>) {
 
 fn insert_gap_buffer(data: &str, results: &mut Vec<BenchResult>) {
     let mut rng = rand::thread_rng();
-    let mut buf = gap_buffer::GapBuffer::with_capacity(1);
+    let mut buf = gap_buffer::GapString::with_capacity(1);
 
     let mut max_index: usize = 0;
 
@@ -104,13 +108,69 @@ fn insert_gap_buffer(data: &str, results: &mut Vec<BenchResult>) {
     let duration = start.elapsed();
 
     results.push(BenchResult {
-        _type: "gap_buffer::GapBuffer".to_owned(),
+        _type: "gap_buffer::GapString".to_owned(),
         method: "insert_str".to_owned(),
         secs: duration.as_secs() as u32,
         nanos: duration.subsec_nanos()
     });
 }
 
+fn insert_piece_table(data: &str, results: &mut Vec<BenchResult>) {
+    let mut rng = rand::thread_rng();
+    let mut buf = gap_buffer::PieceTable::new();
+
+    let mut max_index: usize = 0;
+
+    let start = Instant::now();
+    for _ in 0..INSERTS {
+        let index = rng.gen_range::<usize>(0, max_index + 1);
+        buf.insert_str(index, data);
+        max_index += data.len() - 1;
+    }
+    let duration = start.elapsed();
+
+    results.push(BenchResult {
+        _type: "gap_buffer::PieceTable".to_owned(),
+        method: "insert_str".to_owned(),
+        secs: duration.as_secs() as u32,
+        nanos: duration.subsec_nanos()
+    });
+}
+
+fn append_gap_buffer(data: &str, results: &mut Vec<BenchResult>) {
+    let mut buf = gap_buffer::GapString::with_capacity(1);
+
+    let start = Instant::now();
+    for _ in 0..APPENDS {
+        buf.push_str(data);
+    }
+    let duration = start.elapsed();
+
+    results.push(BenchResult {
+        _type: "gap_buffer::GapString".to_owned(),
+        method: "push_str".to_owned(),
+        secs: duration.as_secs() as u32,
+        nanos: duration.subsec_nanos()
+    });
+}
+
+fn append_piece_table(data: &str, results: &mut Vec<BenchResult>) {
+    let mut buf = gap_buffer::PieceTable::new();
+
+    let start = Instant::now();
+    for _ in 0..APPENDS {
+        buf.push_str(data);
+    }
+    let duration = start.elapsed();
+
+    results.push(BenchResult {
+        _type: "gap_buffer::PieceTable".to_owned(),
+        method: "push_str".to_owned(),
+        secs: duration.as_secs() as u32,
+        nanos: duration.subsec_nanos()
+    });
+}
+
 fn print_results(results: Vec<BenchResult>) {
     let (type_len, method_len) = get_lens(&results);
 
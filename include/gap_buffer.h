@@ -0,0 +1,83 @@
+/*
+ * Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, you can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+/*
+ * C interface of the gap-buffer crate, built with its `ffi` feature:
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * Offsets are byte offsets into UTF-8 content. Functions returning
+ * ptrdiff_t return a negative GAPBUF_ERR_* code on failure, leaving the
+ * buffer unchanged. A buffer must not be used from several threads at once.
+ */
+
+#ifndef GAP_BUFFER_H
+#define GAP_BUFFER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A pointer argument is NULL. */
+#define GAPBUF_ERR_NULL                 (-1)
+/* An offset or range lies outside of the content, or is reversed. */
+#define GAPBUF_ERR_OUT_OF_BOUNDS        (-2)
+/* An offset falls inside a multi-byte char. */
+#define GAPBUF_ERR_NOT_CHAR_BOUNDARY    (-3)
+/* Inserted bytes are not valid UTF-8. */
+#define GAPBUF_ERR_INVALID_UTF8         (-4)
+/* The buffer would grow beyond its maximum capacity. */
+#define GAPBUF_ERR_CAPACITY_EXCEEDED    (-5)
+/* Allocating memory failed. */
+#define GAPBUF_ERR_ALLOCATION_FAILED    (-6)
+/* The edit touches a protected range. */
+#define GAPBUF_ERR_PROTECTED            (-7)
+/* The buffer is read-only. */
+#define GAPBUF_ERR_READ_ONLY            (-8)
+/* The output of gapbuf_read_range is too small for the range. */
+#define GAPBUF_ERR_BUFFER_TOO_SMALL     (-9)
+
+/* Opaque buffer of UTF-8 text. */
+typedef struct GapBuf GapBuf;
+
+/* Creates a new, empty buffer, to be released with gapbuf_free. */
+GapBuf *gapbuf_new(void);
+
+/* Releases a buffer created by gapbuf_new. Does nothing for NULL. */
+void gapbuf_free(GapBuf *buf);
+
+/*
+ * Inserts the len bytes at text, which must be valid UTF-8, at offset.
+ * text may be NULL if len is 0. Returns 0 on success.
+ */
+ptrdiff_t gapbuf_insert(GapBuf *buf, size_t offset, const uint8_t *text, size_t len);
+
+/* Removes the content between start and end. Returns 0 on success. */
+ptrdiff_t gapbuf_remove(GapBuf *buf, size_t start, size_t end);
+
+/* Returns the length of the content in bytes, or 0 for NULL. */
+size_t gapbuf_len(const GapBuf *buf);
+
+/*
+ * Copies the content between start and end into out, which holds out_len
+ * bytes, without a terminating NUL. Returns the number of bytes copied.
+ */
+ptrdiff_t gapbuf_read_range(const GapBuf *buf,
+                            size_t start,
+                            size_t end,
+                            uint8_t *out,
+                            size_t out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GAP_BUFFER_H */
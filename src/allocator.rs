@@ -0,0 +1,168 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+// Allocator a `GapBuffer` gets its memory from.
+//
+// With the `allocator-api` feature these are the `Allocator` trait and
+// `Global` allocator of the allocator-api2 crate, which arena and bump
+// allocators implement, and which are those of the standard library on
+// nightly with its `nightly` feature. Without it, a minimal stand-in with
+// the same signatures keeps `Global` as the only allocator, private to the
+// crate so that enabling the feature only adds to its public items.
+
+#[cfg(feature = "allocator-api")]
+pub use allocator_api2::alloc::{AllocError, Allocator, Global};
+
+#[cfg(not(feature = "allocator-api"))]
+pub(crate) use self::global::{Allocator, Global};
+#[cfg(all(test, not(feature = "allocator-api")))]
+pub(crate) use self::global::AllocError;
+
+
+#[cfg(not(feature = "allocator-api"))]
+mod global {
+    use std::{alloc, fmt, ptr};
+    use std::alloc::Layout;
+    use std::error::Error;
+    use std::ptr::NonNull;
+
+
+    /// Error returned by an `Allocator` that could not provide memory.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct AllocError;
+
+    /// Source of the memory of a `GapBuffer`, a subset of the unstable
+    /// `std::alloc::Allocator`.
+    ///
+    /// # Safety
+    ///
+    /// Memory returned by `allocate`, `grow` or `shrink` must stay valid
+    /// until passed to `deallocate`, `grow` or `shrink`, and fit the layout
+    /// it was requested with.
+    pub unsafe trait Allocator {
+        /// Allocates memory fitting `layout`.
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+        /// Frees `ptr`, allocated by this allocator with `layout`.
+        ///
+        /// # Safety
+        ///
+        /// `ptr` must have been allocated by this allocator with `layout`.
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+
+        /// Grows the allocation at `ptr` to `new_layout`, keeping its
+        /// content.
+        ///
+        /// # Safety
+        ///
+        /// `ptr` must have been allocated by this allocator with
+        /// `old_layout`, which must not be larger than `new_layout`.
+        unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>;
+
+        /// Shrinks the allocation at `ptr` to `new_layout`, keeping the
+        /// content that fits.
+        ///
+        /// # Safety
+        ///
+        /// `ptr` must have been allocated by this allocator with
+        /// `old_layout`, which must not be smaller than `new_layout`.
+        unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>;
+    }
+
+    /// The global memory allocator, as used by `Vec`.
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Global;
+
+    unsafe impl Allocator for Global {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            if layout.size() == 0 {
+                return Ok(slice_ptr(dangling(layout), 0));
+            }
+            let ptr = unsafe { alloc::alloc(layout) };
+            NonNull::new(ptr).map(|ptr| slice_ptr(ptr, layout.size())).ok_or(AllocError)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            if layout.size() > 0 {
+                alloc::dealloc(ptr.as_ptr(), layout);
+            }
+        }
+
+        unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            self.resize(ptr, old_layout, new_layout)
+        }
+
+        unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            self.resize(ptr, old_layout, new_layout)
+        }
+    }
+
+    unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            (**self).allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            (**self).deallocate(ptr, layout)
+        }
+
+        unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            (**self).grow(ptr, old_layout, new_layout)
+        }
+
+        unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            (**self).shrink(ptr, old_layout, new_layout)
+        }
+    }
+
+    impl Global {
+        /// Reallocates, the alignment being the same for every allocation of
+        /// a `GapBuffer`.
+        unsafe fn resize(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            debug_assert!(old_layout.align() == new_layout.align());
+            if old_layout.size() == 0 {
+                return self.allocate(new_layout);
+            }
+            if new_layout.size() == 0 {
+                self.deallocate(ptr, old_layout);
+                return Ok(slice_ptr(dangling(new_layout), 0));
+            }
+            let new = alloc::realloc(ptr.as_ptr(), old_layout, new_layout.size());
+            NonNull::new(new).map(|new| slice_ptr(new, new_layout.size())).ok_or(AllocError)
+        }
+    }
+
+    impl fmt::Display for AllocError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("Memory allocation failed")
+        }
+    }
+
+    impl Error for AllocError {}
+
+    /// Returns a non-null pointer aligned for `layout`, for zero-sized
+    /// allocations.
+    fn dangling(layout: Layout) -> NonNull<u8> {
+        unsafe { NonNull::new_unchecked(ptr::null_mut::<u8>().wrapping_add(layout.align())) }
+    }
+
+    fn slice_ptr(ptr: NonNull<u8>, len: usize) -> NonNull<[u8]> {
+        NonNull::slice_from_raw_parts(ptr, len)
+    }
+}
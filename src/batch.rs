@@ -0,0 +1,145 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+use {EditDelta, GapBufferError, GapString, PatchError};
+
+
+/// Replacement of `range` by `text`, as applied by `GapString::apply_edits`
+/// and returned by `GapString::diff`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String
+}
+
+impl GapString {
+    /// Applies `edits` as a batch, such as typing with multiple cursors, and
+    /// returns the change made by each of them in the order given.
+    ///
+    /// The edits are applied from last to first, moving the gap once per
+    /// edit, after growing it once for all of them. Every range refers to
+    /// the content before the batch, and so do the returned deltas. Edits
+    /// are undone as a single step. Insertions at the same offset end up in
+    /// the order given.
+    ///
+    /// # Panics
+    ///
+    /// * If any two edits overlap.
+    /// * Same as `replace_range` for any of the edits.
+    ///
+    /// No edit is applied in either case.
+    pub fn apply_edits(&mut self, edits: &[Edit]) -> Vec<EditDelta> {
+        self.apply_patch(edits).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Applies `edits` like `apply_edits`, such as the output of a formatter
+    /// or the hunks of a patch, returning an error instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// No edit is applied on error.
+    ///
+    /// * `Overlapping` if any two edits overlap.
+    /// * `Edit` with the error `try_replace_range` would return for any of
+    ///   the edits, or `OutOfBounds` if a range ends before it starts.
+    pub fn apply_patch(&mut self, edits: &[Edit])
+        -> Result<Vec<EditDelta>, PatchError>
+    {
+        let mut order: Vec<usize> = (0..edits.len()).collect();
+        order.sort_by_key(|&i| (edits[i].range.start, edits[i].range.end));
+
+        let mut prev_end = 0;
+        let mut growth = 0;
+        for &i in &order {
+            let range = &edits[i].range;
+            if range.start < prev_end {
+                return Err(PatchError::Overlapping);
+            }
+            if range.start > range.end {
+                return Err(PatchError::Edit(GapBufferError::OutOfBounds));
+            }
+            self.check_offset(range.start)
+                .and_then(|_| self.check_offset(range.end))
+                .and_then(|_| self.check_edit(range.start, range.len()))
+                .map_err(PatchError::Edit)?;
+            prev_end = range.end;
+            growth += edits[i].text.len().saturating_sub(range.len());
+        }
+        self.raw.try_reserve(growth).map_err(PatchError::Edit)?;
+
+        let mut transaction = self.begin_transaction();
+        for &i in order.iter().rev() {
+            let edit = &edits[i];
+            transaction.replace_range(edit.range.clone(), &edit.text);
+        }
+        transaction.commit();
+
+        let deltas = edits.iter()
+            .map(|edit| EditDelta {
+                offset: edit.range.start,
+                removed_len: edit.range.len(),
+                inserted_len: edit.text.len()
+            })
+            .collect();
+        Ok(deltas)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, GapBufferError, PatchError};
+    use super::Edit;
+
+
+    #[test]
+    fn apply_edits_1() {
+        let mut gap_buf = buf_from_str("a\nb\nc\n");
+        gap_buf.enable_history(10);
+        let edit = |range, text: &str| Edit { range, text: text.to_string() };
+        let deltas = gap_buf.apply_edits(&[edit(4..4, "- "),
+                                           edit(0..1, "A"),
+                                           edit(2..2, "- "),
+                                           edit(2..2, "[ ] ")]);
+        assert!(gap_buf == "A\n- [ ] b\n- c\n");
+        assert!(deltas[0].offset == 4 && deltas[0].inserted_len == 2);
+        assert!(deltas[1].old_range() == (0..1));
+
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "a\nb\nc\n");
+    }
+
+    #[test]
+    #[should_panic]
+    fn apply_edits_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.apply_edits(&[Edit { range: 2..5, text: String::new() },
+                              Edit { range: 4..6, text: String::new() }]);
+    }
+
+    #[test]
+    fn apply_patch_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        let edit = |range, text: &str| Edit { range, text: text.to_string() };
+        let overlapping = [edit(2..5, ""), edit(4..6, "x")];
+        assert!(gap_buf.apply_patch(&overlapping) == Err(PatchError::Overlapping));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = [edit(0..1, "x"), edit(6..5, "")];
+        let error = gap_buf.apply_patch(&reversed).unwrap_err();
+        assert!(error == PatchError::Edit(GapBufferError::OutOfBounds));
+        gap_buf.protect(6..8);
+        let error = gap_buf.apply_patch(&[edit(0..1, "x"), edit(7..8, "")]).unwrap_err();
+        assert!(error == PatchError::Edit(GapBufferError::Protected));
+        assert!(gap_buf == "12345678");
+
+        let deltas = gap_buf.apply_patch(&[edit(4..4, "-"), edit(0..1, "")]).unwrap();
+        assert!(gap_buf == "234-5678");
+        assert!(deltas[1].old_range() == (0..1));
+    }
+}
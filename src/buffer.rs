@@ -0,0 +1,1566 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::{fmt, mem, ptr, slice, str};
+use std::alloc::Layout;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::iter::Chain;
+use std::ops::{Drop, Index, IndexMut, Range};
+use std::str::Utf8Error;
+
+use {GapBufferError, InvariantError};
+use allocator::{Allocator, Global};
+#[cfg(all(feature = "mmap", unix))]
+use mmap::Mapping;
+use policy::{GrowthPolicy, ShrinkPolicy};
+
+
+/// Gap size, in elements, the shrink policy shrinks the gap down to.
+pub(crate) const CHUNK_SIZE: isize = 32;
+
+/// Pattern the gap is filled with when the `debug-guards` feature is enabled.
+#[cfg(feature = "debug-guards")]
+const POISON: u8 = 0xa5;
+
+
+/// Dynamic array of `T` that allows efficient insertion and removal
+/// operations that are near the same location.
+///
+/// Text editing is built on top of `GapBuffer<u8>` by `GapString`.
+///
+/// Memory comes from the allocator `A`, the global allocator by default.
+/// With the `allocator-api` feature any `Allocator` can back the buffer,
+/// such as an arena for short-lived scratch buffers.
+///
+/// The buffer is `Send` when `T` and `A` are, so it can be handed to another
+/// thread like a `Vec`. It is not `Sync`: to read the content from several
+/// threads at once, wrap it in a lock or share a `GapString::snapshot`.
+pub struct GapBuffer<T, A: Allocator = Global> {
+    buf_start: *mut T,
+    gap_start: *mut T,
+    gap_end: *mut T,
+    buf_end: *mut T,
+    max_capacity: Option<usize>,
+    growth_policy: GrowthPolicy,
+    shrink_policy: ShrinkPolicy,
+    alloc: A,
+    #[cfg(all(feature = "mmap", unix))]
+    mapping: Option<Mapping>
+}
+
+/// Iterator over the elements of a `GapBuffer`.
+///
+/// Created by [`GapBuffer::iter`](struct.GapBuffer.html#method.iter).
+pub struct Iter<'a, T: 'a> {
+    inner: Chain<slice::Iter<'a, T>, slice::Iter<'a, T>>
+}
+
+impl<T> GapBuffer<T> {
+    /// Converts the buffer into a `Vec`, reusing its allocation.
+    ///
+    /// The gap is moved to the end first, which takes O(n) time unless it
+    /// already is there.
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.check_gap();
+        let len = self.len();
+        self.move_gap_to(len);
+
+        // A mapping cannot be handed over, the elements are moved out of it
+        // instead and the empty buffer unmaps it when dropped.
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            if self.mapping.is_some() {
+                let mut vec = Vec::with_capacity(len);
+                unsafe {
+                    ptr::copy_nonoverlapping(self.buf_start, vec.as_mut_ptr(), len);
+                    vec.set_len(len);
+                }
+                self.gap_start = self.buf_start;
+                return vec;
+            }
+        }
+
+        let vec = unsafe { Vec::from_raw_parts(self.buf_start, len, self.capacity()) };
+        mem::forget(self);
+        vec
+    }
+
+    /// Creates a new, empty buffer without allocating.
+    pub fn new() -> GapBuffer<T> {
+        GapBuffer::with_capacity(0)
+    }
+
+    /// Creates a new buffer with room for `capacity` elements.
+    ///
+    /// # Panics
+    ///
+    /// * If `T` is zero-sized.
+    /// * If the allocation fails.
+    pub fn with_capacity(capacity: usize) -> GapBuffer<T> {
+        GapBuffer::with_capacity_in(capacity, Global)
+    }
+
+    /// Creates a new, empty buffer growing its gap as `policy` decides.
+    pub fn with_growth_policy(policy: GrowthPolicy) -> GapBuffer<T> {
+        let mut buf = GapBuffer::new();
+        buf.growth_policy = policy;
+        buf
+    }
+
+    /// Creates a buffer holding `head` and `tail` with a `gap` element gap
+    /// between them, allocated to fit exactly.
+    pub(crate) fn from_parts(head: &[T], gap: usize, tail: &[T]) -> GapBuffer<T>
+        where T: Copy
+    {
+        unsafe { GapBuffer::copy_from_parts(head, gap, tail, Global) }
+    }
+}
+
+impl<T, A: Allocator> GapBuffer<T, A> {
+    /// Returns the allocator backing the buffer.
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Moves the elements of `other` to the end of the buffer, leaving
+    /// `other` empty.
+    ///
+    /// # Panics
+    ///
+    /// * If the buffer would exceed its maximum capacity.
+    pub fn append(&mut self, other: &mut GapBuffer<T, A>) {
+        if let Err(e) = self.try_append(other) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Returns the elements before and after the gap.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        self.check_gap();
+        (segment(self.buf_start, self.head_len() as usize),
+         segment(self.gap_end, self.tail_len() as usize))
+    }
+
+    /// Returns the number of elements the allocation holds, including the
+    /// gap.
+    pub fn capacity(&self) -> usize {
+        ptr_diff(self.buf_end, self.buf_start) as usize
+    }
+
+    /// Checks that the pointers of the buffer are in order and aligned, and
+    /// with the `debug-guards` feature that nothing wrote into the gap.
+    ///
+    /// Meant for `debug_assert!`s and tests. Takes O(1) time, or O(n) in the
+    /// size of the gap with `debug-guards`.
+    ///
+    /// # Errors
+    ///
+    /// * `InvariantError::PointerOrder` if the pointers are out of order.
+    /// * `InvariantError::GapOverwritten` if the gap was written to.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        if self.buf_start.is_null()
+            || !self.buf_start.is_aligned()
+            || self.gap_start < self.buf_start
+            || self.gap_end < self.gap_start
+            || self.buf_end < self.gap_end
+        {
+            return Err(InvariantError::PointerOrder);
+        }
+        if self.gap_overwritten().is_some() {
+            return Err(InvariantError::GapOverwritten);
+        }
+        Ok(())
+    }
+
+    /// Removes all elements, keeping the allocation.
+    pub fn clear(&mut self) {
+        self.truncate_raw(0, true);
+    }
+
+    /// Returns the element at `index`, or `None` if it is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let (head, tail) = self.as_slices();
+        if index < head.len() {
+            head.get(index)
+        } else {
+            tail.get(index - head.len())
+        }
+    }
+
+    /// Returns the element at `index` mutably, or `None` if it is out of
+    /// bounds.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let (head, tail) = self.as_slices_mut();
+        if index < head.len() {
+            head.get_mut(index)
+        } else {
+            tail.get_mut(index - head.len())
+        }
+    }
+
+    /// Returns the policy deciding how much the gap grows.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth_policy
+    }
+
+    /// Inserts `value` at `index`.
+    ///
+    /// # Panics
+    ///
+    /// * If `index` is out of bounds.
+    /// * If the insertion would exceed the maximum capacity.
+    pub fn insert(&mut self, index: usize, value: T) {
+        assert!(index <= self.len(), "{}", GapBufferError::OutOfBounds);
+        if let Err(e) = self.make_room(index, 1) {
+            panic!("{}", e);
+        }
+
+        unsafe {
+            ptr::write(self.gap_start, value);
+            self.gap_start = self.gap_start.add(1);
+        }
+        self.poison_gap();
+    }
+
+    /// Returns `true` if the buffer holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (head, tail) = self.as_slices();
+        Iter { inner: head.iter().chain(tail.iter()) }
+    }
+
+    /// Returns the number of elements, excluding the gap.
+    pub fn len(&self) -> usize {
+        (self.head_len() + self.tail_len()) as usize
+    }
+
+    /// Moves the gap to the end and returns all elements as one slice.
+    ///
+    /// Takes O(n) time unless the gap already is at the end.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        self.check_gap();
+        let len = self.len();
+        self.move_gap_to(len);
+        self.poison_gap();
+        self.as_slices_mut().0
+    }
+
+    /// Returns the maximum capacity the buffer may grow to, if any.
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.max_capacity
+    }
+
+    /// Creates a new, empty buffer in `alloc`, without allocating.
+    pub fn new_in(alloc: A) -> GapBuffer<T, A> {
+        GapBuffer::with_capacity_in(0, alloc)
+    }
+
+    /// Removes the last element and returns it, or `None` if the buffer is
+    /// empty.
+    ///
+    /// Takes O(1) time while the gap is at the end, the gap is moved there
+    /// first otherwise.
+    pub fn pop(&mut self) -> Option<T> {
+        self.check_gap();
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        self.move_gap_to(len);
+
+        let value = unsafe {
+            self.gap_start = self.gap_start.sub(1);
+            ptr::read(self.gap_start)
+        };
+        self.poison_gap();
+        Some(value)
+    }
+
+    /// Appends `value` to the end of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// * If the buffer would exceed its maximum capacity.
+    pub fn push(&mut self, value: T) {
+        if let Err(e) = self.make_room_at_end(1) {
+            panic!("{}", e);
+        }
+
+        unsafe {
+            ptr::write(self.gap_start, value);
+            self.gap_start = self.gap_start.add(1);
+        }
+        self.poison_gap();
+    }
+
+    /// Removes and returns the element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// * If `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "{}", GapBufferError::OutOfBounds);
+        self.check_gap();
+
+        self.move_gap_to(index);
+        let value = unsafe {
+            let value = ptr::read(self.gap_end);
+            self.gap_end = self.gap_end.add(1);
+            value
+        };
+        self.poison_gap();
+        self.apply_shrink_policy();
+        value
+    }
+
+    /// Removes and drops the elements within `range`.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len(),
+                "{}", GapBufferError::OutOfBounds);
+        if range.start == range.end {
+            return;
+        }
+        self.check_gap();
+
+        self.move_gap_to(range.start);
+        unsafe {
+            let removed = self.gap_end;
+            self.gap_end = self.gap_end.add(range.len());
+            drop_segment(removed, range.len());
+        }
+        self.poison_gap();
+        self.apply_shrink_policy();
+    }
+
+    /// Makes room for at least `additional` more elements without
+    /// reallocating, growing the gap as the growth policy decides if needed.
+    ///
+    /// # Panics
+    ///
+    /// * If the allocation fails or would exceed the maximum capacity.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.try_reserve(additional) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Makes room for exactly `additional` more elements without
+    /// reallocating, with a single `realloc` if the gap is too small.
+    ///
+    /// # Panics
+    ///
+    /// * If the allocation fails or would exceed the maximum capacity.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.check_gap();
+
+        let needed = additional as isize - self.gap_len();
+        if needed <= 0 {
+            return;
+        }
+
+        let capacity = self.capacity() as isize;
+        let result = match self.max_capacity {
+            Some(max) if capacity + needed > max as isize => {
+                Err(GapBufferError::CapacityExceeded)
+            }
+            _ => self.extend_gap(needed)
+        };
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+        self.poison_gap();
+    }
+
+    /// Sets the policy deciding how much the gap grows.
+    ///
+    /// Defaults to `GrowthPolicy::Doubling`.
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.growth_policy = policy;
+    }
+
+    /// Limits the capacity the buffer may grow to.
+    ///
+    /// Once set, operations that would need to grow the allocation beyond
+    /// `max` fail with `GapBufferError::CapacityExceeded` instead. A limit
+    /// below the current capacity does not shrink the buffer, it only
+    /// prevents further growth. `None` removes the limit.
+    pub fn set_max_capacity(&mut self, max: Option<usize>) {
+        self.max_capacity = max;
+    }
+
+    /// Sets the policy deciding when the gap is shrunk after removals.
+    ///
+    /// Defaults to `ShrinkPolicy::Manual`.
+    pub fn set_shrink_policy(&mut self, policy: ShrinkPolicy) {
+        self.shrink_policy = policy;
+    }
+
+    /// Returns the policy deciding when the gap is shrunk after removals.
+    pub fn shrink_policy(&self) -> ShrinkPolicy {
+        self.shrink_policy
+    }
+
+    /// Shrinks the allocation to at least `min_capacity` elements, and not
+    /// below the length.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.check_gap();
+        let gap_len = min_capacity as isize - self.len() as isize;
+        self.shrink_gap_to(gap_len.max(0));
+    }
+
+    /// Shrinks the allocation to fit the elements, removing the gap.
+    pub fn shrink_to_fit(&mut self) {
+        self.check_gap();
+        self.shrink_gap_to(0);
+    }
+
+    /// Shortens the buffer to `len` elements, dropping the rest. Does
+    /// nothing if the buffer is not longer than `len`.
+    ///
+    /// Takes O(1) time when the gap is at or after `len`, and a single move
+    /// of the kept part of the tail otherwise.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+        self.truncate_raw(len, true);
+        self.apply_shrink_policy();
+    }
+
+    /// Creates a new buffer with room for `capacity` elements, allocated
+    /// from `alloc`.
+    ///
+    /// # Panics
+    ///
+    /// * If `T` is zero-sized.
+    /// * If the allocation fails.
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> GapBuffer<T, A> {
+        assert!(mem::size_of::<T>() != 0, "Zero-sized types are not supported");
+
+        // Nothing is allocated for zero capacity, the pointer just has to be
+        // non-null and aligned.
+        let buffer: *mut T = match layout::<T>(capacity) {
+            Some(layout) if capacity > 0 => allocate(&alloc, layout),
+            Some(_) => ptr::NonNull::dangling().as_ptr(),
+            None => ptr::null_mut()
+        };
+        if buffer.is_null() {
+            panic!("Unable to allocate requested capacity");
+        }
+
+        let mut buf = GapBuffer {
+            buf_start: buffer,
+            gap_start: buffer,
+            gap_end: unsafe { buffer.add(capacity) },
+            buf_end: unsafe { buffer.add(capacity) },
+            max_capacity: None,
+            growth_policy: GrowthPolicy::Doubling,
+            shrink_policy: ShrinkPolicy::Manual,
+            alloc,
+            #[cfg(all(feature = "mmap", unix))]
+            mapping: None
+        };
+        buf.poison_gap();
+        buf
+    }
+
+    /// Panics if anything has written into the gap since it was poisoned.
+    #[cfg(feature = "debug-guards")]
+    pub(crate) fn check_gap(&self) {
+        if let Some(pos) = self.gap_overwritten() {
+            let size = mem::size_of::<T>();
+            panic!("Gap overwritten at byte {} of {} (buffer offset {})",
+                   pos, self.gap_len() as usize * size, self.head_len() as usize + pos / size);
+        }
+    }
+
+    #[cfg(not(feature = "debug-guards"))]
+    pub(crate) fn check_gap(&self) {}
+
+    pub(crate) fn gap_len(&self) -> isize {
+        ptr_diff(self.gap_end, self.gap_start)
+    }
+
+    pub(crate) fn move_gap_to(&mut self, offset: usize) {
+        let diff = offset as isize - self.head_len();
+
+        if diff == 0 { return; }
+
+        if diff < 0 {
+            // Elements between offset and the gap move to the end of the gap.
+            unsafe {
+                self.gap_start = self.gap_start.offset(diff);
+                self.gap_end = self.gap_end.offset(diff);
+                ptr::copy(self.gap_start, self.gap_end, diff.unsigned_abs());
+            }
+        } else {
+            // Elements after the gap move to the start of the gap.
+            unsafe {
+                ptr::copy(self.gap_end, self.gap_start, diff as usize);
+                self.gap_start = self.gap_start.offset(diff);
+                self.gap_end = self.gap_end.offset(diff);
+            }
+        }
+    }
+
+    /// Fills the gap with `POISON` so `check_gap` can detect stray writes.
+    #[cfg(feature = "debug-guards")]
+    pub(crate) fn poison_gap(&mut self) {
+        let gap_bytes = self.gap_len() as usize * mem::size_of::<T>();
+        unsafe {
+            ptr::write_bytes(self.gap_start as *mut u8, POISON, gap_bytes);
+        }
+    }
+
+    #[cfg(not(feature = "debug-guards"))]
+    pub(crate) fn poison_gap(&mut self) {}
+
+    /// Moves the elements of `other` to the end of the buffer.
+    pub(crate) fn try_append(&mut self, other: &mut GapBuffer<T, A>)
+        -> Result<(), GapBufferError>
+    {
+        let other_len = other.len();
+        self.make_room_at_end(other_len)?;
+
+        {
+            let (head, tail) = other.as_slices();
+            unsafe {
+                ptr::copy_nonoverlapping(head.as_ptr(), self.gap_start, head.len());
+                ptr::copy_nonoverlapping(tail.as_ptr(),
+                                         self.gap_start.add(head.len()),
+                                         tail.len());
+                self.gap_start = self.gap_start.add(other_len);
+            }
+        }
+        self.poison_gap();
+
+        // The elements now belong to this buffer and must not be dropped.
+        other.truncate_raw(0, false);
+        Ok(())
+    }
+
+    /// Makes room for at least `additional` more elements like `reserve`,
+    /// failing instead of panicking.
+    pub(crate) fn try_reserve(&mut self, additional: usize)
+        -> Result<(), GapBufferError>
+    {
+        if additional as isize <= self.gap_len() {
+            return Ok(());
+        }
+        self.grow_gap(additional as isize)?;
+        self.poison_gap();
+        Ok(())
+    }
+
+    fn as_slices_mut(&mut self) -> (&mut [T], &mut [T]) {
+        self.check_gap();
+
+        let head_len = self.head_len() as usize;
+        let tail_len = self.tail_len() as usize;
+        unsafe {
+            (slice::from_raw_parts_mut(self.buf_start, head_len),
+             slice::from_raw_parts_mut(self.gap_end, tail_len))
+        }
+    }
+
+    fn allocate_extra(&mut self, extra: isize) -> Result<(), GapBufferError> {
+        let new_buf = unsafe { self.reallocate(self.capacity() + extra as usize) };
+        if new_buf.is_null() {
+            return Err(GapBufferError::AllocationFailed);
+        }
+
+        self.buf_start = new_buf;
+        Ok(())
+    }
+
+    fn apply_shrink_policy(&mut self) {
+        if let ShrinkPolicy::Ratio(ratio) = self.shrink_policy {
+            let gap_len = self.gap_len();
+            let limit = (self.len() as f32 * ratio) as isize;
+            if gap_len > CHUNK_SIZE && gap_len > limit {
+                self.shrink_gap_to(CHUNK_SIZE);
+            }
+        }
+    }
+
+    /// Creates a buffer holding a bitwise copy of `head` and `tail`, with a
+    /// `gap` element gap between them, allocated from `alloc`.
+    ///
+    /// Unless `T` is `Copy`, the caller has to make sure the originals are
+    /// not dropped.
+    unsafe fn copy_from_parts(head: &[T], gap: usize, tail: &[T], alloc: A)
+        -> GapBuffer<T, A>
+    {
+        let mut buf = GapBuffer::with_capacity_in(head.len() + gap + tail.len(), alloc);
+        ptr::copy_nonoverlapping(head.as_ptr(), buf.buf_start, head.len());
+        buf.gap_start = buf.buf_start.add(head.len());
+        buf.gap_end = buf.gap_start.add(gap);
+        ptr::copy_nonoverlapping(tail.as_ptr(), buf.gap_end, tail.len());
+        buf
+    }
+
+    /// Reallocates to make the gap exactly `extra` elements larger.
+    fn extend_gap(&mut self, extra: isize) -> Result<(), GapBufferError> {
+        // Offsets are taken before reallocating, the old pointers are no
+        // longer valid once realloc has moved the allocation.
+        let head_len = self.head_len();
+        let tail_len = self.tail_len();
+        let available = self.gap_len();
+        let new_gap_size = available + extra;
+
+        self.allocate_extra(extra)?;
+        unsafe {
+            let old_tail = self.buf_start.offset(head_len + available);
+            self.gap_start = self.buf_start.offset(head_len);
+            self.gap_end = self.gap_start.offset(new_gap_size);
+            self.buf_end = self.gap_end.offset(tail_len);
+            if tail_len > 0 {
+                ptr::copy(old_tail, self.gap_end, tail_len as usize);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the first byte of the gap that is not poisoned.
+    #[cfg(feature = "debug-guards")]
+    fn gap_overwritten(&self) -> Option<usize> {
+        let gap_bytes = self.gap_len() as usize * mem::size_of::<T>();
+        let gap = segment(self.gap_start as *const u8, gap_bytes);
+        gap.iter().position(|b| *b != POISON)
+    }
+
+    #[cfg(not(feature = "debug-guards"))]
+    fn gap_overwritten(&self) -> Option<usize> {
+        None
+    }
+
+    fn grow_gap(&mut self, size: isize) -> Result<(), GapBufferError> {
+        let needed = size - self.gap_len();
+        let capacity = self.capacity() as isize;
+        let mut extra = self.growth_policy
+            .extra(capacity as usize, needed as usize) as isize;
+
+        if let Some(max) = self.max_capacity {
+            // Round down to the limit if that still makes enough room.
+            if capacity + needed > max as isize {
+                return Err(GapBufferError::CapacityExceeded);
+            }
+            extra = extra.min(max as isize - capacity);
+        }
+
+        self.extend_gap(extra)
+    }
+
+    fn head_len(&self) -> isize {
+        ptr_diff(self.gap_start, self.buf_start)
+    }
+
+    /// Moves the gap to `offset`, growing it to at least `len` elements.
+    fn make_room(&mut self, offset: usize, len: usize)
+        -> Result<(), GapBufferError>
+    {
+        self.check_gap();
+
+        if len as isize > self.gap_len() {
+            self.grow_gap(len as isize)?;
+        }
+
+        self.move_gap_to(offset);
+        Ok(())
+    }
+
+    /// Moves the gap to the end, growing it to at least `len` elements.
+    fn make_room_at_end(&mut self, len: usize) -> Result<(), GapBufferError> {
+        self.check_gap();
+
+        if len as isize > self.gap_len() {
+            self.grow_gap(len as isize)?;
+        }
+
+        if self.gap_end != self.buf_end {
+            let end = self.len();
+            self.move_gap_to(end);
+        }
+        Ok(())
+    }
+
+    /// Resizes the allocation to `capacity` elements, returning the new
+    /// pointer or NULL on failure. The old pointer stays valid on failure.
+    unsafe fn reallocate(&mut self, capacity: usize) -> *mut T {
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            if self.mapping.is_some() {
+                return self.unmap(capacity);
+            }
+        }
+
+        let old = match layout::<T>(self.capacity()) {
+            Some(layout) => layout,
+            None => return ptr::null_mut()
+        };
+        let new = match layout::<T>(capacity) {
+            Some(layout) => layout,
+            None => return ptr::null_mut()
+        };
+
+        let buf_start = ptr::NonNull::new_unchecked(self.buf_start as *mut u8);
+        let result = if old.size() == 0 {
+            if new.size() == 0 {
+                return self.buf_start;
+            }
+            return allocate(&self.alloc, new);
+        } else if new.size() == 0 {
+            self.alloc.deallocate(buf_start, old);
+            return ptr::NonNull::dangling().as_ptr();
+        } else if new.size() > old.size() {
+            self.alloc.grow(buf_start, old, new)
+        } else {
+            self.alloc.shrink(buf_start, old, new)
+        };
+        result.map_or(ptr::null_mut(), |ptr| ptr.as_ptr() as *mut T)
+    }
+
+    /// Moves the elements out of the mapping into a new allocation of
+    /// `capacity` elements, returning its pointer or NULL on failure like
+    /// `reallocate`.
+    #[cfg(all(feature = "mmap", unix))]
+    unsafe fn unmap(&mut self, capacity: usize) -> *mut T {
+        let new: *mut T = match layout::<T>(capacity) {
+            Some(layout) if layout.size() == 0 => ptr::NonNull::dangling().as_ptr(),
+            Some(layout) => allocate(&self.alloc, layout),
+            None => return ptr::null_mut()
+        };
+        if !new.is_null() {
+            ptr::copy_nonoverlapping(self.buf_start, new, capacity.min(self.capacity()));
+            self.mapping = None;
+        }
+        new
+    }
+
+    fn shrink_gap_to(&mut self, size: isize) {
+        if self.gap_len() <= size { return; }
+
+        let head_len = self.head_len();
+        let tail_len = self.tail_len();
+        let new_size = (head_len + size + tail_len) as usize;
+
+        if tail_len > 0 {
+            unsafe {
+                ptr::copy(self.gap_end, self.gap_start.offset(size), tail_len as usize);
+            }
+        }
+
+        // The original allocation is still valid if realloc fails, and keeps
+        // its capacity: the tail moves back to its end so that the layout
+        // freed later is the one allocated.
+        let new_buf = unsafe { self.reallocate(new_size) };
+        if new_buf.is_null() {
+            if tail_len > 0 {
+                unsafe {
+                    ptr::copy(self.gap_start.offset(size), self.gap_end, tail_len as usize);
+                }
+            }
+            self.poison_gap();
+            return;
+        }
+
+        unsafe {
+            self.buf_start = new_buf;
+            self.gap_start = self.buf_start.offset(head_len);
+            self.gap_end = self.gap_start.offset(size);
+            self.buf_end = self.gap_end.offset(tail_len);
+        }
+        self.poison_gap();
+    }
+
+    fn tail_len(&self) -> isize {
+        ptr_diff(self.buf_end, self.gap_end)
+    }
+
+    /// Shortens the buffer to `len` elements, which must not be more than
+    /// its length, dropping the rest if `drop` is set.
+    fn truncate_raw(&mut self, len: usize, drop: bool) {
+        let head_len = self.head_len() as usize;
+        let tail_len = self.tail_len() as usize;
+
+        // Pointers are updated before anything is dropped, so a panicking
+        // destructor leaks elements instead of dropping them twice.
+        unsafe {
+            if len <= head_len {
+                let tail = self.gap_end;
+                self.gap_start = self.buf_start.add(len);
+                self.gap_end = self.buf_end;
+                if drop {
+                    drop_segment(self.gap_start, head_len - len);
+                    drop_segment(tail, tail_len);
+                }
+            } else {
+                let kept = len - head_len;
+                let tail = self.gap_end;
+                self.gap_end = self.buf_end;
+                if drop {
+                    drop_segment(tail.add(kept), tail_len - kept);
+                }
+                self.gap_end = self.buf_end.sub(kept);
+                ptr::copy(tail, self.gap_end, kept);
+            }
+        }
+        self.poison_gap();
+    }
+}
+
+impl<T, A: Allocator + Clone> GapBuffer<T, A> {
+    /// Truncates the buffer at `at` and returns the elements after it as a
+    /// new buffer, moving them only once.
+    ///
+    /// # Panics
+    ///
+    /// * If `at` is out of bounds.
+    pub fn split_off(&mut self, at: usize) -> GapBuffer<T, A> {
+        assert!(at <= self.len(), "{}", GapBufferError::OutOfBounds);
+
+        let other = {
+            let (head, tail) = self.as_slices();
+            unsafe {
+                if at < head.len() {
+                    GapBuffer::copy_from_parts(&head[at..], 0, tail, self.alloc.clone())
+                } else {
+                    GapBuffer::copy_from_parts(&[],
+                                               0,
+                                               &tail[at - head.len()..],
+                                               self.alloc.clone())
+                }
+            }
+        };
+
+        // The elements now belong to `other` and must not be dropped here.
+        self.truncate_raw(at, false);
+        self.apply_shrink_policy();
+        other
+    }
+}
+
+impl<A: Allocator> GapBuffer<u8, A> {
+    /// Returns the content as a `str`, or an error if it is not valid UTF-8.
+    ///
+    /// Borrows from the buffer unless the gap splits the content, in which
+    /// case the two segments are joined into a new `String`. The buffer
+    /// itself holds any bytes, and a char may well be split by the gap.
+    pub fn to_str(&self) -> Result<Cow<'_, str>, Utf8Error> {
+        match self.joined() {
+            Cow::Borrowed(bytes) => str::from_utf8(bytes).map(Cow::Borrowed),
+            Cow::Owned(bytes) => {
+                String::from_utf8(bytes).map(Cow::Owned).map_err(|e| e.utf8_error())
+            }
+        }
+    }
+
+    /// Returns the content as a `str`, replacing invalid UTF-8 sequences with
+    /// `U+FFFD REPLACEMENT CHARACTER`.
+    ///
+    /// Borrows from the buffer like `to_str` if the content is valid.
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        match self.joined() {
+            Cow::Borrowed(bytes) => String::from_utf8_lossy(bytes),
+            Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+
+    /// Inserts `ch` at `index`, encoding it directly into the gap.
+    pub(crate) fn try_insert_char(&mut self, index: usize, ch: char)
+        -> Result<(), GapBufferError>
+    {
+        if index > self.len() {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        self.make_room(index, ch.len_utf8())?;
+        self.write_char(ch);
+        Ok(())
+    }
+
+    /// Appends `ch`, encoding it directly into the gap, like `try_push_slice`.
+    pub(crate) fn try_push_char(&mut self, ch: char) -> Result<(), GapBufferError> {
+        self.make_room_at_end(ch.len_utf8())?;
+        self.write_char(ch);
+        Ok(())
+    }
+
+    /// Returns the content as one slice, borrowed unless both segments are
+    /// non-empty.
+    fn joined(&self) -> Cow<'_, [u8]> {
+        match self.as_slices() {
+            (head, []) => Cow::Borrowed(head),
+            ([], tail) => Cow::Borrowed(tail),
+            (head, tail) => Cow::Owned([head, tail].concat())
+        }
+    }
+
+    /// Encodes `ch` at the start of the gap, which must be large enough.
+    fn write_char(&mut self, ch: char) {
+        let len = ch.len_utf8();
+        unsafe {
+            ch.encode_utf8(slice::from_raw_parts_mut(self.gap_start, len));
+            self.gap_start = self.gap_start.add(len);
+        }
+        self.poison_gap();
+    }
+}
+
+#[cfg(all(feature = "mmap", unix))]
+impl GapBuffer<u8> {
+    /// Creates a buffer holding the `len` bytes of a file mapped behind the
+    /// gap of `mapping`.
+    pub(crate) fn from_mapping(mapping: Mapping, len: usize) -> GapBuffer<u8> {
+        let buffer = mapping.as_ptr();
+        let gap = Mapping::gap();
+        let mut buf = GapBuffer {
+            buf_start: buffer,
+            gap_start: buffer,
+            gap_end: unsafe { buffer.add(gap) },
+            buf_end: unsafe { buffer.add(gap + len) },
+            max_capacity: None,
+            growth_policy: GrowthPolicy::Doubling,
+            shrink_policy: ShrinkPolicy::Manual,
+            alloc: Global,
+            mapping: Some(mapping)
+        };
+        buf.poison_gap();
+        buf
+    }
+}
+
+impl<T: Copy, A: Allocator> GapBuffer<T, A> {
+    /// Inserts `values` at `index`, moving the gap only once.
+    ///
+    /// # Panics
+    ///
+    /// * If `index` is out of bounds.
+    /// * If the insertion would exceed the maximum capacity.
+    pub fn insert_slice(&mut self, index: usize, values: &[T]) {
+        if let Err(e) = self.try_insert_slice(index, values) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Replaces `range` with `values`, moving the gap only once.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    /// * If the replacement would exceed the maximum capacity.
+    pub fn replace_range(&mut self, range: Range<usize>, values: &[T]) {
+        if let Err(e) = self.try_replace_range(range, values) {
+            panic!("{}", e);
+        }
+    }
+
+    pub(crate) fn try_insert_slice(&mut self, index: usize, values: &[T])
+        -> Result<(), GapBufferError>
+    {
+        if index > self.len() {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        self.make_room(index, values.len())?;
+        self.write_gap(values);
+        Ok(())
+    }
+
+    /// Appends `values`, growing the gap as the growth policy decides so
+    /// repeated appends take amortized O(1) time per element.
+    pub(crate) fn try_push_slice(&mut self, values: &[T])
+        -> Result<(), GapBufferError>
+    {
+        self.make_room_at_end(values.len())?;
+        self.write_gap(values);
+        Ok(())
+    }
+
+    pub(crate) fn try_replace_range(&mut self, range: Range<usize>, values: &[T])
+        -> Result<(), GapBufferError>
+    {
+        self.check_gap();
+
+        if range.start > range.end || range.end > self.len() {
+            return Err(GapBufferError::OutOfBounds);
+        }
+
+        let removed = range.len() as isize;
+        let len = values.len() as isize;
+        if len > self.gap_len() + removed {
+            self.grow_gap(len - removed)?;
+        }
+
+        self.move_gap_to(range.start);
+        unsafe {
+            self.gap_end = self.gap_end.offset(removed);
+        }
+        self.write_gap(values);
+        self.apply_shrink_policy();
+        Ok(())
+    }
+
+    /// Copies `values` to the start of the gap, which must be large enough.
+    fn write_gap(&mut self, values: &[T]) {
+        unsafe {
+            ptr::copy_nonoverlapping(values.as_ptr(), self.gap_start, values.len());
+            self.gap_start = self.gap_start.add(values.len());
+        }
+        self.poison_gap();
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for GapBuffer<T, A> {
+    /// Clones the elements into a new allocation of the same capacity, with
+    /// the gap at the same position.
+    fn clone(&self) -> GapBuffer<T, A> {
+        let (head, tail) = self.as_slices();
+        let mut buf = GapBuffer::with_capacity_in(self.capacity(), self.alloc.clone());
+
+        // The gap shrinks one element at a time, so a panicking clone leaves
+        // `buf` holding only initialized elements.
+        unsafe {
+            for value in head {
+                ptr::write(buf.gap_start, value.clone());
+                buf.gap_start = buf.gap_start.add(1);
+            }
+            for value in tail.iter().rev() {
+                ptr::write(buf.gap_end.sub(1), value.clone());
+                buf.gap_end = buf.gap_end.sub(1);
+            }
+        }
+        buf.poison_gap();
+
+        buf.max_capacity = self.max_capacity;
+        buf.growth_policy = self.growth_policy;
+        buf.shrink_policy = self.shrink_policy;
+        buf
+    }
+}
+
+impl<T> From<Vec<T>> for GapBuffer<T> {
+    /// Takes over the allocation of `vec`, its spare capacity becoming the
+    /// gap at the end.
+    fn from(vec: Vec<T>) -> GapBuffer<T> {
+        assert!(mem::size_of::<T>() != 0, "Zero-sized types are not supported");
+
+        let mut vec = mem::ManuallyDrop::new(vec);
+        let buffer = vec.as_mut_ptr();
+        let mut buf = GapBuffer {
+            buf_start: buffer,
+            gap_start: unsafe { buffer.add(vec.len()) },
+            gap_end: unsafe { buffer.add(vec.capacity()) },
+            buf_end: unsafe { buffer.add(vec.capacity()) },
+            max_capacity: None,
+            growth_policy: GrowthPolicy::Doubling,
+            shrink_policy: ShrinkPolicy::Manual,
+            alloc: Global,
+            #[cfg(all(feature = "mmap", unix))]
+            mapping: None
+        };
+        buf.poison_gap();
+        buf
+    }
+}
+
+impl<T> Default for GapBuffer<T> {
+    /// Creates a new, empty buffer without allocating.
+    fn default() -> GapBuffer<T> {
+        GapBuffer::new()
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for GapBuffer<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (head, tail) = self.as_slices();
+        f.debug_struct("GapBuffer")
+            .field("head", &head)
+            .field("gap", &self.gap_len())
+            .field("tail", &tail)
+            .finish()
+    }
+}
+
+impl<T: Hash, A: Allocator> Hash for GapBuffer<T, A> {
+    /// Hashes the length and then the elements the way `Vec<T>` does. Types
+    /// hashing slices in one write, like `u8`, only match with hashers that
+    /// treat consecutive writes as one stream.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (head, tail) = self.as_slices();
+        state.write_usize(self.len());
+        T::hash_slice(head, state);
+        T::hash_slice(tail, state);
+    }
+}
+
+impl<T, A: Allocator> Index<usize> for GapBuffer<T, A> {
+    type Output = T;
+
+    /// # Panics
+    ///
+    /// * If `index` is out of bounds.
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!("{}", GapBufferError::OutOfBounds)
+        }
+    }
+}
+
+impl<T, A: Allocator> IndexMut<usize> for GapBuffer<T, A> {
+    /// # Panics
+    ///
+    /// * If `index` is out of bounds.
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        match self.get_mut(index) {
+            Some(value) => value,
+            None => panic!("{}", GapBufferError::OutOfBounds)
+        }
+    }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a GapBuffer<T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T: PartialEq, A: Allocator> PartialEq for GapBuffer<T, A> {
+    fn eq(&self, other: &GapBuffer<T, A>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, A: Allocator> Eq for GapBuffer<T, A> {}
+
+impl<T: PartialOrd, A: Allocator> PartialOrd for GapBuffer<T, A> {
+    /// Compares the elements lexicographically like slices do.
+    fn partial_cmp(&self, other: &GapBuffer<T, A>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord, A: Allocator> Ord for GapBuffer<T, A> {
+    fn cmp(&self, other: &GapBuffer<T, A>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<T, A: Allocator> Drop for GapBuffer<T, A> {
+    fn drop(&mut self) {
+        if mem::needs_drop::<T>() {
+            let head_len = self.head_len() as usize;
+            let tail_len = self.tail_len() as usize;
+            unsafe {
+                drop_segment(self.buf_start, head_len);
+                drop_segment(self.gap_end, tail_len);
+            }
+        }
+        #[cfg(all(feature = "mmap", unix))]
+        {
+            if self.mapping.is_some() {
+                return;
+            }
+        }
+        if let Some(layout) = layout::<T>(self.capacity()) {
+            if layout.size() > 0 {
+                unsafe {
+                    let buf_start = ptr::NonNull::new_unchecked(self.buf_start as *mut u8);
+                    self.alloc.deallocate(buf_start, layout);
+                }
+            }
+        }
+    }
+}
+
+// Safety: the pointers all point into the one allocation (or mapping) the
+// buffer owns, which no other value refers to, and are only dereferenced
+// through `&self` or `&mut self`. Moving the buffer to another thread moves
+// that ownership along, like `Vec<T, A>`: the elements must be `Send`, and
+// the allocator must be `Send` as it frees the memory on the new thread.
+unsafe impl<T: Send, A: Allocator + Send> Send for GapBuffer<T, A> {}
+
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Iter<'a, T> {
+        Iter { inner: self.inner.clone() }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Allocates memory fitting `layout` from `alloc`, returning NULL on
+/// failure.
+fn allocate<T, A: Allocator>(alloc: &A, layout: Layout) -> *mut T {
+    alloc.allocate(layout).map_or(ptr::null_mut(), |ptr| ptr.as_ptr() as *mut T)
+}
+
+/// Drops the `len` elements starting at `start`.
+unsafe fn drop_segment<T>(start: *mut T, len: usize) {
+    if len > 0 {
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(start, len));
+    }
+}
+
+fn ptr_to_isize<T>(p: *const T) -> isize {
+    p as isize
+}
+
+/// Returns the distance from `q` to `p` in elements.
+fn ptr_diff<T>(p: *const T, q: *const T) -> isize {
+    (ptr_to_isize(p) - ptr_to_isize(q)) / mem::size_of::<T>() as isize
+}
+
+/// Returns the layout of an allocation holding `capacity` elements, or
+/// `None` if its size overflows.
+fn layout<T>(capacity: usize) -> Option<Layout> {
+    Layout::array::<T>(capacity).ok()
+}
+
+fn segment<'a, T>(start: *const T, len: usize) -> &'a [T] {
+    unsafe { slice::from_raw_parts(start, len) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::alloc::Layout;
+    use std::borrow::Cow;
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+    use std::rc::Rc;
+    use std::thread;
+
+    use InvariantError;
+    use allocator::{AllocError, Allocator, Global};
+    use super::GapBuffer;
+
+
+    #[test]
+    fn as_slices_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u8, 2, 3], 2, &[4, 5]);
+        assert!(gap_buf.as_slices() == (&[1, 2, 3][..], &[4, 5][..]));
+
+        gap_buf.insert(1, 9);
+        assert!(gap_buf.as_slices() == (&[1, 9][..], &[2, 3, 4, 5][..]));
+    }
+
+    #[test]
+    fn debug_1() {
+        let gap_buf = GapBuffer::from_parts(&[1u8, 2, 3], 2, &[4, 5]);
+        let text = format!("{:?}", gap_buf);
+        assert!(text == "GapBuffer { head: [1, 2, 3], gap: 2, tail: [4, 5] }");
+    }
+
+    #[test]
+    fn insert_1() {
+        let mut gap_buf = GapBuffer::new();
+        for i in 0..8 {
+            gap_buf.push(i);
+        }
+        gap_buf.insert(4, 100);
+        gap_buf.insert(0, 200);
+        gap_buf.insert(10, 300);
+
+        let values: Vec<i32> = gap_buf.iter().cloned().collect();
+        assert!(values == [200, 0, 1, 2, 3, 100, 4, 5, 6, 7, 300]);
+    }
+
+    #[test]
+    fn insert_slice_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u32, 2, 3, 4], 0, &[]);
+        gap_buf.insert_slice(2, &[10, 11]);
+        gap_buf.insert_slice(0, &[20]);
+        assert!(gap_buf == GapBuffer::from_parts(&[20, 1, 2, 10, 11], 0, &[3, 4]));
+    }
+
+    #[test]
+    fn make_contiguous_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u8, 2, 3], 2, &[4, 5]);
+        gap_buf.insert(1, 9);
+
+        assert!(gap_buf.make_contiguous() == [1, 9, 2, 3, 4, 5]);
+        assert!(gap_buf.as_slices().1.is_empty());
+    }
+
+    #[test]
+    fn pop_1() {
+        let mut gap_buf: GapBuffer<String> = GapBuffer::new();
+        gap_buf.push("a".to_string());
+        gap_buf.push("c".to_string());
+        gap_buf.insert(1, "b".to_string());
+        assert!(gap_buf.pop().as_deref() == Some("c"));
+        assert!(gap_buf.pop().as_deref() == Some("b"));
+        assert!(gap_buf.pop().as_deref() == Some("a"));
+        assert!(gap_buf.pop().is_none());
+    }
+
+    #[test]
+    fn to_str_1() {
+        let mut gap_buf = GapBuffer::from("aéb".as_bytes().to_vec());
+        gap_buf.insert(2, b'c');
+        assert!(gap_buf.to_str().is_err());
+        assert!(gap_buf.to_string_lossy() == "a�c�b");
+
+        gap_buf.remove(2);
+        assert!(gap_buf.to_str().unwrap() == "aéb");
+        gap_buf.make_contiguous();
+        assert!(matches!(gap_buf.to_string_lossy(), Cow::Borrowed("aéb")));
+    }
+
+    #[test]
+    fn remove_1() {
+        let mut gap_buf = GapBuffer::from_parts(&['a', 'b', 'c'], 4, &['d', 'e']);
+        assert!(gap_buf.remove(3) == 'd');
+        assert!(gap_buf.remove(0) == 'a');
+        assert!(gap_buf[1] == 'c');
+
+        gap_buf[1] = 'x';
+        let values: String = gap_buf.iter().collect();
+        assert!(values == "bxe");
+    }
+
+    #[test]
+    fn remove_range_1() {
+        let counter = Rc::new(());
+        let mut gap_buf = GapBuffer::new();
+        for _ in 0..8 {
+            gap_buf.push(counter.clone());
+        }
+
+        gap_buf.remove_range(2..5);
+        assert!(gap_buf.len() == 5);
+        assert!(Rc::strong_count(&counter) == 6);
+
+        gap_buf.truncate(1);
+        assert!(Rc::strong_count(&counter) == 2);
+
+        drop(gap_buf);
+        assert!(Rc::strong_count(&counter) == 1);
+    }
+
+    #[test]
+    fn split_off_1() {
+        let counter = Rc::new(());
+        let mut gap_buf = GapBuffer::new();
+        for _ in 0..6 {
+            gap_buf.push(counter.clone());
+        }
+        gap_buf.insert(2, counter.clone());
+
+        let mut other = gap_buf.split_off(4);
+        assert!(gap_buf.len() == 4 && other.len() == 3);
+        assert!(Rc::strong_count(&counter) == 8);
+
+        gap_buf.append(&mut other);
+        assert!(gap_buf.len() == 7 && other.is_empty());
+        assert!(Rc::strong_count(&counter) == 8);
+
+        let clone = gap_buf.clone();
+        assert!(Rc::strong_count(&counter) == 15);
+        drop(clone);
+        gap_buf.clear();
+        assert!(Rc::strong_count(&counter) == 1);
+    }
+
+    #[test]
+    fn with_capacity_in_1() {
+        let allocated = Cell::new(0);
+        let alloc = Counting(&allocated);
+        let mut gap_buf = GapBuffer::with_capacity_in(4, &alloc);
+        assert!(allocated.get() == 16);
+
+        gap_buf.insert_slice(0, &[1u32, 2, 3, 4, 5]);
+        assert!(allocated.get() == 4 * gap_buf.capacity());
+        let other = gap_buf.split_off(2);
+        gap_buf.shrink_to_fit();
+        assert!(allocated.get() == 4 * (gap_buf.capacity() + other.capacity()));
+        assert!(gap_buf.iter().eq(&[1, 2]) && other.iter().eq(&[3, 4, 5]));
+
+        drop(gap_buf);
+        drop(other);
+        assert!(allocated.get() == 0);
+    }
+
+    #[test]
+    fn shrink_to_fit_1() {
+        let allocated = Cell::new(0);
+        let alloc = NoShrink(Counting(&allocated));
+        let mut gap_buf = GapBuffer::with_capacity_in(8, &alloc);
+        gap_buf.insert_slice(0, &[1u32, 2, 3, 4]);
+        gap_buf.insert(2, 9);
+        gap_buf.shrink_to_fit();
+        assert!(gap_buf.capacity() == 8 && allocated.get() == 32);
+        assert!(gap_buf.iter().eq(&[1, 2, 9, 3, 4]));
+
+        gap_buf.push(5);
+        drop(gap_buf);
+        assert!(allocated.get() == 0);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-guards")]
+    #[should_panic(expected = "Gap overwritten")]
+    fn debug_guards_1() {
+        let mut gap_buf = GapBuffer::from_parts(b"1234", 0, b"5678");
+        gap_buf.insert(4, b'-');
+        unsafe { *gap_buf.gap_start = b'x'; }
+        gap_buf.iter().count();
+    }
+
+    #[test]
+    fn check_invariants_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u32, 2], 3, &[4]);
+        assert!(gap_buf.check_invariants().is_ok());
+
+        let gap_start = gap_buf.gap_start;
+        gap_buf.gap_start = unsafe { gap_buf.gap_end.add(1) };
+        assert!(gap_buf.check_invariants() == Err(InvariantError::PointerOrder));
+        gap_buf.gap_start = gap_start;
+
+        #[cfg(feature = "debug-guards")]
+        {
+            unsafe { *gap_buf.gap_start = 3; }
+            assert!(gap_buf.check_invariants() == Err(InvariantError::GapOverwritten));
+            gap_buf.poison_gap();
+        }
+        gap_buf.insert(2, 3);
+        assert!(gap_buf.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn from_vec_1() {
+        let mut vec = Vec::with_capacity(8);
+        vec.extend_from_slice(&[1, 2, 3]);
+        let ptr = vec.as_ptr();
+
+        let mut buf = GapBuffer::from(vec);
+        assert!(buf.capacity() == 8);
+        buf.insert(0, 0);
+        assert!(buf.as_slices() == (&[0][..], &[1, 2, 3][..]));
+        let vec = buf.into_vec();
+        assert!(vec.as_ptr() == ptr);
+    }
+
+    #[test]
+    fn into_vec_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u16, 2, 3], 4, &[4, 5]);
+        gap_buf.insert(1, 9);
+
+        let vec = gap_buf.into_vec();
+        assert!(vec == [1, 9, 2, 3, 4, 5]);
+        assert!(vec.capacity() == 9);
+        assert!(GapBuffer::<u16>::new().into_vec().is_empty());
+    }
+
+    #[test]
+    fn iter_1() {
+        let mut gap_buf = GapBuffer::from_parts(&[1u8, 2, 3], 2, &[4, 5]);
+        gap_buf.insert(1, 9);
+
+        let values: Vec<u8> = gap_buf.iter().rev().cloned().collect();
+        assert!(values == [5, 4, 3, 2, 9, 1]);
+        assert!(gap_buf.iter().len() == 6);
+    }
+
+    #[test]
+    fn send_1() {
+        let mut gap_buf = GapBuffer::from(vec!["a".to_string(), "b".to_string()]);
+        gap_buf.insert(1, "c".to_string());
+
+        let gap_buf = thread::spawn(move || {
+            gap_buf.insert(3, "d".to_string());
+            gap_buf
+        }).join().unwrap();
+        assert!(gap_buf.iter().map(String::as_str).eq(["a", "c", "b", "d"]));
+        thread::spawn(move || drop(gap_buf)).join().unwrap();
+    }
+
+    /// Allocator keeping track of how many bytes it has handed out.
+    struct Counting<'a>(&'a Cell<usize>);
+
+    unsafe impl<'a> Allocator for Counting<'a> {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.set(self.0.get() + layout.size());
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.set(self.0.get() - layout.size());
+            Global.deallocate(ptr, layout)
+        }
+
+        unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            self.0.set(self.0.get() + new_layout.size() - old_layout.size());
+            Global.grow(ptr, old_layout, new_layout)
+        }
+
+        unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            self.0.set(self.0.get() + new_layout.size() - old_layout.size());
+            Global.shrink(ptr, old_layout, new_layout)
+        }
+    }
+
+    /// Allocator failing to shrink any allocation.
+    struct NoShrink<'a>(Counting<'a>);
+
+    unsafe impl<'a> Allocator for NoShrink<'a> {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.deallocate(ptr, layout)
+        }
+
+        unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            self.0.grow(ptr, old_layout, new_layout)
+        }
+
+        unsafe fn shrink(&self, _: NonNull<u8>, _: Layout, _: Layout)
+            -> Result<NonNull<[u8]>, AllocError>
+        {
+            Err(AllocError)
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::mem;
+use std::ops::Range;
+
+use GapString;
+
+
+/// Change made by a single edit: `removed_len` bytes at `offset` were
+/// replaced by `inserted_len` bytes.
+///
+/// Passed to change listeners, and returned by the editing methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EditDelta {
+    pub offset: usize,
+    pub removed_len: usize,
+    pub inserted_len: usize
+}
+
+impl EditDelta {
+    /// Returns the range the edit replaced, in offsets from before the edit.
+    pub fn old_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.removed_len
+    }
+
+    /// Returns the range of the inserted text, in offsets from after the
+    /// edit.
+    pub fn new_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.inserted_len
+    }
+}
+
+/// Handle to a listener registered by `GapString::on_change`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ListenerId(usize);
+
+type Listener = Box<dyn Fn(&EditDelta) + Send>;
+
+/// Change listeners of a buffer, called in the order they were registered.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    listeners: Vec<(ListenerId, Listener)>,
+    next_id: usize
+}
+
+impl GapString {
+    /// Registers `listener` to be called after every edit of the content.
+    ///
+    /// Every change is reported, including undo and redo, and rolled back
+    /// transactions. Preedit text is left out until committed, when it is
+    /// reported as a single insertion, and offsets are those of the
+    /// committed content. Clones of the buffer start out without listeners.
+    ///
+    /// Listeners must be `Send` so that the buffer stays `Send`; share state
+    /// with them through `Arc` and `Mutex` rather than `Rc` and `RefCell`.
+    pub fn on_change<F>(&mut self, listener: F) -> ListenerId
+        where F: Fn(&EditDelta) + Send + 'static
+    {
+        let id = ListenerId(self.listeners.next_id);
+        self.listeners.next_id += 1;
+        self.listeners.listeners.push((id, Box::new(listener)));
+        id
+    }
+
+    /// Unregisters a listener.
+    pub fn remove_listener(&mut self, id: ListenerId) {
+        self.listeners.listeners.retain(|&(other, _)| other != id);
+    }
+
+    /// Calls the listeners for an edit at `offset`.
+    pub(crate) fn notify_listeners(&self,
+                                   offset: usize,
+                                   removed: usize,
+                                   inserted: usize)
+    {
+        let offset = self.committed_offset(offset);
+        let delta = EditDelta { offset, removed_len: removed, inserted_len: inserted };
+        for (_, listener) in &self.listeners.listeners {
+            listener(&delta);
+        }
+    }
+}
+
+impl Listeners {
+    /// Returns the number of bytes allocated for the listeners, including
+    /// what they capture.
+    pub(crate) fn heap_size(&self) -> usize {
+        let captured: usize = self.listeners.iter()
+            .map(|(_, listener)| mem::size_of_val(&**listener))
+            .sum();
+        self.listeners.capacity() * mem::size_of::<(ListenerId, Listener)>() + captured
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use buf_from_str;
+    use super::EditDelta;
+
+
+    #[test]
+    fn edit_delta_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        let delta = gap_buf.replace_range(2..6, "ab");
+        assert!(delta.old_range() == (2..6));
+        assert!(delta.new_range() == (2..4));
+        assert!(gap_buf.insert_str(6, "9").new_range() == (6..7));
+        assert!(gap_buf.remove(0..1).old_range() == (0..1));
+    }
+
+    #[test]
+    fn on_change_1() {
+        let deltas = Arc::new(Mutex::new(Vec::new()));
+        let mut gap_buf = buf_from_str("12345678");
+        let seen = deltas.clone();
+        let id = gap_buf.on_change(move |delta| seen.lock().unwrap().push(*delta));
+
+        gap_buf.insert_str(4, "-");
+        gap_buf.replace_range(0..2, "abc");
+        gap_buf.set_preedit(0, "か");
+        gap_buf.set_preedit(0, "かな");
+        gap_buf.insert_str(16, "!");
+        gap_buf.commit_preedit();
+        gap_buf.set_preedit(0, "x");
+        gap_buf.clear_preedit();
+        gap_buf.remove_listener(id);
+        gap_buf.remove(6..7);
+
+        let delta = |offset, removed_len, inserted_len| {
+            EditDelta { offset, removed_len, inserted_len }
+        };
+        assert!(*deltas.lock().unwrap() == [delta(4, 0, 1),
+                                            delta(0, 2, 3),
+                                            delta(10, 0, 1),
+                                            delta(0, 0, 6)]);
+    }
+}
@@ -0,0 +1,392 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::{fmt, str};
+use std::borrow::Cow;
+use std::ops::Range;
+
+use buffer::GapBuffer;
+
+
+/// Chunk size, in bytes, used by `ChunkedString::new`.
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+
+/// Text buffer made of a list of gap buffers, each holding at most
+/// `chunk_size` bytes of the content.
+///
+/// Every chunk has a gap of its own, so moving to an edit far away copies at
+/// most one chunk instead of everything in between as a single gap would.
+/// Meant for documents of hundreds of megabytes, where `GapString` would
+/// spend its time moving the gap. Chunks always split the content on char
+/// boundaries.
+#[derive(Clone)]
+pub struct ChunkedString {
+    chunks: Vec<GapBuffer<u8>>,
+    chunk_size: usize,
+    len: usize
+}
+
+impl ChunkedString {
+    /// Returns an iterator over the chars of the content.
+    pub fn chars(&self) -> impl DoubleEndedIterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    /// Returns the maximum number of bytes per chunk.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns an iterator over the content as `&str` pieces, in order and
+    /// without copying.
+    ///
+    /// Yields the non-empty segments before and after the gap of each chunk.
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &str> + '_ {
+        self.chunks.iter()
+            .flat_map(|chunk| {
+                let (head, tail) = chunk.as_slices();
+                [head, tail]
+            })
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| unsafe { str::from_utf8_unchecked(segment) })
+    }
+
+    /// Inserts `s` at `offset`.
+    ///
+    /// Only the chunk holding `offset` is edited if `s` fits into it, the
+    /// chunk is split and new chunks are added after it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn insert_str(&mut self, offset: usize, s: &str) {
+        self.check_offset(offset);
+        if s.is_empty() {
+            return;
+        }
+        if self.chunks.is_empty() {
+            self.chunks.push(GapBuffer::with_capacity(self.chunk_size));
+        }
+
+        let (index, local) = self.locate(offset);
+        self.len += s.len();
+        if self.chunks[index].len() + s.len() <= self.chunk_size {
+            self.chunks[index].insert_slice(local, s.as_bytes());
+            return;
+        }
+
+        let mut rest = self.chunks[index].split_off(local);
+        let (first, mut s) = split_at_most(s, self.chunk_size - local);
+        self.chunks[index].insert_slice(local, first.as_bytes());
+
+        let mut new_chunks = Vec::new();
+        while !s.is_empty() {
+            let (piece, remaining) = split_at_most(s, self.chunk_size);
+            let mut chunk = GapBuffer::with_capacity(self.chunk_size);
+            chunk.insert_slice(0, piece.as_bytes());
+            new_chunks.push(chunk);
+            s = remaining;
+        }
+        match new_chunks.last_mut() {
+            Some(last) if last.len() + rest.len() <= self.chunk_size => last.append(&mut rest),
+            _ if !rest.is_empty() => new_chunks.push(rest),
+            _ => {}
+        }
+        self.chunks.splice(index + 1..index + 1, new_chunks);
+    }
+
+    /// Returns `true` if the content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the length of the content in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Creates a new, empty buffer with the default chunk size of 64 KiB.
+    pub fn new() -> ChunkedString {
+        ChunkedString::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Removes `range`.
+    ///
+    /// Chunks left empty are dropped, and the chunk the range started in is
+    /// merged with the next one if they fit into a single chunk.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    /// * If `range` starts or ends inside a char.
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.check_range(&range);
+        if range.start == range.end {
+            return;
+        }
+
+        let mut first = None;
+        let mut chunk_start = 0;
+        let mut index = 0;
+        while index < self.chunks.len() && chunk_start < range.end {
+            let chunk_len = self.chunks[index].len();
+            let start = range.start.max(chunk_start);
+            let end = range.end.min(chunk_start + chunk_len);
+            if start < end {
+                self.chunks[index].remove_range(start - chunk_start..end - chunk_start);
+                first = first.or(Some(index));
+            }
+            chunk_start += chunk_len;
+
+            if self.chunks[index].is_empty() {
+                self.chunks.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        self.len -= range.len();
+
+        if let Some(index) = first {
+            let index = index.saturating_sub(1);
+            self.merge(index);
+            self.merge(index);
+        }
+    }
+
+    /// Returns the content within `range`.
+    ///
+    /// Borrows from the buffer if `range` lies within a single segment,
+    /// copies it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    /// * If `range` starts or ends inside a char.
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.check_range(&range);
+
+        let mut result = Cow::Borrowed("");
+        let mut segment_start = 0;
+        for segment in self.chunks() {
+            let segment_end = segment_start + segment.len();
+            let start = range.start.max(segment_start);
+            let end = range.end.min(segment_end);
+            if start < end {
+                let piece = &segment[start - segment_start..end - segment_start];
+                if result.is_empty() {
+                    result = Cow::Borrowed(piece);
+                } else {
+                    result.to_mut().push_str(piece);
+                }
+            }
+            if segment_end >= range.end {
+                break;
+            }
+            segment_start = segment_end;
+        }
+        result
+    }
+
+    /// Creates a new, empty buffer whose chunks hold at most `chunk_size`
+    /// bytes.
+    ///
+    /// # Panics
+    ///
+    /// * If `chunk_size` is less than 4, too small to hold any char.
+    pub fn with_chunk_size(chunk_size: usize) -> ChunkedString {
+        assert!(chunk_size >= 4, "Chunk size {} cannot hold every char", chunk_size);
+        ChunkedString { chunks: Vec::new(), chunk_size, len: 0 }
+    }
+
+    fn check_offset(&self, offset: usize) {
+        assert!(offset <= self.len, "Offset {} out of bounds", offset);
+        assert!(self.is_char_boundary(offset), "Offset {} is not on a char boundary", offset);
+    }
+
+    fn check_range(&self, range: &Range<usize>) {
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        self.check_offset(range.start);
+        self.check_offset(range.end);
+    }
+
+    fn is_char_boundary(&self, offset: usize) -> bool {
+        if offset == 0 || offset == self.len {
+            return true;
+        }
+        let (index, local) = self.locate(offset);
+        match self.chunks[index].get(local) {
+            Some(b) => b & 0xc0 != 0x80,
+            None => true
+        }
+    }
+
+    /// Returns the index of the chunk holding `offset`, which must be in
+    /// bounds, and the offset within it. Offsets between two chunks are at
+    /// the end of the first one.
+    fn locate(&self, mut offset: usize) -> (usize, usize) {
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            if offset <= chunk.len() {
+                return (index, offset);
+            }
+            offset -= chunk.len();
+        }
+        unreachable!()
+    }
+
+    /// Moves the content of the chunk after `index` into it if both fit into
+    /// a single chunk.
+    fn merge(&mut self, index: usize) {
+        if index + 1 >= self.chunks.len() {
+            return;
+        }
+        if self.chunks[index].len() + self.chunks[index + 1].len() <= self.chunk_size {
+            let mut next = self.chunks.remove(index + 1);
+            self.chunks[index].append(&mut next);
+        }
+    }
+}
+
+impl Default for ChunkedString {
+    fn default() -> ChunkedString {
+        ChunkedString::new()
+    }
+}
+
+impl<'a> From<&'a str> for ChunkedString {
+    /// Creates a buffer holding `s`, split into chunks of the default size.
+    fn from(s: &'a str) -> ChunkedString {
+        let mut buf = ChunkedString::new();
+        buf.insert_str(0, s);
+        buf
+    }
+}
+
+impl fmt::Debug for ChunkedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.chunks()).finish()
+    }
+}
+
+impl fmt::Display for ChunkedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for chunk in self.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq<str> for ChunkedString {
+    fn eq(&self, other: &str) -> bool {
+        if self.len != other.len() {
+            return false;
+        }
+        let mut rest = other;
+        self.chunks().all(|chunk| {
+            let (head, tail) = rest.as_bytes().split_at(chunk.len());
+            rest = unsafe { str::from_utf8_unchecked(tail) };
+            head == chunk.as_bytes()
+        })
+    }
+}
+
+impl<'a> PartialEq<&'a str> for ChunkedString {
+    fn eq(&self, other: &&'a str) -> bool {
+        *self == **other
+    }
+}
+
+/// Splits `s` at the last char boundary at or before `max`.
+fn split_at_most(s: &str, max: usize) -> (&str, &str) {
+    let mut at = max.min(s.len());
+    while !s.is_char_boundary(at) {
+        at -= 1;
+    }
+    s.split_at(at)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::ChunkedString;
+
+
+    #[test]
+    fn insert_str_1() {
+        let mut buf = ChunkedString::with_chunk_size(8);
+        buf.insert_str(0, "0123456789");
+        buf.insert_str(4, "abcdé");
+        buf.insert_str(16, "xyz");
+        assert!(buf == "0123abcdé456789xyz");
+        assert!(buf.chunks.iter().all(|chunk| chunk.len() <= 8));
+        assert!(buf.chars().rev().take(3).collect::<String>() == "zyx");
+    }
+
+    #[test]
+    fn insert_str_2() {
+        let mut buf = ChunkedString::with_chunk_size(16);
+        let mut expected = String::new();
+
+        // Pseudo-random edits, checked against a plain string.
+        let mut seed = 7u32;
+        for _ in 0..500 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let mut offset = seed as usize % (expected.len() + 1);
+            while !expected.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let text = match seed % 4 {
+                0 => "é\n😀",
+                1 => "0123456789abcdefghijklmnopqrstuvwxyz",
+                2 => {
+                    let mut end = (offset + seed as usize % 40).min(expected.len());
+                    while !expected.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    buf.remove(offset..end);
+                    expected.replace_range(offset..end, "");
+                    ""
+                }
+                _ => "x"
+            };
+            buf.insert_str(offset, text);
+            expected.insert_str(offset, text);
+            assert!(buf == expected.as_str());
+            assert!(buf.chunks.iter().all(|chunk| chunk.check_invariants().is_ok()));
+        }
+        assert!(buf.chunks.iter().all(|chunk| chunk.len() <= 16));
+    }
+
+    #[test]
+    fn remove_1() {
+        let mut buf = ChunkedString::with_chunk_size(4);
+        buf.insert_str(0, "abcdefghijklmnop");
+        buf.remove(3..13);
+        assert!(buf == "abcnop");
+        assert!(buf.chunks.len() == 2);
+        buf.remove(0..6);
+        assert!(buf.is_empty() && buf.chunks.is_empty());
+    }
+
+    #[test]
+    fn slice_1() {
+        let mut buf = ChunkedString::with_chunk_size(4);
+        buf.insert_str(0, "abcdéfgh");
+        assert!(buf.slice(1..3) == "bc");
+        assert!(buf.slice(2..7) == "cdéf");
+        assert!(buf.slice(9..9).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_2() {
+        let buf = ChunkedString::from("é");
+        buf.slice(0..1);
+    }
+}
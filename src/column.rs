@@ -0,0 +1,279 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+#[cfg(feature = "unicode")]
+use unicode_width::UnicodeWidthChar;
+
+use GapString;
+
+
+impl GapString {
+    /// Removes the chars within columns `cols` from every line in `lines`.
+    ///
+    /// Columns are counted in chars, lines shorter than `cols.end` lose only
+    /// what they have within the range. Lines are edited from last to first
+    /// so offsets of the lines not yet edited stay valid.
+    ///
+    /// The edits are undone as a single step.
+    ///
+    /// # Panics
+    ///
+    /// * If `lines` reaches past the last line.
+    /// * If the buffer is read-only or any line's edit touches a protected
+    ///   range. No line is edited in that case.
+    pub fn delete_column(&mut self, lines: Range<usize>, cols: Range<usize>) {
+        let edits: Vec<Range<usize>> = self.line_ranges(lines)
+            .iter()
+            .map(|line| {
+                let (start, _) = self.column_offset(line, cols.start);
+                let (end, _) = self.column_offset(line, cols.end);
+                start..end
+            })
+            .filter(|range| range.start < range.end)
+            .collect();
+        self.check_column_edits(edits.iter().map(|r| (r.start, r.len())));
+
+        let mut transaction = self.begin_transaction();
+        for range in edits.into_iter().rev() {
+            transaction.remove(range);
+        }
+        transaction.commit();
+    }
+
+    /// Inserts `s` at column `col` of every line in `lines`.
+    ///
+    /// Columns are counted in chars, lines shorter than `col` are padded
+    /// with spaces up to `col`. Lines are edited from last to first so
+    /// offsets of the lines not yet edited stay valid.
+    ///
+    /// The edits are undone as a single step.
+    ///
+    /// # Panics
+    ///
+    /// * If `lines` reaches past the last line.
+    /// * If the buffer is read-only or any line's edit touches a protected
+    ///   range. No line is edited in that case.
+    pub fn insert_column(&mut self, lines: Range<usize>, col: usize, s: &str) {
+        let edits: Vec<(usize, usize)> = self.line_ranges(lines)
+            .iter()
+            .map(|line| self.column_offset(line, col))
+            .collect();
+        self.check_column_edits(edits.iter().map(|&(offset, _)| (offset, 0)));
+
+        let mut transaction = self.begin_transaction();
+        for (offset, found) in edits.into_iter().rev() {
+            if found < col {
+                let mut padded = " ".repeat(col - found);
+                padded.push_str(s);
+                transaction.insert_str(offset, &padded);
+            } else {
+                transaction.insert_str(offset, s);
+            }
+        }
+        transaction.commit();
+    }
+
+    /// Returns the offset of the char displayed at visual column `col` of
+    /// `line`, or `None` if the line doesn't exist or is narrower than
+    /// `col`. The column after the last char of a line is valid.
+    ///
+    /// Columns are counted as by `visual_column`. A column within a tab or a
+    /// wide char gives the offset of that char, such as when placing the
+    /// cursor where the user clicked.
+    ///
+    /// # Panics
+    ///
+    /// * If `tab_width` is 0.
+    pub fn offset_at_visual_column(&self, line: usize, col: usize, tab_width: usize)
+        -> Option<usize>
+    {
+        assert!(tab_width > 0, "Tab width of 0");
+        let start = self.line_to_byte(line)?;
+        let end = self.line_to_byte(line + 1).map_or(self.len(), |next| next - 1);
+
+        let mut found = 0;
+        for (i, ch) in self.slice_unchecked(start..end).char_indices() {
+            let next = advance(found, ch, tab_width);
+            if next > col {
+                return Some(start + i);
+            }
+            found = next;
+        }
+        if found == col { Some(end) } else { None }
+    }
+
+    /// Returns the column `offset` is displayed at within its line, with
+    /// tabs expanded to the next multiple of `tab_width`.
+    ///
+    /// Other chars take one column each, or with the `unicode` feature their
+    /// East Asian width: two columns for wide chars such as CJK ideographs,
+    /// and none for combining marks and control chars.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    /// * If `tab_width` is 0.
+    pub fn visual_column(&self, offset: usize, tab_width: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        assert!(tab_width > 0, "Tab width of 0");
+
+        let start = self.line_to_byte(self.byte_to_line(offset)).unwrap();
+        self.slice_unchecked(start..offset)
+            .chars()
+            .fold(0, |col, ch| advance(col, ch, tab_width))
+    }
+
+    /// Panics if any of the `(offset, removed)` edits is not allowed, before
+    /// any line has been touched.
+    fn check_column_edits<I>(&self, edits: I)
+        where I: Iterator<Item = (usize, usize)>
+    {
+        for (offset, removed) in edits {
+            if let Err(e) = self.check_edit(offset, removed) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    /// Returns the byte offset of char column `col` within `line`, or of the
+    /// line's end if it is shorter, along with the column reached.
+    fn column_offset(&self, line: &Range<usize>, col: usize) -> (usize, usize) {
+        let mut found = 0;
+        for (i, _) in self.slice_unchecked(line.clone()).char_indices() {
+            if found == col {
+                return (line.start + i, found);
+            }
+            found += 1;
+        }
+        (line.end, found)
+    }
+
+    /// Returns the byte ranges of `lines`, excluding line endings.
+    fn line_ranges(&self, lines: Range<usize>) -> Vec<Range<usize>> {
+        lines.clone()
+            .map(|line| {
+                let start = match self.line_to_byte(line) {
+                    Some(start) => start,
+                    None => panic!("Invalid line range: {:?}", lines)
+                };
+                let end = self.line_to_byte(line + 1).map_or(self.len(), |next| next - 1);
+                start..end
+            })
+            .collect()
+    }
+}
+
+/// Returns the visual column after `ch`, displayed at column `col`.
+fn advance(col: usize, ch: char, tab_width: usize) -> usize {
+    if ch == '\t' {
+        (col / tab_width + 1) * tab_width
+    } else {
+        col + char_width(ch)
+    }
+}
+
+#[cfg(feature = "unicode")]
+fn char_width(ch: char) -> usize {
+    ch.width().unwrap_or(0)
+}
+
+#[cfg(not(feature = "unicode"))]
+fn char_width(_: char) -> usize {
+    1
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use buf_from_str;
+
+
+    #[test]
+    fn insert_column_1() {
+        let mut gap_buf = buf_from_str("abc\ndef\nghi\n");
+        gap_buf.insert_column(0..3, 1, "|");
+
+        let text = gap_buf.to_string();
+        assert!(text == "a|bc\nd|ef\ng|hi\n");
+    }
+
+    #[test]
+    fn insert_column_2() {
+        let mut gap_buf = buf_from_str("abcd\na\nabcd");
+        gap_buf.insert_column(1..3, 3, "//");
+
+        let text = gap_buf.to_string();
+        assert!(text == "abcd\na  //\nabc//d");
+    }
+
+    #[test]
+    fn delete_column_1() {
+        let mut gap_buf = buf_from_str("äbcd\nx\néfgh");
+        gap_buf.delete_column(0..3, 1..3);
+
+        let text = gap_buf.to_string();
+        assert!(text == "äd\nx\néh");
+    }
+
+    #[test]
+    #[should_panic]
+    fn delete_column_2() {
+        let mut gap_buf = buf_from_str("abc\ndef");
+        gap_buf.delete_column(0..3, 0..1);
+    }
+
+    #[test]
+    fn delete_column_3() {
+        let mut gap_buf = buf_from_str("abc\ndef\nghi");
+        gap_buf.protect(5..6);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            gap_buf.delete_column(0..3, 0..2);
+        }));
+        assert!(result.is_err());
+        assert!(gap_buf == "abc\ndef\nghi");
+    }
+
+    #[test]
+    fn visual_column_1() {
+        let gap_buf = buf_from_str("\tab\tc\nx\ty");
+        assert!(gap_buf.visual_column(1, 4) == 4);
+        assert!(gap_buf.visual_column(4, 4) == 8);
+        assert!(gap_buf.visual_column(4, 2) == 6);
+        assert!(gap_buf.visual_column(7, 4) == 1);
+        assert!(gap_buf.visual_column(8, 8) == 8);
+    }
+
+    #[test]
+    fn offset_at_visual_column_1() {
+        let gap_buf = buf_from_str("\tab\tc\nx\ty");
+        assert!(gap_buf.offset_at_visual_column(0, 0, 4) == Some(0));
+        assert!(gap_buf.offset_at_visual_column(0, 2, 4) == Some(0));
+        assert!(gap_buf.offset_at_visual_column(0, 5, 4) == Some(2));
+        assert!(gap_buf.offset_at_visual_column(0, 9, 4) == Some(5));
+        assert!(gap_buf.offset_at_visual_column(0, 10, 4).is_none());
+        assert!(gap_buf.offset_at_visual_column(1, 3, 4) == Some(7));
+        assert!(gap_buf.offset_at_visual_column(2, 0, 4).is_none());
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn visual_column_2() {
+        let gap_buf = buf_from_str("日本\te\u{301}x");
+        assert!(gap_buf.visual_column(6, 4) == 4);
+        assert!(gap_buf.visual_column(7, 4) == 8);
+        assert!(gap_buf.visual_column(10, 4) == 9);
+        assert!(gap_buf.offset_at_visual_column(0, 1, 4) == Some(0));
+        assert!(gap_buf.offset_at_visual_column(0, 9, 4) == Some(10));
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Deref;
+
+use GapString;
+
+
+/// Editing position in a `GapString` that keeps the gap parked at itself.
+///
+/// Created by [`GapString::cursor`](struct.GapString.html#method.cursor).
+/// Typing and deleting at the cursor needs no gap moves, only `seek` moves
+/// the gap. The buffer can be read through `Deref`.
+pub struct Cursor<'a> {
+    buf: &'a mut GapString,
+    pos: usize
+}
+
+impl GapString {
+    /// Returns a cursor at `offset`, moving the gap there.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn cursor(&mut self, offset: usize) -> Cursor<'_> {
+        let mut cursor = Cursor { buf: self, pos: 0 };
+        cursor.seek(offset);
+        cursor
+    }
+}
+
+impl<'a> Cursor<'a> {
+    /// Removes the char before the cursor and returns it, or `None` at the
+    /// start of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `GapString::remove`.
+    pub fn delete_backward(&mut self) -> Option<char> {
+        let ch = self.peek_back()?;
+        let start = self.pos - ch.len_utf8();
+        self.buf.remove(start..self.pos);
+        self.pos = start;
+        Some(ch)
+    }
+
+    /// Removes the char after the cursor and returns it, or `None` at the
+    /// end of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `GapString::remove`.
+    pub fn delete_forward(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.buf.remove(self.pos..self.pos + ch.len_utf8());
+        Some(ch)
+    }
+
+    /// Inserts `s` at the cursor and moves the cursor past it.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `GapString::insert_str`.
+    pub fn insert(&mut self, s: &str) {
+        self.buf.insert_str(self.pos, s);
+        self.pos += s.len();
+    }
+
+    /// Returns the char after the cursor, or `None` at the end of the buffer.
+    pub fn peek(&self) -> Option<char> {
+        self.buf.char_at(self.pos)
+    }
+
+    /// Returns the char before the cursor, or `None` at the start of the
+    /// buffer.
+    pub fn peek_back(&self) -> Option<char> {
+        let mut start = self.pos.checked_sub(1)?;
+        while !self.buf.is_char_boundary(start) {
+            start -= 1;
+        }
+        self.buf.char_at(start)
+    }
+
+    /// Returns the offset of the cursor.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Moves the cursor, and the gap with it, to `offset`.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn seek(&mut self, offset: usize) {
+        if let Err(e) = self.buf.check_offset(offset) {
+            panic!("{}", e);
+        }
+
+        self.buf.raw.check_gap();
+        self.buf.raw.move_gap_to(offset);
+        self.buf.raw.poison_gap();
+        self.pos = offset;
+    }
+}
+
+impl<'a> Deref for Cursor<'a> {
+    type Target = GapString;
+
+    fn deref(&self) -> &GapString {
+        self.buf
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+
+
+    #[test]
+    fn cursor_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        {
+            let mut cursor = gap_buf.cursor(4);
+            cursor.insert("-é");
+            assert!(cursor.position() == 7);
+            assert!(cursor.as_strs() == ("1234-é", "5678"));
+
+            assert!(cursor.peek() == Some('5'));
+            assert!(cursor.peek_back() == Some('é'));
+            assert!(cursor.delete_backward() == Some('é'));
+            assert!(cursor.delete_forward() == Some('5'));
+            assert!(cursor.position() == 5);
+        }
+        assert!(gap_buf == "1234-678");
+    }
+
+    #[test]
+    fn cursor_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        let mut cursor = gap_buf.cursor(0);
+        assert!(cursor.delete_backward().is_none());
+
+        cursor.seek(8);
+        assert!(cursor.peek().is_none());
+        assert!(cursor.as_strs() == ("12345678", ""));
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+// Line diff between two buffers, using the linear space variant of Myers'
+// O(ND) algorithm ("An O(ND) Difference Algorithm and Its Variations",
+// 1986), after trimming the lines both contents start and end with.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use {Edit, GapString};
+
+
+/// Changed lines, as a range of old lines replaced by a range of new ones.
+type Hunk = (Range<usize>, Range<usize>);
+
+impl GapString {
+    /// Returns the edits turning the content into that of `other`, in
+    /// order and not overlapping, as taken by `apply_edits`.
+    ///
+    /// The content is compared line by line, and the edits replace as few
+    /// lines as possible. Applying them rather than replacing the whole
+    /// content keeps marks outside the changed lines in place, such as when
+    /// reloading a file changed on disk.
+    ///
+    /// Takes O((n + m) d) time and O(n + m) space, where d is the number of
+    /// lines that differ.
+    pub fn diff(&self, other: &GapString) -> Vec<Edit> {
+        let old_len = self.len();
+        let new_len = other.len();
+
+        // Whole lines both contents start and end with.
+        let prefix = self.bytes()
+            .zip(other.bytes())
+            .take_while(|&(a, b)| a == b)
+            .enumerate()
+            .filter(|&(_, (a, _))| a == b'\n')
+            .last()
+            .map_or(0, |(i, _)| i + 1);
+        let suffix = self.bytes()
+            .rev()
+            .zip(other.bytes().rev())
+            .take(old_len.min(new_len) - prefix)
+            .take_while(|&(a, b)| a == b)
+            .enumerate()
+            .filter(|&(_, (a, _))| a == b'\n')
+            .last()
+            .map_or(0, |(i, _)| i);
+
+        let old = self.slice_unchecked(prefix..old_len - suffix);
+        let new = other.slice_unchecked(prefix..new_len - suffix);
+        let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+        let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+        // Compares lines by number rather than by content.
+        let mut ids = HashMap::new();
+        let mut intern = |line| {
+            let next = ids.len();
+            *ids.entry(line).or_insert(next)
+        };
+        let a: Vec<usize> = old_lines.iter().map(|&line| intern(line)).collect();
+        let b: Vec<usize> = new_lines.iter().map(|&line| intern(line)).collect();
+
+        let mut hunks = Vec::new();
+        diff_lines(&a, &b, 0, 0, &mut hunks);
+
+        let old_starts = line_starts(&old_lines, prefix);
+        let new_starts = line_starts(&new_lines, 0);
+        hunks.into_iter()
+            .map(|(old_range, new_range)| Edit {
+                range: old_starts[old_range.start]..old_starts[old_range.end],
+                text: new[new_starts[new_range.start]..new_starts[new_range.end]].to_string()
+            })
+            .collect()
+    }
+}
+
+/// Returns the offset of every line, plus the end of the last one.
+fn line_starts(lines: &[&str], offset: usize) -> Vec<usize> {
+    let mut starts = Vec::with_capacity(lines.len() + 1);
+    starts.push(offset);
+    for line in lines {
+        let end = starts[starts.len() - 1] + line.len();
+        starts.push(end);
+    }
+    starts
+}
+
+/// Pushes the hunks turning `a` into `b`, which start at line `a_start` and
+/// `b_start`, merging adjacent ones.
+fn diff_lines(a: &[usize], b: &[usize], a_start: usize, b_start: usize, hunks: &mut Vec<Hunk>) {
+    let prefix = a.iter().zip(b).take_while(|&(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a_start, b_start) = (a_start + prefix, b_start + prefix);
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|&(x, y)| x == y).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    if a.is_empty() || b.is_empty() {
+        push_hunk(hunks, (a_start..a_start + a.len(), b_start..b_start + b.len()));
+        return;
+    }
+    match middle_snake(a, b) {
+        Some((x, y)) => {
+            diff_lines(&a[..x], &b[..y], a_start, b_start, hunks);
+            diff_lines(&a[x..], &b[y..], a_start + x, b_start + y, hunks);
+        }
+        None => push_hunk(hunks, (a_start..a_start + a.len(), b_start..b_start + b.len()))
+    }
+}
+
+fn push_hunk(hunks: &mut Vec<Hunk>, hunk: Hunk) {
+    if hunk.0.is_empty() && hunk.1.is_empty() {
+        return;
+    }
+    if let Some(last) = hunks.last_mut() {
+        if last.0.end == hunk.0.start && last.1.end == hunk.1.start {
+            last.0.end = hunk.0.end;
+            last.1.end = hunk.1.end;
+            return;
+        }
+    }
+    hunks.push(hunk);
+}
+
+/// Returns where a shortest edit path from `a` to `b` crosses its middle,
+/// searching from both ends at once, or `None` if they have nothing in
+/// common.
+fn middle_snake(a: &[usize], b: &[usize]) -> Option<(usize, usize)> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let len = 2 * max_d + 2;
+    // Furthest x reached on each diagonal k = x - y, forward and backward.
+    let mut forward = vec![-1isize; len as usize];
+    let mut backward = vec![-1isize; len as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // The paths meet on a forward step if the difference is odd, and on a
+    // backward one otherwise.
+    let odd = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let i = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[i - 1] < forward[i + 1]) {
+                forward[i + 1]
+            } else {
+                forward[i - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && a[x1 as usize] == b[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[i] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if odd {
+                let j = offset + delta - k1;
+                if j >= 0 && j < len && backward[j as usize] != -1 {
+                    let x2 = n - backward[j as usize];
+                    if x1 >= x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k1 += 2;
+        }
+
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let i = (offset + k2) as usize;
+            let mut x2 = if k2 == -d || (k2 != d && backward[i - 1] < backward[i + 1]) {
+                backward[i + 1]
+            } else {
+                backward[i - 1] + 1
+            };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && a[(n - x2 - 1) as usize] == b[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[i] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !odd {
+                let j = offset + delta - k2;
+                if j >= 0 && j < len && forward[j as usize] != -1 {
+                    let x1 = forward[j as usize];
+                    let y1 = offset + x1 - j;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, Edit, GapString};
+
+
+    #[test]
+    fn diff_1() {
+        let old = buf_from_str("one\ntwo\nthree\nfour\nfive\n");
+        let new = buf_from_str("one\n2\nthree\nfive\nsix\n");
+        let edits = old.diff(&new);
+        let edit = |range, text: &str| Edit { range, text: text.to_string() };
+        assert!(edits == [edit(4..8, "2\n"),
+                          edit(14..19, ""),
+                          edit(24..24, "six\n")]);
+
+        let mut gap_buf = old.clone();
+        gap_buf.apply_edits(&edits);
+        assert!(gap_buf == new);
+        assert!(new.diff(&new).is_empty());
+        assert!(old.diff(&GapString::new()) == [edit(0..24, "")]);
+    }
+
+    #[test]
+    fn diff_2() {
+        // Pseudo-random edits of pseudo-random lines.
+        let mut seed = 11u32;
+        let mut random = |n: u32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % n
+        };
+        for _ in 0..50 {
+            let mut lines: Vec<String> = (0..40).map(|_| format!("é{}\n", random(8))).collect();
+            let old = buf_from_str(&lines.concat());
+            for _ in 0..random(10) {
+                let i = random(lines.len() as u32) as usize;
+                match random(3) {
+                    0 => { lines.remove(i); }
+                    1 => lines.insert(i, format!("{}\n", random(8))),
+                    _ => lines[i] = format!("é{}", random(8))
+                }
+            }
+            let new = buf_from_str(&lines.concat());
+
+            let mut gap_buf = old.clone();
+            gap_buf.apply_edits(&old.diff(&new));
+            assert!(gap_buf == new);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::str;
+use std::io::{self, Read, Write};
+
+use encoding_rs::{EncoderResult, Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use GapString;
+
+
+/// Size of the buffer text is encoded into before writing.
+const ENCODE_BUF_SIZE: usize = 8 * 1024;
+
+
+/// Encoding a buffer's content was loaded from, and is saved back in by
+/// `GapString::write_encoded_to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceEncoding {
+    pub encoding: &'static Encoding,
+    /// Whether the content starts with a byte order mark.
+    pub bom: bool
+}
+
+impl GapString {
+    /// Creates a buffer holding everything read from `reader`, decoded from
+    /// `encoding` or else from the encoding given by its byte order mark,
+    /// UTF-8 if valid, and windows-1252 otherwise.
+    ///
+    /// The byte order mark is left out of the content. The encoding and
+    /// whether there was a byte order mark are recorded, so that
+    /// `write_encoded_to` writes the content back the same way.
+    ///
+    /// # Errors
+    ///
+    /// * If reading fails.
+    /// * If the content is not valid in `encoding`, which would otherwise
+    ///   lose the invalid bytes.
+    pub fn from_reader_with_encoding<R: Read>(mut reader: R,
+                                              encoding: Option<&'static Encoding>)
+        -> io::Result<GapString>
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let bom = Encoding::for_bom(&bytes);
+        let encoding = match (encoding, bom) {
+            (Some(encoding), _) => encoding,
+            (None, Some((encoding, _))) => encoding,
+            (None, None) if str::from_utf8(&bytes).is_ok() => UTF_8,
+            (None, None) => WINDOWS_1252
+        };
+        let has_bom = bom.is_some_and(|(bom_encoding, _)| bom_encoding == encoding);
+        let (text, malformed) = encoding.decode_with_bom_removal(&bytes);
+        if malformed {
+            let msg = format!("Content is not valid {}", encoding.name());
+            return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        }
+
+        let mut buf = GapString::from(text.into_owned());
+        buf.encoding = SourceEncoding { encoding, bom: has_bom };
+        Ok(buf)
+    }
+
+    /// Sets the encoding `write_encoded_to` writes the content in.
+    pub fn set_source_encoding(&mut self, encoding: SourceEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Returns the encoding the content was loaded from, UTF-8 without a byte
+    /// order mark unless loaded by `from_reader_with_encoding` or set by
+    /// `set_source_encoding`.
+    pub fn source_encoding(&self) -> SourceEncoding {
+        self.encoding
+    }
+
+    /// Writes the whole content to `w` in its source encoding, starting with
+    /// a byte order mark if it had one. Every `\n` is written as the line
+    /// ending, as by `write_to`.
+    ///
+    /// The content is encoded a chunk at a time, without copying it whole.
+    ///
+    /// # Errors
+    ///
+    /// * If writing fails.
+    /// * If the content has a char the encoding cannot represent. What was
+    ///   written up to it is left in `w`.
+    pub fn write_encoded_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let SourceEncoding { encoding, bom } = self.encoding;
+        if encoding == UTF_16LE || encoding == UTF_16BE {
+            return self.write_utf16_to(w, encoding == UTF_16LE, bom);
+        }
+        if bom && encoding == UTF_8 {
+            w.write_all(b"\xef\xbb\xbf")?;
+        }
+
+        let mut encoder = encoding.new_encoder();
+        let mut out = vec![0; ENCODE_BUF_SIZE];
+        let chunks = self.output_chunks().map(|chunk| (chunk, false));
+        for (mut chunk, last) in chunks.chain(Some(("", true))) {
+            loop {
+                let (result, read, written) =
+                    encoder.encode_from_utf8_without_replacement(chunk, &mut out, last);
+                w.write_all(&out[..written])?;
+                chunk = &chunk[read..];
+                match result {
+                    EncoderResult::InputEmpty => break,
+                    EncoderResult::OutputFull => {}
+                    EncoderResult::Unmappable(ch) => {
+                        let msg = format!("Cannot encode {:?} in {}", ch, encoding.name());
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the content as UTF-16, which encoding_rs only decodes.
+    fn write_utf16_to<W: Write>(&self, mut w: W, little_endian: bool, bom: bool)
+        -> io::Result<()>
+    {
+        let units = bom.then_some('\u{feff}').into_iter()
+            .chain(self.output_chunks().flat_map(str::chars));
+        let mut out = Vec::with_capacity(ENCODE_BUF_SIZE);
+        let mut pair = [0; 2];
+        for ch in units {
+            for unit in ch.encode_utf16(&mut pair) {
+                let bytes = if little_endian { unit.to_le_bytes() } else { unit.to_be_bytes() };
+                out.extend_from_slice(&bytes);
+            }
+            if out.len() + 4 > ENCODE_BUF_SIZE {
+                w.write_all(&out)?;
+                out.clear();
+            }
+        }
+        w.write_all(&out)
+    }
+}
+
+impl Default for SourceEncoding {
+    fn default() -> SourceEncoding {
+        SourceEncoding { encoding: UTF_8, bom: false }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use GapString;
+    use super::SourceEncoding;
+
+
+    #[test]
+    fn from_reader_with_encoding_1() {
+        let bytes = b"\xff\xfeh\x00\xe9\x00\n\x00";
+        let gap_buf = GapString::from_reader_with_encoding(&bytes[..], None).unwrap();
+        assert!(gap_buf == "hé\n");
+        assert!(gap_buf.source_encoding() == SourceEncoding { encoding: UTF_16LE, bom: true });
+        let mut out = Vec::new();
+        gap_buf.write_encoded_to(&mut out).unwrap();
+        assert!(out == bytes);
+
+        let gap_buf = GapString::from_reader_with_encoding(&b"caf\xe9"[..], None).unwrap();
+        assert!(gap_buf == "café");
+        assert!(gap_buf.source_encoding().encoding == WINDOWS_1252);
+        let gap_buf = GapString::from_reader_with_encoding(&b"\xef\xbb\xbfok"[..], None).unwrap();
+        assert!(gap_buf == "ok");
+        assert!(gap_buf.source_encoding() == SourceEncoding { encoding: UTF_8, bom: true });
+
+        let invalid = GapString::from_reader_with_encoding(&b"\xff"[..], Some(UTF_8));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn write_encoded_to_1() {
+        let mut gap_buf = GapString::from("日本");
+        gap_buf.insert_str(3, "-");
+        gap_buf.set_source_encoding(SourceEncoding { encoding: SHIFT_JIS, bom: false });
+        let mut out = Vec::new();
+        gap_buf.write_encoded_to(&mut out).unwrap();
+        assert!(out == b"\x93\xfa-\x96\x7b");
+
+        gap_buf.set_source_encoding(SourceEncoding { encoding: WINDOWS_1252, bom: false });
+        assert!(gap_buf.write_encoded_to(&mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::{error, fmt, io};
+use std::str::Utf8Error;
+
+use GapBytes;
+
+
+/// Errors returned by the fallible `GapBuffer` operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GapBufferError {
+    /// Allocating memory for the buffer failed.
+    AllocationFailed,
+    /// The operation would grow the buffer beyond its maximum capacity.
+    CapacityExceeded,
+    /// Bytes given as text are not valid UTF-8.
+    InvalidUtf8,
+    /// An offset falls inside a multi-byte char.
+    NotCharBoundary,
+    /// An offset or range lies outside of the content.
+    OutOfBounds,
+    /// The edit touches a protected range.
+    Protected,
+    /// The buffer is read-only.
+    ReadOnly
+}
+
+/// Broken invariant found by `check_invariants`, meaning the buffer is
+/// corrupted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantError {
+    /// The start of the gap, its end and the end of the allocation are not
+    /// in that order after the start of the allocation, or that one is
+    /// null or misaligned.
+    PointerOrder,
+    /// Something wrote into the gap since it was poisoned, only detected
+    /// with the `debug-guards` feature.
+    GapOverwritten,
+    /// Either side of the gap is not valid UTF-8, or the gap splits a char.
+    InvalidUtf8
+}
+
+/// Errors returned by `GapString::apply_op`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OpError {
+    /// The operation is not the one following the revision of the buffer.
+    OutOfOrder,
+    /// The operation applies to content of another length.
+    LengthMismatch,
+    /// One of the edits of the operation cannot be made.
+    Edit(GapBufferError)
+}
+
+/// Errors returned by `GapString::apply_patch` and
+/// `GapString::apply_unified_diff`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// Two edits overlap.
+    Overlapping,
+    /// One of the edits cannot be made.
+    Edit(GapBufferError),
+    /// The unified diff cannot be parsed at the given line, counted from 1.
+    Malformed(usize),
+    /// The lines a hunk keeps or removes are not those of the content, at
+    /// the given line of the unified diff, counted from 1.
+    Mismatch(usize)
+}
+
+/// Error returned by `GapString::restore_history` for a history saved with
+/// other content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StaleHistory;
+
+/// Error returned by `GapString::from_utf8`, holding on to the bytes that
+/// were not valid UTF-8.
+#[derive(Debug)]
+pub struct FromUtf8Error {
+    pub(crate) bytes: GapBytes,
+    pub(crate) error: Utf8Error
+}
+
+impl FromUtf8Error {
+    /// Returns the bytes given to `GapString::from_utf8`.
+    pub fn into_bytes(self) -> GapBytes {
+        self.bytes
+    }
+
+    /// Returns where the content stopped being valid UTF-8.
+    pub fn utf8_error(&self) -> Utf8Error {
+        self.error
+    }
+}
+
+impl fmt::Display for GapBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GapBufferError::AllocationFailed => write!(f, "Allocation failed"),
+            GapBufferError::CapacityExceeded => {
+                write!(f, "Maximum capacity exceeded")
+            }
+            GapBufferError::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+            GapBufferError::NotCharBoundary => {
+                write!(f, "Offset is not on a char boundary")
+            }
+            GapBufferError::OutOfBounds => write!(f, "Offset out of bounds"),
+            GapBufferError::Protected => {
+                write!(f, "Edit overlaps a protected range")
+            }
+            GapBufferError::ReadOnly => write!(f, "Buffer is read-only")
+        }
+    }
+}
+
+impl error::Error for GapBufferError {}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvariantError::PointerOrder => write!(f, "Buffer pointers out of order"),
+            InvariantError::GapOverwritten => write!(f, "Gap overwritten"),
+            InvariantError::InvalidUtf8 => write!(f, "Content is not valid UTF-8")
+        }
+    }
+}
+
+impl error::Error for InvariantError {}
+
+impl fmt::Display for OpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OpError::OutOfOrder => write!(f, "Operation out of order"),
+            OpError::LengthMismatch => write!(f, "Operation does not span the content"),
+            OpError::Edit(ref e) => fmt::Display::fmt(e, f)
+        }
+    }
+}
+
+impl error::Error for OpError {}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchError::Overlapping => write!(f, "Overlapping edits"),
+            PatchError::Edit(ref e) => fmt::Display::fmt(e, f),
+            PatchError::Malformed(line) => write!(f, "Malformed patch at line {}", line),
+            PatchError::Mismatch(line) => {
+                write!(f, "Patch does not match the content at line {}", line)
+            }
+        }
+    }
+}
+
+impl error::Error for PatchError {}
+
+impl fmt::Display for StaleHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Undo history saved with other content")
+    }
+}
+
+impl error::Error for StaleHistory {}
+
+impl fmt::Display for FromUtf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl error::Error for FromUtf8Error {}
+
+impl From<GapBufferError> for io::Error {
+    fn from(err: GapBufferError) -> io::Error {
+        io::Error::other(err)
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+// C interface, declared in `include/gap_buffer.h`.
+//
+// A buffer is an opaque `GapBuf *` owned by the caller, created by
+// `gapbuf_new` and released by `gapbuf_free`. Offsets are byte offsets into
+// UTF-8 content, and fallible functions return a negative `GAPBUF_ERR_*`
+// code instead of panicking.
+
+use std::{ptr, slice};
+
+use {GapString, GapBufferError};
+
+
+// Error codes, the others being those of the `GapBufferError` variants.
+
+/// A pointer argument is NULL.
+pub const GAPBUF_ERR_NULL: isize = -1;
+pub const GAPBUF_ERR_OUT_OF_BOUNDS: isize = -2;
+pub const GAPBUF_ERR_NOT_CHAR_BOUNDARY: isize = -3;
+pub const GAPBUF_ERR_INVALID_UTF8: isize = -4;
+pub const GAPBUF_ERR_CAPACITY_EXCEEDED: isize = -5;
+pub const GAPBUF_ERR_ALLOCATION_FAILED: isize = -6;
+pub const GAPBUF_ERR_PROTECTED: isize = -7;
+pub const GAPBUF_ERR_READ_ONLY: isize = -8;
+/// The output of `gapbuf_read_range` is too small for the range.
+pub const GAPBUF_ERR_BUFFER_TOO_SMALL: isize = -9;
+
+
+/// Creates a new, empty buffer, to be released with `gapbuf_free`.
+#[no_mangle]
+pub extern "C" fn gapbuf_new() -> *mut GapString {
+    Box::into_raw(Box::new(GapString::new()))
+}
+
+/// Releases a buffer created by `gapbuf_new`. Does nothing for NULL.
+///
+/// # Safety
+///
+/// `buf` must be NULL or a buffer from `gapbuf_new` not yet released.
+#[no_mangle]
+pub unsafe extern "C" fn gapbuf_free(buf: *mut GapString) {
+    if !buf.is_null() {
+        drop(Box::from_raw(buf));
+    }
+}
+
+/// Inserts the `len` bytes at `text`, which must be valid UTF-8, at
+/// `offset`. Returns 0, or an error code leaving the buffer unchanged.
+///
+/// # Safety
+///
+/// `buf` must be a live buffer, and `text` must point to `len` readable
+/// bytes or be NULL with `len` 0.
+#[no_mangle]
+pub unsafe extern "C" fn gapbuf_insert(buf: *mut GapString,
+                                       offset: usize,
+                                       text: *const u8,
+                                       len: usize)
+    -> isize
+{
+    let buf = match buf.as_mut() {
+        Some(buf) => buf,
+        None => return GAPBUF_ERR_NULL
+    };
+    let bytes = match bytes_arg(text, len) {
+        Some(bytes) => bytes,
+        None => return GAPBUF_ERR_NULL
+    };
+    status(buf.try_insert_bytes(offset, bytes).map(|_| 0))
+}
+
+/// Returns the length of the content in bytes, or 0 for NULL.
+///
+/// # Safety
+///
+/// `buf` must be NULL or a live buffer.
+#[no_mangle]
+pub unsafe extern "C" fn gapbuf_len(buf: *const GapString) -> usize {
+    buf.as_ref().map_or(0, |buf| buf.len())
+}
+
+/// Copies the content between `start` and `end` into `out`, which holds
+/// `out_len` bytes. Returns the number of bytes copied, or an error code.
+///
+/// # Safety
+///
+/// `buf` must be a live buffer, and `out` must point to `out_len` writable
+/// bytes or be NULL with `out_len` 0.
+#[no_mangle]
+pub unsafe extern "C" fn gapbuf_read_range(buf: *const GapString,
+                                           start: usize,
+                                           end: usize,
+                                           out: *mut u8,
+                                           out_len: usize)
+    -> isize
+{
+    let buf = match buf.as_ref() {
+        Some(buf) => buf,
+        None => return GAPBUF_ERR_NULL
+    };
+    if out.is_null() && out_len > 0 {
+        return GAPBUF_ERR_NULL;
+    }
+    if start > end {
+        return GAPBUF_ERR_OUT_OF_BOUNDS;
+    }
+    if let Err(e) = buf.check_offset(start).and_then(|_| buf.check_offset(end)) {
+        return error_code(e);
+    }
+    if end - start > out_len {
+        return GAPBUF_ERR_BUFFER_TOO_SMALL;
+    }
+
+    // Copies straight from both segments, without joining them first.
+    let (head, tail) = buf.as_slices();
+    let mut written = 0;
+    for (segment_start, segment) in [(0, head), (head.len(), tail)] {
+        let from = start.clamp(segment_start, segment_start + segment.len()) - segment_start;
+        let to = end.clamp(segment_start, segment_start + segment.len()) - segment_start;
+        if from < to {
+            ptr::copy_nonoverlapping(segment[from..to].as_ptr(), out.add(written), to - from);
+            written += to - from;
+        }
+    }
+    written as isize
+}
+
+/// Removes the content between `start` and `end`. Returns 0, or an error
+/// code leaving the buffer unchanged.
+///
+/// # Safety
+///
+/// `buf` must be a live buffer.
+#[no_mangle]
+pub unsafe extern "C" fn gapbuf_remove(buf: *mut GapString, start: usize, end: usize)
+    -> isize
+{
+    match buf.as_mut() {
+        Some(buf) => status(buf.try_remove(start..end).map(|_| 0)),
+        None => GAPBUF_ERR_NULL
+    }
+}
+
+/// Returns the `len` bytes at `ptr`, allowing NULL for none.
+unsafe fn bytes_arg<'a>(ptr: *const u8, len: usize) -> Option<&'a [u8]> {
+    if ptr.is_null() {
+        if len == 0 { Some(&[]) } else { None }
+    } else {
+        Some(slice::from_raw_parts(ptr, len))
+    }
+}
+
+fn status(result: Result<isize, GapBufferError>) -> isize {
+    result.unwrap_or_else(error_code)
+}
+
+fn error_code(error: GapBufferError) -> isize {
+    match error {
+        GapBufferError::OutOfBounds => GAPBUF_ERR_OUT_OF_BOUNDS,
+        GapBufferError::NotCharBoundary => GAPBUF_ERR_NOT_CHAR_BOUNDARY,
+        GapBufferError::InvalidUtf8 => GAPBUF_ERR_INVALID_UTF8,
+        GapBufferError::CapacityExceeded => GAPBUF_ERR_CAPACITY_EXCEEDED,
+        GapBufferError::AllocationFailed => GAPBUF_ERR_ALLOCATION_FAILED,
+        GapBufferError::Protected => GAPBUF_ERR_PROTECTED,
+        GapBufferError::ReadOnly => GAPBUF_ERR_READ_ONLY
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+
+    #[test]
+    fn gapbuf_insert_1() {
+        unsafe {
+            let buf = gapbuf_new();
+            assert!(gapbuf_insert(buf, 0, "héllo".as_ptr(), 6) == 0);
+            assert!(gapbuf_insert(buf, 6, " world".as_ptr(), 6) == 0);
+            assert!(gapbuf_insert(buf, 2, "x".as_ptr(), 1) == GAPBUF_ERR_NOT_CHAR_BOUNDARY);
+            assert!(gapbuf_insert(buf, 13, "x".as_ptr(), 1) == GAPBUF_ERR_OUT_OF_BOUNDS);
+            assert!(gapbuf_insert(buf, 0, b"\xff".as_ptr(), 1) == GAPBUF_ERR_INVALID_UTF8);
+            assert!(gapbuf_insert(buf, 0, ptr::null(), 0) == 0);
+            assert!(gapbuf_insert(ptr::null_mut(), 0, ptr::null(), 0) == GAPBUF_ERR_NULL);
+            assert!(gapbuf_len(buf) == 12);
+            gapbuf_free(buf);
+        }
+    }
+
+    #[test]
+    fn gapbuf_read_range_1() {
+        unsafe {
+            let buf = gapbuf_new();
+            gapbuf_insert(buf, 0, "hello world".as_ptr(), 11);
+            gapbuf_insert(buf, 5, ",".as_ptr(), 1);
+            assert!(gapbuf_remove(buf, 0, 1) == 0);
+            assert!(gapbuf_remove(buf, 3, 1) == GAPBUF_ERR_OUT_OF_BOUNDS);
+
+            let mut out = [0u8; 16];
+            let written = gapbuf_read_range(buf, 0, 11, out.as_mut_ptr(), out.len());
+            assert!(&out[..written as usize] == b"ello, world");
+            let written = gapbuf_read_range(buf, 2, 6, out.as_mut_ptr(), out.len());
+            assert!(&out[..written as usize] == b"lo, ");
+            assert!(gapbuf_read_range(buf, 0, 11, out.as_mut_ptr(), 4) == GAPBUF_ERR_BUFFER_TOO_SMALL);
+            assert!(gapbuf_read_range(buf, 0, 0, ptr::null_mut(), 0) == 0);
+            gapbuf_free(buf);
+        }
+    }
+}
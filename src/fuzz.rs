@@ -0,0 +1,204 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use GapString;
+
+
+/// Largest gap of a generated buffer.
+const MAX_GAP: usize = 64;
+
+/// Largest number of edits of a generated `FuzzCase`.
+const MAX_OPS: usize = 256;
+
+
+/// Buffer and edits to apply to it, generated by `Arbitrary` for fuzz
+/// targets.
+///
+/// Every edit is valid for the content left by the ones before it, so a
+/// target only has to run them:
+///
+/// ```ignore
+/// fuzz_target!(|case: FuzzCase| {
+///     case.run();
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct FuzzCase {
+    pub buffer: GapString,
+    pub ops: Vec<FuzzOp>
+}
+
+/// Edit of a `FuzzCase`, its offsets being on char boundaries of the
+/// content it applies to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FuzzOp {
+    Insert(usize, String),
+    /// Removes a non-empty range.
+    Remove(Range<usize>),
+    Replace(Range<usize>, String),
+    /// Moves the gap to an offset, leaving the content as it is.
+    MoveGap(usize)
+}
+
+impl FuzzCase {
+    /// Applies the edits to the buffer and returns it, checking after every
+    /// edit that the buffer is intact and its content is that of a `String`
+    /// edited the same way.
+    ///
+    /// # Panics
+    ///
+    /// * If `check_invariants` fails or the content differs from the
+    ///   `String`.
+    /// * If an edit panics.
+    pub fn run(mut self) -> GapString {
+        let mut expected = self.buffer.to_string();
+        for op in &self.ops {
+            op.apply(&mut self.buffer);
+            op.apply_to_string(&mut expected);
+            if let Err(e) = self.buffer.check_invariants() {
+                panic!("{} after {:?}", e, op);
+            }
+            assert!(self.buffer == expected.as_str(), "Content differs after {:?}", op);
+        }
+        self.buffer
+    }
+}
+
+impl FuzzOp {
+    /// Applies the edit to `buf`.
+    ///
+    /// # Panics
+    ///
+    /// * Same as the `GapString` method making the edit.
+    pub fn apply(&self, buf: &mut GapString) {
+        match *self {
+            FuzzOp::Insert(offset, ref s) => { buf.insert_str(offset, s); }
+            FuzzOp::Remove(ref range) => { buf.remove(range.clone()); }
+            FuzzOp::Replace(ref range, ref s) => { buf.replace_range(range.clone(), s); }
+            FuzzOp::MoveGap(offset) => { buf.cursor(offset); }
+        }
+    }
+
+    fn apply_to_string(&self, content: &mut String) {
+        match *self {
+            FuzzOp::Insert(offset, ref s) => content.insert_str(offset, s),
+            FuzzOp::Remove(ref range) => content.replace_range(range.clone(), ""),
+            FuzzOp::Replace(ref range, ref s) => content.replace_range(range.clone(), s),
+            FuzzOp::MoveGap(_) => {}
+        }
+    }
+
+    /// Generates an edit valid for `content`.
+    fn generate(u: &mut Unstructured<'_>, content: &str) -> Result<FuzzOp> {
+        let op = match u.int_in_range(0..=3)? {
+            0 => FuzzOp::Insert(floor_boundary(u, content, 0)?, String::arbitrary(u)?),
+            1 if !content.is_empty() => {
+                let start = floor_char_boundary(content, u.int_in_range(0..=content.len() - 1)?);
+                FuzzOp::Remove(start..ceil_boundary(u, content, start + 1)?)
+            }
+            2 => {
+                let start = floor_boundary(u, content, 0)?;
+                let end = floor_boundary(u, content, start)?;
+                FuzzOp::Replace(start..end, String::arbitrary(u)?)
+            }
+            _ => FuzzOp::MoveGap(floor_boundary(u, content, 0)?)
+        };
+        Ok(op)
+    }
+}
+
+impl<'a> Arbitrary<'a> for GapString {
+    /// Generates arbitrary content with a gap of up to 64 bytes somewhere
+    /// in it.
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<GapString> {
+        let content = <&str>::arbitrary(u)?;
+        let gap = u.int_in_range(0..=MAX_GAP)?;
+        let offset = floor_boundary(u, content, 0)?;
+        Ok(GapString::with_gap_capacity(content, gap, offset))
+    }
+}
+
+impl<'a> Arbitrary<'a> for FuzzCase {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<FuzzCase> {
+        let buffer = GapString::arbitrary(u)?;
+        let mut content = buffer.to_string();
+        let mut ops = Vec::new();
+        for _ in 0..u.int_in_range(0..=MAX_OPS)? {
+            let op = FuzzOp::generate(u, &content)?;
+            op.apply_to_string(&mut content);
+            ops.push(op);
+        }
+        Ok(FuzzCase { buffer, ops })
+    }
+}
+
+/// Returns a char boundary of `s` at or after `min`, which must be one.
+fn floor_boundary(u: &mut Unstructured<'_>, s: &str, min: usize) -> Result<usize> {
+    let offset = u.int_in_range(min..=s.len())?;
+    Ok(floor_char_boundary(s, offset))
+}
+
+/// Returns a char boundary of `s` at or after `min`, which must be in
+/// bounds.
+fn ceil_boundary(u: &mut Unstructured<'_>, s: &str, min: usize) -> Result<usize> {
+    let mut offset = u.int_in_range(min..=s.len())?;
+    while !s.is_char_boundary(offset) {
+        offset += 1;
+    }
+    Ok(offset)
+}
+
+fn floor_char_boundary(s: &str, mut offset: usize) -> usize {
+    while !s.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use super::{FuzzCase, FuzzOp};
+
+
+    #[test]
+    fn apply_1() {
+        let case = FuzzCase::arbitrary(&mut Unstructured::new(&[])).unwrap();
+        assert!(case.buffer.is_empty() && case.ops.is_empty());
+
+        let mut buf = case.run();
+        FuzzOp::Insert(0, "héllo".to_string()).apply(&mut buf);
+        FuzzOp::Replace(1..3, "e".to_string()).apply(&mut buf);
+        FuzzOp::MoveGap(2).apply(&mut buf);
+        FuzzOp::Remove(0..1).apply(&mut buf);
+        assert!(buf == "ello");
+    }
+
+    #[test]
+    fn arbitrary_1() {
+        // Pseudo-random input, as a fuzzer would provide.
+        let mut seed = 7u32;
+        for _ in 0..50 {
+            let data: Vec<u8> = (0..2048).map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            }).collect();
+            let case = FuzzCase::arbitrary(&mut Unstructured::new(&data)).unwrap();
+            let expected = case.ops.iter().fold(case.buffer.to_string(), |mut s, op| {
+                op.apply_to_string(&mut s);
+                s
+            });
+            assert!(case.run() == expected.as_str());
+        }
+    }
+}
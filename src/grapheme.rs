@@ -0,0 +1,226 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::borrow::Cow;
+
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+use GapString;
+
+
+/// Iterator over the extended grapheme clusters of a `GapString`.
+///
+/// Created by [`GapString::graphemes`](struct.GapString.html#method.graphemes).
+pub struct Graphemes<'a> {
+    buf: &'a GapString,
+    offset: usize
+}
+
+impl GapString {
+    /// Returns `offset` if it is a grapheme cluster boundary, the next
+    /// boundary after it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn ceil_grapheme_boundary(&self, offset: usize) -> usize {
+        if self.is_grapheme_boundary(offset) {
+            offset
+        } else {
+            self.next_grapheme_boundary(offset)
+        }
+    }
+
+    /// Returns `offset` if it is a grapheme cluster boundary, the last
+    /// boundary before it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn floor_grapheme_boundary(&self, offset: usize) -> usize {
+        if self.is_grapheme_boundary(offset) {
+            offset
+        } else {
+            self.prev_grapheme_boundary(offset)
+        }
+    }
+
+    /// Returns an iterator over the extended grapheme clusters of the buffer.
+    ///
+    /// Clusters are borrowed from the buffer unless they span the gap.
+    pub fn graphemes(&self) -> Graphemes<'_> {
+        Graphemes {
+            buf: self,
+            offset: 0
+        }
+    }
+
+    /// Returns `true` if `offset` is between two extended grapheme clusters,
+    /// or at the start or end of the content.
+    ///
+    /// Only the chars around `offset` are looked at, on both sides of the
+    /// gap if needed.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn is_grapheme_boundary(&self, offset: usize) -> bool {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        self.with_grapheme_cursor(offset, false, |cursor, chunk, chunk_start| {
+            cursor.is_boundary(chunk, chunk_start)
+        })
+    }
+
+    /// Returns the first grapheme cluster boundary after `offset`, or the
+    /// end of the content if there is none, such as for moving the cursor
+    /// one user-perceived character to the right.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn next_grapheme_boundary(&self, offset: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        self.with_grapheme_cursor(offset, false, |cursor, chunk, chunk_start| {
+            cursor.next_boundary(chunk, chunk_start)
+        }).unwrap_or(offset)
+    }
+
+    /// Returns the last grapheme cluster boundary before `offset`, or 0 if
+    /// there is none, such as for deleting one user-perceived character
+    /// backwards.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn prev_grapheme_boundary(&self, offset: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        self.with_grapheme_cursor(offset, true, |cursor, chunk, chunk_start| {
+            cursor.prev_boundary(chunk, chunk_start)
+        }).unwrap_or(offset)
+    }
+
+    /// Runs `op` on a grapheme cursor at `offset`, feeding it the head or
+    /// the tail as it asks for them.
+    ///
+    /// An `offset` right at the gap starts in the head if `backward` is set,
+    /// in the tail otherwise.
+    fn with_grapheme_cursor<T, F>(&self, offset: usize, backward: bool, mut op: F) -> T
+        where F: FnMut(&mut GraphemeCursor, &str, usize) -> Result<T, GraphemeIncomplete>
+    {
+        let (head, tail) = self.as_strs();
+        let mut cursor = GraphemeCursor::new(offset, self.len(), true);
+
+        let mut in_tail = if backward { offset > head.len() } else { offset >= head.len() };
+        loop {
+            let (chunk, chunk_start) = if in_tail {
+                (tail, head.len())
+            } else {
+                (head, 0)
+            };
+
+            match op(&mut cursor, chunk, chunk_start) {
+                Ok(result) => return result,
+                Err(GraphemeIncomplete::NextChunk) => in_tail = true,
+                Err(GraphemeIncomplete::PrevChunk) => in_tail = false,
+                Err(GraphemeIncomplete::PreContext(n)) if n <= head.len() => {
+                    cursor.provide_context(&head[..n], 0);
+                }
+                Err(GraphemeIncomplete::PreContext(n)) => {
+                    cursor.provide_context(&tail[..n - head.len()], head.len());
+                }
+                Err(_) => unreachable!()
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Graphemes<'a> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        let start = self.offset;
+        if start >= self.buf.len() {
+            return None;
+        }
+
+        // A fresh cursor per cluster, a reused one can carry stale regional
+        // indicator state over from the previous chunk.
+        let end = self.buf.next_grapheme_boundary(start);
+        self.offset = end;
+        Some(self.buf.slice(start..end))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+
+
+    #[test]
+    fn graphemes_1() {
+        let mut gap_buf = buf_from_str("ae\u{301}🇩🇪!");
+        gap_buf.insert_str(0, "");
+
+        let graphemes: Vec<String> = gap_buf.graphemes()
+            .map(|g| g.into_owned())
+            .collect();
+        assert!(graphemes == ["a", "e\u{301}", "🇩🇪", "!"]);
+    }
+
+    #[test]
+    fn graphemes_2() {
+        let mut gap_buf = buf_from_str("xe\u{301}🇩🇪");
+        for &offset in &[2, 8] {
+            gap_buf.insert_str(offset, "");
+
+            let graphemes: Vec<String> = gap_buf.graphemes()
+                .map(|g| g.into_owned())
+                .collect();
+            assert!(graphemes == ["x", "e\u{301}", "🇩🇪"]);
+        }
+    }
+
+    #[test]
+    fn next_grapheme_boundary_1() {
+        let mut gap_buf = buf_from_str("ae\u{301}🇩🇪🇫🇷!");
+        for gap in [0, 1, 2, 4, 12, 16, 20] {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.next_grapheme_boundary(1) == 4);
+            assert!(gap_buf.next_grapheme_boundary(4) == 12);
+            assert!(gap_buf.next_grapheme_boundary(12) == 20);
+            assert!(gap_buf.next_grapheme_boundary(21) == 21);
+            assert!(gap_buf.prev_grapheme_boundary(20) == 12);
+            assert!(gap_buf.prev_grapheme_boundary(12) == 4);
+            assert!(gap_buf.prev_grapheme_boundary(4) == 1);
+            assert!(gap_buf.prev_grapheme_boundary(0) == 0);
+        }
+    }
+
+    #[test]
+    fn is_grapheme_boundary_1() {
+        let mut gap_buf = buf_from_str("ae\u{301}🇩🇪🇫🇷!");
+        for gap in [0, 2, 8, 16, 21] {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.is_grapheme_boundary(0));
+            assert!(!gap_buf.is_grapheme_boundary(2));
+            assert!(!gap_buf.is_grapheme_boundary(8));
+            assert!(gap_buf.is_grapheme_boundary(12));
+            assert!(!gap_buf.is_grapheme_boundary(16));
+            assert!(gap_buf.is_grapheme_boundary(21));
+            assert!(gap_buf.floor_grapheme_boundary(16) == 12);
+            assert!(gap_buf.ceil_grapheme_boundary(16) == 20);
+            assert!(gap_buf.ceil_grapheme_boundary(12) == 12);
+        }
+    }
+}
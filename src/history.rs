@@ -0,0 +1,462 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::collections::VecDeque;
+use std::mem;
+use std::ops::Range;
+
+use {GapString, GapBufferError, StaleHistory};
+
+
+/// Undo and redo stacks of a `GapString`, see `enable_history`.
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    undo: VecDeque<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    /// Number of undo steps kept, 0 while the history is disabled.
+    depth: usize,
+    /// Offset and removed text of the edit in progress.
+    pending: Option<(usize, String)>,
+    /// Edits of the open transaction, see `begin_transaction`.
+    pub(crate) transaction: Option<Vec<Change>>
+}
+
+/// Undo and redo steps of a buffer, saved by `GapString::save_history` to
+/// be restored once the buffer is loaded again, like Vim's undo files.
+///
+/// With the `serde` feature it can be serialized, such as next to the file.
+/// It holds a checksum of the content it was saved with, so that it is not
+/// restored onto content changed in between.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UndoHistory {
+    undo: Vec<Vec<Change>>,
+    redo: Vec<Vec<Change>>,
+    depth: usize,
+    len: usize,
+    checksum: u64
+}
+
+/// `removed` replaced by `inserted` at `offset`, an offset into the
+/// committed content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Change {
+    offset: usize,
+    removed: String,
+    inserted: String
+}
+
+impl GapString {
+    /// Returns `true` if there is an edit to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    /// Returns `true` if there is an edit to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    /// Forgets all recorded edits, keeping the history enabled.
+    pub fn clear_history(&mut self) {
+        self.history.undo.clear();
+        self.history.redo.clear();
+    }
+
+    /// Stops recording edits and forgets the recorded ones.
+    pub fn disable_history(&mut self) {
+        self.enable_history(0);
+        self.clear_history();
+    }
+
+    /// Starts recording edits so they can be undone, keeping the last
+    /// `depth` of them. Changes the depth if the history is already enabled,
+    /// a `depth` of 0 disables it.
+    ///
+    /// Every edit is recorded, including `clear`, `truncate` and the like.
+    /// Preedit text is recorded once committed, and `undo` and `redo` clear
+    /// any preedit text before they apply.
+    pub fn enable_history(&mut self, depth: usize) {
+        self.history.depth = depth;
+        self.history.trim();
+    }
+
+    /// Re-applies the last undone edit. Returns `false` if there is none.
+    ///
+    /// # Panics
+    ///
+    /// * If a transaction is open.
+    /// * If the buffer is read-only or the edit touches a protected range.
+    pub fn redo(&mut self) -> bool {
+        assert!(self.history.transaction.is_none(), "Redo within a transaction");
+        let edits = match self.history.redo.pop() {
+            Some(edits) => edits,
+            None => return false
+        };
+
+        let result = self.apply_unrecorded(&edits, false);
+        if let Err(e) = result {
+            self.history.redo.push(edits);
+            panic!("{}", e);
+        }
+        self.history.undo.push_back(edits);
+        true
+    }
+
+    /// Replaces the recorded edits and the depth of the history by those of
+    /// `history`, saved from a buffer with the same committed content.
+    ///
+    /// # Errors
+    ///
+    /// * `StaleHistory` if the committed content is not the one `history`
+    ///   was saved with, leaving the history unchanged.
+    pub fn restore_history(&mut self, history: UndoHistory)
+        -> Result<(), StaleHistory>
+    {
+        let content = self.committed_string();
+        if content.len() != history.len || checksum(&content) != history.checksum {
+            return Err(StaleHistory);
+        }
+
+        self.history.undo = history.undo.into();
+        self.history.redo = history.redo;
+        self.history.depth = history.depth;
+        self.history.trim();
+        Ok(())
+    }
+
+    /// Returns the recorded edits and the depth of the history, to restore
+    /// them with `restore_history` after the buffer is loaded again.
+    ///
+    /// Takes O(n) time to compute a checksum of the committed content. The
+    /// edits of an open transaction are left out.
+    pub fn save_history(&self) -> UndoHistory {
+        let content = self.committed_string();
+        UndoHistory {
+            undo: self.history.undo.iter().cloned().collect(),
+            redo: self.history.redo.clone(),
+            depth: self.history.depth,
+            len: content.len(),
+            checksum: checksum(&content)
+        }
+    }
+
+    /// Reverts the last recorded edit. Returns `false` if there is none.
+    ///
+    /// # Panics
+    ///
+    /// * If a transaction is open.
+    /// * If the buffer is read-only or the edit touches a protected range.
+    pub fn undo(&mut self) -> bool {
+        assert!(self.history.transaction.is_none(), "Undo within a transaction");
+        let edits = match self.history.undo.pop_back() {
+            Some(edits) => edits,
+            None => return false
+        };
+
+        let result = self.apply_unrecorded(&edits, true);
+        if let Err(e) = result {
+            self.history.undo.push_back(edits);
+            panic!("{}", e);
+        }
+        self.history.redo.push(edits);
+        true
+    }
+
+    /// Applies `edits`, or reverts them if `revert` is set, after clearing
+    /// any preedit text and without recording anything.
+    ///
+    /// All of the edits are checked and room is reserved for them first, so
+    /// that none is applied on error.
+    pub(crate) fn apply_unrecorded(&mut self, edits: &[Change], revert: bool)
+        -> Result<(), GapBufferError>
+    {
+        let steps: Vec<(usize, &str, &str)> = if revert {
+            edits.iter()
+                .rev()
+                .map(|edit| (edit.offset, &edit.inserted[..], &edit.removed[..]))
+                .collect()
+        } else {
+            edits.iter()
+                .map(|edit| (edit.offset, &edit.removed[..], &edit.inserted[..]))
+                .collect()
+        };
+
+        self.unrecorded(|buf| {
+            buf.clear_preedit();
+            buf.check_writable()?;
+            buf.check_protected_all(steps.iter()
+                .map(|&(offset, removed, inserted)| (offset, removed.len(), inserted.len())))?;
+            let growth = steps.iter()
+                .map(|&(_, removed, inserted)| inserted.len().saturating_sub(removed.len()))
+                .sum();
+            buf.raw.try_reserve(growth)?;
+
+            for &(offset, removed, inserted) in &steps {
+                buf.try_replace_range(offset..offset + removed.len(), inserted)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Records the committed preedit text within `range` as inserted.
+    pub(crate) fn record_commit(&mut self, range: Range<usize>) {
+        if !self.history.is_recording() {
+            return;
+        }
+
+        let text = self.slice_unchecked(range.clone()).into_owned();
+        self.history.record(Change { offset: range.start, removed: String::new(), inserted: text });
+    }
+
+    /// Records the edit saved by `save_removed` once `inserted` bytes were
+    /// inserted at `offset`.
+    pub(crate) fn record_edit(&mut self, offset: usize, inserted: usize) {
+        let (committed, removed) = match self.history.pending.take() {
+            Some(pending) => pending,
+            None => return
+        };
+
+        let text = self.slice_unchecked(offset..offset + inserted).into_owned();
+        self.history.record(Change { offset: committed, removed, inserted: text });
+    }
+
+    /// Reverts `edits` without recording them and without the read-only and
+    /// protection checks they already passed.
+    pub(crate) fn revert_unchecked(&mut self, edits: &[Change]) {
+        self.unrecorded(|buf| {
+            buf.clear_preedit();
+            for edit in edits.iter().rev() {
+                let range = edit.offset..edit.offset + edit.inserted.len();
+                buf.replace_raw(range, &edit.removed).unwrap_or_else(|e| panic!("{}", e));
+            }
+        });
+    }
+
+    /// Saves the text about to be replaced at `offset` for the history.
+    pub(crate) fn save_removed(&mut self, offset: usize, removed: usize) {
+        if !self.history.is_recording() {
+            return;
+        }
+
+        let text = self.slice_unchecked(offset..offset + removed).into_owned();
+        self.history.pending = Some((self.committed_offset(offset), text));
+    }
+
+    /// Runs `f` without recording its edits.
+    pub(crate) fn unrecorded<F, R>(&mut self, f: F) -> R
+        where F: FnOnce(&mut GapString) -> R
+    {
+        let history = mem::take(&mut self.history);
+        let result = f(self);
+        self.history = history;
+        result
+    }
+}
+
+impl History {
+    /// Returns the number of bytes allocated for the recorded edits.
+    pub(crate) fn heap_size(&self) -> usize {
+        let steps = self.undo.iter().chain(&self.redo).chain(&self.transaction);
+        let changes: usize = steps.map(|edits| {
+            let text: usize = edits.iter()
+                .map(|edit| edit.removed.capacity() + edit.inserted.capacity())
+                .sum();
+            edits.capacity() * mem::size_of::<Change>() + text
+        }).sum();
+        let pending = self.pending.as_ref().map_or(0, |(_, removed)| removed.capacity());
+        (self.undo.capacity() + self.redo.capacity()) * mem::size_of::<Vec<Change>>()
+            + changes
+            + pending
+    }
+
+    /// Adds `edits` to the undo stack as a single step.
+    pub(crate) fn push(&mut self, edits: Vec<Change>) {
+        if edits.is_empty() || self.depth == 0 {
+            return;
+        }
+
+        self.redo.clear();
+        self.undo.push_back(edits);
+        self.trim();
+    }
+
+    fn is_recording(&self) -> bool {
+        self.depth > 0 || self.transaction.is_some()
+    }
+
+    fn record(&mut self, edit: Change) {
+        if edit.removed.is_empty() && edit.inserted.is_empty() {
+            return;
+        }
+
+        match self.transaction {
+            Some(ref mut edits) => edits.push(edit),
+            None => self.push(vec![edit])
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+}
+
+/// Returns the 64-bit FNV-1a hash of `s`, which unlike `Hash` is stable
+/// across platforms and releases.
+fn checksum(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Clone for History {
+    /// Copies the undo and redo stacks, leaving out any open transaction.
+    fn clone(&self) -> History {
+        History {
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+            depth: self.depth,
+            pending: None,
+            transaction: None
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use {buf_from_str, StaleHistory};
+
+
+    #[test]
+    fn undo_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(10);
+        gap_buf.insert_str(4, "-");
+        gap_buf.remove(0..2);
+        gap_buf.replace_range(0..2, "abc");
+        assert!(gap_buf == "abc-5678");
+
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "34-5678");
+        assert!(gap_buf.undo());
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "12345678");
+        assert!(!gap_buf.undo());
+
+        assert!(gap_buf.redo());
+        assert!(gap_buf.redo());
+        assert!(gap_buf == "34-5678");
+        gap_buf.push_str("9");
+        assert!(!gap_buf.can_redo());
+        assert!(gap_buf == "34-56789");
+    }
+
+    #[test]
+    fn undo_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(2);
+        gap_buf.truncate(6);
+        gap_buf.clear();
+        gap_buf.push_str("abc");
+        assert!(gap_buf.undo());
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "123456");
+        assert!(!gap_buf.undo());
+    }
+
+    #[test]
+    fn undo_3() {
+        let mut gap_buf = buf_from_str("abc");
+        gap_buf.enable_history(10);
+        gap_buf.set_preedit(1, "か");
+        gap_buf.push_str("d");
+        gap_buf.set_preedit(1, "かな");
+        gap_buf.commit_preedit();
+        gap_buf.set_preedit(0, "x");
+        assert!(gap_buf == "xaかなbcd");
+
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "abcd");
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "abc");
+        assert!(gap_buf.redo());
+        assert!(gap_buf.redo());
+        assert!(gap_buf == "aかなbcd");
+    }
+
+    #[test]
+    fn undo_4() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(10);
+        let mut transaction = gap_buf.begin_transaction();
+        transaction.insert_str(0, "a");
+        transaction.insert_str(5, "b");
+        transaction.commit();
+        gap_buf.protect(0..2);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            gap_buf.undo();
+        }));
+        assert!(result.is_err());
+        assert!(gap_buf == "a1234b5678");
+        assert!(gap_buf.can_undo());
+    }
+
+    #[test]
+    fn save_history_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(10);
+        gap_buf.insert_str(4, "-");
+        gap_buf.remove(0..2);
+        gap_buf.undo();
+        let history = gap_buf.save_history();
+
+        let mut reloaded = buf_from_str("12345678");
+        assert!(reloaded.restore_history(history.clone()) == Err(StaleHistory));
+        assert!(!reloaded.can_undo());
+        let mut reloaded = buf_from_str("1234-5678");
+        reloaded.restore_history(history).unwrap();
+        assert!(reloaded.redo());
+        assert!(reloaded == "34-5678");
+        assert!(reloaded.undo() && reloaded.undo());
+        assert!(reloaded == "12345678");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_1() {
+        use serde_test::{assert_tokens, Token};
+
+        let mut gap_buf = buf_from_str("ab");
+        gap_buf.enable_history(10);
+        gap_buf.replace_range(0..1, "c");
+        assert_tokens(&gap_buf.save_history(), &[
+            Token::Struct { name: "UndoHistory", len: 5 },
+            Token::Str("undo"),
+            Token::Seq { len: Some(1) },
+            Token::Seq { len: Some(1) },
+            Token::Struct { name: "Change", len: 3 },
+            Token::Str("offset"), Token::U64(0),
+            Token::Str("removed"), Token::Str("a"),
+            Token::Str("inserted"), Token::Str("c"),
+            Token::StructEnd,
+            Token::SeqEnd,
+            Token::SeqEnd,
+            Token::Str("redo"), Token::Seq { len: Some(0) }, Token::SeqEnd,
+            Token::Str("depth"), Token::U64(10),
+            Token::Str("len"), Token::U64(2),
+            Token::Str("checksum"), Token::U64(0x08a2_4c07_b54a_11b0),
+            Token::StructEnd
+        ]);
+    }
+}
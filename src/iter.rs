@@ -0,0 +1,350 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::char;
+use std::iter::Rev;
+
+use GapString;
+use buffer::Iter;
+
+
+/// Iterator over the bytes of a `GapString`.
+///
+/// Created by [`GapString::bytes`](struct.GapString.html#method.bytes).
+#[derive(Clone)]
+pub struct Bytes<'a> {
+    inner: Iter<'a, u8>
+}
+
+/// Iterator over the chars of a `GapString`.
+///
+/// Created by [`GapString::chars`](struct.GapString.html#method.chars).
+#[derive(Clone)]
+pub struct Chars<'a> {
+    head: &'a [u8],
+    tail: &'a [u8],
+    front: usize,
+    back: usize
+}
+
+/// Iterator over the chars of a `GapString` and their offsets.
+///
+/// Created by [`GapString::char_indices`](struct.GapString.html#method.char_indices).
+#[derive(Clone)]
+pub struct CharIndices<'a> {
+    chars: Chars<'a>
+}
+
+/// Iterator over the contiguous pieces of the content of a `GapString`, in
+/// order and without copying.
+///
+/// Created by [`GapString::chunks`](struct.GapString.html#method.chunks).
+#[derive(Clone)]
+pub struct Chunks<'a> {
+    front: Option<&'a str>,
+    back: Option<&'a str>
+}
+
+impl GapString {
+    /// Returns an iterator over the bytes of the buffer, in both directions.
+    pub fn bytes(&self) -> Bytes<'_> {
+        Bytes { inner: self.raw.iter() }
+    }
+
+    /// Returns an iterator over the chars of the buffer along with their
+    /// byte offsets.
+    ///
+    /// The offsets are logical offsets into the content, as taken by
+    /// `insert_str` and `remove`, regardless of where the gap is.
+    pub fn char_indices(&self) -> CharIndices<'_> {
+        CharIndices { chars: self.chars() }
+    }
+
+    /// Returns an iterator over the chars starting at `offset`.
+    ///
+    /// Only the chars reached are decoded, so scanning forward from a cursor
+    /// takes time proportional to the distance covered.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn chars_at(&self, offset: usize) -> Chars<'_> {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        let mut chars = self.chars();
+        chars.front = offset;
+        chars
+    }
+
+    /// Returns an iterator over the chars ending at `offset`, from the
+    /// nearest one backwards.
+    ///
+    /// Decodes only the chars reached, like `chars_at`.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn chars_before(&self, offset: usize) -> Rev<Chars<'_>> {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        let mut chars = self.chars();
+        chars.back = offset;
+        chars.rev()
+    }
+
+    /// Returns an iterator over the chars of the buffer, decoding the head
+    /// and the tail segment in place.
+    ///
+    /// The iterator is double-ended, so scanning backwards from the end
+    /// with `rev` decodes only the chars it reaches.
+    pub fn chars(&self) -> Chars<'_> {
+        let (head, tail) = self.as_slices();
+        Chars {
+            head,
+            tail,
+            front: 0,
+            back: head.len() + tail.len()
+        }
+    }
+
+    /// Returns an iterator over the content as `&str` chunks, for consumers
+    /// that can take the content piece by piece.
+    ///
+    /// Yields the head and the tail segment, skipping either one if empty.
+    /// The chunks always split the content on char boundaries, but their
+    /// number and length are not otherwise specified.
+    pub fn chunks(&self) -> Chunks<'_> {
+        let (head, tail) = self.as_strs();
+        Chunks {
+            front: Some(head).filter(|s| !s.is_empty()),
+            back: Some(tail).filter(|s| !s.is_empty())
+        }
+    }
+}
+
+impl<'a> Chars<'a> {
+    fn byte(&self, offset: usize) -> u8 {
+        if offset < self.head.len() {
+            self.head[offset]
+        } else {
+            self.tail[offset - self.head.len()]
+        }
+    }
+
+    /// Decodes the char starting at `offset`, returning it and its width.
+    fn decode(&self, offset: usize) -> (char, usize) {
+        let first = self.byte(offset);
+        let (width, mut code) = match first {
+            0x00..=0x7f => (1, first as u32),
+            0xc0..=0xdf => (2, (first & 0x1f) as u32),
+            0xe0..=0xef => (3, (first & 0x0f) as u32),
+            _ => (4, (first & 0x07) as u32)
+        };
+        for i in 1..width {
+            code = (code << 6) | (self.byte(offset + i) & 0x3f) as u32;
+        }
+
+        (char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER), width)
+    }
+}
+
+impl<'a> Iterator for Bytes<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        self.inner.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Bytes<'a> {
+    fn next_back(&mut self) -> Option<u8> {
+        self.inner.next_back().cloned()
+    }
+}
+
+impl<'a> ExactSizeIterator for Bytes<'a> {}
+
+impl<'a> Iterator for Chars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.front >= self.back {
+            return None;
+        }
+
+        let (ch, width) = self.decode(self.front);
+        self.front += width;
+        Some(ch)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len.div_ceil(4), Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Chars<'a> {
+    fn next_back(&mut self) -> Option<char> {
+        if self.front >= self.back {
+            return None;
+        }
+
+        self.back -= 1;
+        while self.byte(self.back) & 0xc0 == 0x80 {
+            self.back -= 1;
+        }
+        Some(self.decode(self.back).0)
+    }
+}
+
+impl<'a> Iterator for CharIndices<'a> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        let offset = self.chars.front;
+        self.chars.next().map(|ch| (offset, ch))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chars.size_hint()
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.front.take().or_else(|| self.back.take())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.iter().count() + self.back.iter().count();
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Chunks<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        self.back.take().or_else(|| self.front.take())
+    }
+}
+
+impl<'a> ExactSizeIterator for Chunks<'a> {}
+
+impl<'a> DoubleEndedIterator for CharIndices<'a> {
+    fn next_back(&mut self) -> Option<(usize, char)> {
+        let ch = self.chars.next_back()?;
+        Some((self.chars.back, ch))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, GapString};
+
+
+    #[test]
+    fn chars_1() {
+        let mut gap_buf = buf_from_str("añ€😀");
+        gap_buf.insert_str(3, "b");
+
+        let chars: Vec<char> = gap_buf.chars().collect();
+        assert!(chars == ['a', 'ñ', 'b', '€', '😀']);
+    }
+
+    #[test]
+    fn chars_2() {
+        let mut gap_buf = buf_from_str("a€b");
+        gap_buf.cursor(4);
+        assert!(gap_buf.as_strs() == ("a€", "b"));
+
+        let text: String = gap_buf.chars().collect();
+        assert!(text == "a€b");
+        assert!(GapString::new().chars().next().is_none());
+    }
+
+    #[test]
+    fn chars_3() {
+        let mut gap_buf = buf_from_str("añ€😀");
+        gap_buf.insert_str(3, "b");
+
+        let chars: Vec<char> = gap_buf.chars().rev().collect();
+        assert!(chars == ['😀', '€', 'b', 'ñ', 'a']);
+
+        let mut chars = gap_buf.chars();
+        assert!(chars.next() == Some('a'));
+        assert!(chars.next_back() == Some('😀'));
+        assert!(chars.next_back() == Some('€'));
+        assert!(chars.next() == Some('ñ'));
+        assert!(chars.next() == Some('b'));
+        assert!(chars.next().is_none() && chars.next_back().is_none());
+    }
+
+    #[test]
+    fn bytes_1() {
+        let mut gap_buf = buf_from_str("abc");
+        gap_buf.cursor(1);
+        assert!(gap_buf.bytes().collect::<Vec<_>>() == b"abc");
+        assert!(gap_buf.bytes().rev().collect::<Vec<_>>() == b"cba");
+    }
+
+    #[test]
+    fn chars_at_1() {
+        let mut gap_buf = buf_from_str("foo bär baz");
+        gap_buf.cursor(7);
+
+        let word: String = gap_buf.chars_at(4).take_while(|ch| *ch != ' ').collect();
+        assert!(word == "bär");
+        let before: String = gap_buf.chars_before(8).take_while(|ch| *ch != 'f').collect();
+        assert!(before == "räb oo");
+        assert!(gap_buf.chars_at(12).next().is_none());
+        assert!(gap_buf.chars_before(0).next().is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn chars_at_2() {
+        let gap_buf = buf_from_str("bär");
+        gap_buf.chars_at(2);
+    }
+
+    #[test]
+    fn char_indices_1() {
+        let mut gap_buf = buf_from_str("a€c");
+        gap_buf.insert_str(1, "b");
+
+        let indices: Vec<(usize, char)> = gap_buf.char_indices().collect();
+        assert!(indices == [(0, 'a'), (1, 'b'), (2, '€'), (5, 'c')]);
+
+        let (offset, _) = indices[3];
+        gap_buf.remove(offset..offset + 1);
+        assert!(gap_buf == "ab€");
+
+        let indices: Vec<(usize, char)> = gap_buf.char_indices().rev().collect();
+        assert!(indices == [(2, '€'), (1, 'b'), (0, 'a')]);
+    }
+
+    #[test]
+    fn chunks_1() {
+        let mut gap_buf = buf_from_str("ab€cd");
+        gap_buf.cursor(2);
+        assert!(gap_buf.chunks().collect::<Vec<_>>() == ["ab", "€cd"]);
+        assert!(gap_buf.chunks().rev().collect::<String>() == "€cdab");
+
+        gap_buf.cursor(0);
+        assert!(gap_buf.chunks().len() == 1);
+        assert!(gap_buf.chunks().collect::<String>() == "ab€cd");
+        assert!(GapString::new().chunks().next().is_none());
+    }
+}
@@ -5,299 +5,2256 @@
 // obtain one at http://mozilla.org/MPL/2.0/.
 
 
+#[cfg(feature = "allocator-api")]
+extern crate allocator_api2;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "encoding")]
+extern crate encoding_rs;
+#[cfg(all(feature = "mmap", unix))]
 extern crate libc;
+#[cfg(feature = "regex")]
+extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+extern crate serde_test;
+#[cfg(feature = "unicode")]
+extern crate unicode_segmentation;
+#[cfg(feature = "unicode")]
+extern crate unicode_width;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
 
 
-use std::{fmt, mem};
-use std::ops::{Drop, Range};
+use std::{fmt, str};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read};
+use std::iter::FromIterator;
+use std::ops::{Index, Range};
+use std::path::Path;
 
+use change::Listeners;
+use history::History;
+use lines::LineIndex;
+use mark::Marks;
+use ops::OpLog;
+use protect::Protection;
+use snapshot::SnapshotCache;
+use spans::AttachedSpans;
 
-const CHUNK_SIZE: isize = 32;
+#[cfg(feature = "allocator-api")]
+pub use allocator::{AllocError, Allocator, Global};
+pub use batch::Edit;
+pub use buffer::{GapBuffer, Iter};
+pub use chunked::ChunkedString;
+pub use change::{EditDelta, ListenerId};
+pub use cursor::Cursor;
+#[cfg(feature = "encoding")]
+pub use encoding::SourceEncoding;
+pub use error::{FromUtf8Error, GapBufferError, InvariantError, OpError, PatchError,
+                StaleHistory};
+#[cfg(feature = "arbitrary")]
+pub use fuzz::{FuzzCase, FuzzOp};
+#[cfg(feature = "unicode")]
+pub use grapheme::Graphemes;
+pub use history::UndoHistory;
+pub use iter::{Bytes, CharIndices, Chars, Chunks};
+pub use line_ending::LineEnding;
+pub use mark::{Bias, Mark};
+pub use memory::MemStats;
+pub use ops::{OpComponent, Operation};
+pub use piece_table::PieceTable;
+pub use policy::{GrowthPolicy, ShrinkPolicy};
+pub use preview::PreviewSession;
+pub use protect::ProtectedRange;
+pub use range_io::{RangeReader, RangeWriter};
+pub use search::{Matches, Pattern, Split, SplitWithRanges};
+pub use snapshot::{Snapshot, SnapshotChunks};
+pub use spans::{Spans, SpansId};
+pub use text_buffer::TextBuffer;
+pub use transaction::Transaction;
+#[cfg(feature = "wasm")]
+pub use wasm::JsGapBuffer;
 
 
-/// Dynamic array that allows efficient insertion and removal operations
-/// that are near the same location. Ideal for text editors.
-pub struct GapBuffer {
-    buf_start: *mut u8,
-    gap_start: *mut u8,
-    gap_end: *mut u8,
-    buf_end: *mut u8
+/// Buffer of raw bytes without any encoding invariant, such as the content of
+/// a hex editor.
+///
+/// Shares its implementation with `GapString`, which wraps one and keeps it
+/// valid UTF-8. The two convert into each other with `GapString::from_utf8`
+/// and `GapString::into_bytes` without copying.
+pub type GapBytes = GapBuffer<u8>;
+
+
+mod allocator;
+mod batch;
+mod buffer;
+mod change;
+mod chunked;
+mod column;
+mod cursor;
+mod diff;
+#[cfg(feature = "encoding")]
+mod encoding;
+mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "unicode")]
+mod grapheme;
+mod history;
+mod iter;
+mod line_ending;
+mod lines;
+mod mark;
+mod memory;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod ops;
+#[cfg(feature = "patch")]
+mod patch;
+mod piece_table;
+mod policy;
+mod preedit;
+mod preview;
+mod protect;
+mod range_io;
+#[cfg(feature = "regex")]
+mod regex_search;
+mod search;
+#[cfg(feature = "serde")]
+mod serialize;
+mod snapshot;
+mod spans;
+mod text_buffer;
+mod transaction;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod word;
+
+
+/// UTF-8 text stored in a `GapBuffer<u8>`, allowing efficient insertion and
+/// removal operations that are near the same location. Ideal for text
+/// editors.
+///
+/// A buffer is `Send`, so it can move between an editor's worker threads.
+/// It is not `Sync`, as taking a snapshot updates state shared through
+/// `&self` and change listeners need not be `Sync`: background threads read
+/// the content through a `Snapshot` instead.
+pub struct GapString {
+    raw: GapBuffer<u8>,
+    preedit: Option<Range<usize>>,
+    protection: Protection,
+    marks: Marks,
+    spans: AttachedSpans,
+    lines: Option<LineIndex>,
+    history: History,
+    ops: OpLog,
+    listeners: Listeners,
+    snapshots: RefCell<SnapshotCache>,
+    #[cfg(feature = "encoding")]
+    encoding: SourceEncoding,
+    line_ending: LineEnding,
+    readonly: bool
+}
+
+impl GapString {
+    /// Moves the content of `other` to the end of the buffer, leaving `other`
+    /// empty.
+    ///
+    /// The gap grows at most once and the content is copied directly from
+    /// the segments of `other`.
+    ///
+    /// # Panics
+    ///
+    /// * If the buffer would exceed its maximum capacity.
+    /// * If either buffer is read-only, or `other` has protected content.
+    pub fn append(&mut self, other: &mut GapString) {
+        let offset = self.len();
+        let other_len = other.len();
+        let result = other.check_edit(0, other_len)
+            .and_then(|_| self.begin_edit(offset, 0, other_len))
+            .and_then(|_| other.begin_edit(0, other_len, 0))
+            .and_then(|_| self.raw.try_append(&mut other.raw));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+
+        self.edited(offset, 0, other_len);
+        other.edited(0, other_len, 0);
+    }
+
+    /// Returns the content as bytes, to read it without regard for chars.
+    pub fn as_bytes(&self) -> &GapBytes {
+        &self.raw
+    }
+
+    /// Returns the byte at `offset`, or `None` if it is out of bounds.
+    pub fn byte_at(&self, offset: usize) -> Option<u8> {
+        self.raw.get(offset).cloned()
+    }
+
+    /// Returns the bytes before and after the gap.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        self.raw.as_slices()
+    }
+
+    /// Returns the content before and after the gap.
+    ///
+    /// The gap always sits on a char boundary, so both halves are valid
+    /// UTF-8 on their own.
+    pub fn as_strs(&self) -> (&str, &str) {
+        let (head, tail) = self.as_slices();
+        unsafe { (str::from_utf8_unchecked(head), str::from_utf8_unchecked(tail)) }
+    }
+
+    /// Returns the total size of the allocation in bytes, including the gap.
+    ///
+    /// This is the content length the buffer can hold before it has to grow.
+    pub fn capacity(&self) -> usize {
+        self.raw.capacity()
+    }
+
+    /// Returns the char starting at `offset`.
+    ///
+    /// Returns `None` if `offset` is out of bounds or not on a char boundary.
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        let first = self.byte_at(offset)?;
+        let width = match first {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return None
+        };
+
+        let mut bytes = [0; 4];
+        for (i, b) in bytes.iter_mut().enumerate().take(width) {
+            *b = self.byte_at(offset + i)?;
+        }
+        str::from_utf8(&bytes[..width]).ok()?.chars().next()
+    }
+
+    /// Returns the number of chars in the buffer.
+    ///
+    /// Counts the bytes starting a char in each segment, a loop the compiler
+    /// vectorizes, so this takes O(n) time but decodes nothing.
+    pub fn char_len(&self) -> usize {
+        let (head, tail) = self.as_slices();
+        count_char_starts(head) + count_char_starts(tail)
+    }
+
+    /// Checks the invariants of `GapBuffer::check_invariants`, and that the
+    /// content on either side of the gap is valid UTF-8.
+    ///
+    /// Meant for `debug_assert!`s in editors built on the buffer. Takes O(n)
+    /// time.
+    ///
+    /// # Errors
+    ///
+    /// * Same as `GapBuffer::check_invariants`.
+    /// * `InvariantError::InvalidUtf8` if either side of the gap is not
+    ///   valid UTF-8.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        self.raw.check_invariants()?;
+        let (head, tail) = self.as_slices();
+        if str::from_utf8(head).is_err() || str::from_utf8(tail).is_err() {
+            return Err(InvariantError::InvalidUtf8);
+        }
+        Ok(())
+    }
+
+    /// Removes all content, keeping the allocation.
+    ///
+    /// # Panics
+    ///
+    /// * If the buffer is read-only or its content overlaps a protected
+    ///   range.
+    pub fn clear(&mut self) {
+        let buf_len = self.len();
+        if let Err(e) = self.begin_edit(0, buf_len, 0) {
+            panic!("{}", e);
+        }
+
+        self.raw.clear();
+        self.edited(0, buf_len, 0);
+    }
+
+    /// Removes `range` from the buffer and returns the removed text.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `try_remove` errors.
+    pub fn drain(&mut self, range: Range<usize>) -> String {
+        self.try_remove_returning(range).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates a buffer holding the content of the file at `path`.
+    ///
+    /// The file is read straight into an allocation sized by its metadata.
+    ///
+    /// # Errors
+    ///
+    /// * If the file cannot be opened or read.
+    /// * If the file's content is not valid UTF-8.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<GapString> {
+        let mut file = File::open(path)?;
+        let len = file.metadata().map(|m| m.len() as usize).unwrap_or(0);
+        let mut bytes = Vec::with_capacity(len);
+        file.read_to_end(&mut bytes)?;
+        GapString::from_bytes(bytes)
+    }
+
+    /// Creates a buffer holding everything read from `reader`.
+    ///
+    /// The content is read straight into the buffer's allocation, the spare
+    /// capacity left by reading becoming the gap at the end.
+    ///
+    /// # Errors
+    ///
+    /// * If reading fails.
+    /// * If the content is not valid UTF-8.
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<GapString> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        GapString::from_bytes(bytes)
+    }
+
+    /// Creates a buffer holding the content of `bytes`, keeping its
+    /// allocation and growth settings.
+    ///
+    /// The segments are checked in place. A char split by the gap is
+    /// handled by moving the gap in front of it, moving at most 3 bytes.
+    ///
+    /// # Errors
+    ///
+    /// * If the content is not valid UTF-8, returning `bytes` unchanged
+    ///   along with the error.
+    pub fn from_utf8(mut bytes: GapBytes) -> Result<GapString, FromUtf8Error> {
+        let split = match str::from_utf8(bytes.as_slices().0) {
+            Ok(_) => None,
+            Err(e) if e.error_len().is_none() => Some(e.valid_up_to()),
+            Err(error) => return Err(FromUtf8Error { bytes, error })
+        };
+        if let Some(offset) = split {
+            bytes.check_gap();
+            bytes.move_gap_to(offset);
+            bytes.poison_gap();
+        }
+
+        if str::from_utf8(bytes.as_slices().1).is_err() {
+            let error = bytes.to_str().unwrap_err();
+            return Err(FromUtf8Error { bytes, error });
+        }
+        Ok(GapString::from_raw(bytes))
+    }
+
+    /// Returns the content within `range`, or `None` if `range` is out of
+    /// bounds or does not start and end on char boundaries.
+    ///
+    /// Borrows from the buffer like `slice`.
+    pub fn get(&self, range: Range<usize>) -> Option<Cow<'_, str>> {
+        if range.start > range.end || range.end > self.len() {
+            return None;
+        }
+        if !self.is_char_boundary(range.start) || !self.is_char_boundary(range.end) {
+            return None;
+        }
+        Some(self.slice_unchecked(range))
+    }
+
+    /// Returns the policy deciding how much the gap grows.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.raw.growth_policy()
+    }
+
+    /// Inserts `bytes`, which must be valid UTF-8, into the buffer at
+    /// `offset` and returns the change made.
+    ///
+    /// For bytes read from elsewhere without going through `str`. Content
+    /// that is not text belongs in a `GapBytes`, which holds any bytes.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `try_insert_bytes` errors.
+    pub fn insert_bytes(&mut self, offset: usize, bytes: &[u8]) -> EditDelta {
+        self.try_insert_bytes(offset, bytes).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Inserts `ch` into the buffer at `offset`, encoding it directly into
+    /// the gap, and returns the change made.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `insert_str`.
+    pub fn insert_char(&mut self, offset: usize, ch: char) -> EditDelta {
+        let len = ch.len_utf8();
+        let result = self.check_offset(offset)
+            .and_then(|_| self.begin_edit(offset, 0, len))
+            .and_then(|_| self.raw.try_insert_char(offset, ch));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+        self.edited(offset, 0, len);
+        EditDelta { offset, removed_len: 0, inserted_len: len }
+    }
+
+    /// Inserts `s` into the buffer at `offset` and returns the change made.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    /// * If the insertion would exceed the maximum capacity.
+    /// * If the buffer is read-only.
+    /// * If `offset` is inside a protected range.
+    pub fn insert_str(&mut self, offset: usize, s: &str) -> EditDelta {
+        self.try_insert_str(offset, s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Converts the buffer into its bytes, keeping the allocation and the
+    /// gap where they are.
+    pub fn into_bytes(self) -> GapBytes {
+        self.raw
+    }
+
+    /// Converts the buffer into a `String`, reusing its allocation.
+    ///
+    /// The gap is moved to the end first, which takes O(n) time unless it
+    /// already is there.
+    pub fn into_string(self) -> String {
+        unsafe { String::from_utf8_unchecked(self.raw.into_vec()) }
+    }
+
+    /// Returns `true` if the buffer holds no content.
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    /// Returns `true` if the buffer is read-only.
+    pub fn is_readonly(&self) -> bool {
+        self.readonly
+    }
+
+    /// Returns the length of the content in bytes, excluding the gap.
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    /// Moves the gap to the end and returns the whole content as one `&str`.
+    ///
+    /// Takes O(n) time unless the gap already is at the end. Prefer
+    /// `as_strs` or `slice` when the two halves will do.
+    pub fn make_contiguous(&mut self) -> &str {
+        let bytes = self.raw.make_contiguous();
+        unsafe { str::from_utf8_unchecked(bytes) }
+    }
+
+    /// Returns the maximum capacity the buffer may grow to, if any.
+    pub fn max_capacity(&self) -> Option<usize> {
+        self.raw.max_capacity()
+    }
+
+    /// Creates a new, empty buffer without allocating.
+    pub fn new() -> GapString {
+        GapString::with_capacity(0)
+    }
+
+    /// Removes the last char and returns it, or `None` if the buffer is
+    /// empty.
+    ///
+    /// Takes O(1) time while the gap is at the end, as after `push_str`.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `remove`.
+    pub fn pop_char(&mut self) -> Option<char> {
+        let len = self.len();
+        let mut start = len.checked_sub(1)?;
+        while !self.is_char_boundary(start) {
+            start -= 1;
+        }
+        let ch = self.char_at(start);
+        self.remove(start..len);
+        ch
+    }
+
+    /// Appends `ch` to the end of the buffer, encoding it directly into the
+    /// gap, and returns the change made.
+    ///
+    /// Takes amortized O(1) time, see `push_str`.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `insert_str`.
+    pub fn push_char(&mut self, ch: char) -> EditDelta {
+        let offset = self.len();
+        let len = ch.len_utf8();
+        let result = self.begin_edit(offset, 0, len)
+            .and_then(|_| self.raw.try_push_char(ch));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+        self.edited(offset, 0, len);
+        EditDelta { offset, removed_len: 0, inserted_len: len }
+    }
+
+    /// Appends `s` to the end of the buffer and returns the change made.
+    ///
+    /// While the gap already is at the end, no bytes are moved. When the gap
+    /// is too small it grows as the growth policy decides, by default by at
+    /// least the current capacity, so appending takes amortized O(1) time
+    /// per byte.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `insert_str`.
+    pub fn push_str(&mut self, s: &str) -> EditDelta {
+        let offset = self.len();
+        let result = self.begin_edit(offset, 0, s.len())
+            .and_then(|_| self.raw.try_push_slice(s.as_bytes()));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+        self.edited(offset, 0, s.len());
+        EditDelta { offset, removed_len: 0, inserted_len: s.len() }
+    }
+
+    /// Replaces the content of the buffer with the content of the file at
+    /// `path`.
+    ///
+    /// The file is read as by `from_reader_normalized`, decoded as by
+    /// `from_reader_with_encoding` with the `encoding` feature, and the
+    /// content is changed by the edits `diff` returns rather than replaced
+    /// whole. Marks on the lines that did not change on disk stay on them.
+    /// The edits are undone as a single step. The line ending, and the
+    /// source encoding, become those of the file.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * If the file cannot be opened or read.
+    /// * If the file's content cannot be decoded, or is not valid UTF-8
+    ///   without the `encoding` feature.
+    /// * If the buffer is read-only.
+    /// * If a changed line overlaps a protected range.
+    pub fn reload_from_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let file = File::open(path)?;
+        #[cfg(feature = "encoding")]
+        let mut content = GapString::from_reader_with_encoding(file, None)?;
+        #[cfg(not(feature = "encoding"))]
+        let mut content = GapString::from_reader(file)?;
+        content.normalize_line_endings();
+
+        let edits = self.diff(&content);
+        self.apply_patch(&edits).map_err(io::Error::other)?;
+        self.line_ending = content.line_ending;
+        #[cfg(feature = "encoding")]
+        {
+            self.encoding = content.encoding;
+        }
+        Ok(())
+    }
+
+    /// Removes `range` from the buffer and returns the change made.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is empty or out of bounds.
+    /// * If `range` starts or ends inside a char.
+    /// * If the buffer is read-only.
+    /// * If `range` overlaps a protected range.
+    pub fn remove(&mut self, range: Range<usize>) -> EditDelta {
+        assert!(range.start < range.end, "Invalid range: {:?}", range);
+        self.try_remove(range).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Removes `range` from the buffer and returns the removed text, such as
+    /// for cutting it to a clipboard.
+    ///
+    /// Unlike `drain`, an empty range is rejected like by `remove`.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `remove`.
+    pub fn remove_returning(&mut self, range: Range<usize>) -> String {
+        assert!(range.start < range.end, "Invalid range: {:?}", range);
+        self.try_remove_returning(range).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Replaces `range` with `s`, moving the gap only once, and returns the
+    /// change made.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `try_replace_range` errors.
+    pub fn replace_range(&mut self, range: Range<usize>, s: &str) -> EditDelta {
+        self.try_replace_range(range, s).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Makes room for at least `additional` more bytes without reallocating,
+    /// growing the gap as the growth policy decides if needed.
+    ///
+    /// # Panics
+    ///
+    /// * If the allocation fails or would exceed the maximum capacity.
+    pub fn reserve(&mut self, additional: usize) {
+        self.raw.reserve(additional);
+    }
+
+    /// Makes room for exactly `additional` more bytes without reallocating,
+    /// with a single `realloc` if the gap is too small.
+    ///
+    /// # Panics
+    ///
+    /// * If the allocation fails or would exceed the maximum capacity.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.raw.reserve_exact(additional);
+    }
+
+    /// Writes the content to the file at `path`, creating or truncating it.
+    ///
+    /// The head and the tail are written straight from the buffer, so no
+    /// copy of the content is made.
+    ///
+    /// # Errors
+    ///
+    /// * If the file cannot be created or written.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        self.write_to(&mut file)?;
+        file.sync_all()
+    }
+
+    /// Sets the policy deciding how much the gap grows.
+    ///
+    /// Defaults to `GrowthPolicy::Doubling`.
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.raw.set_growth_policy(policy);
+    }
+
+    /// Limits the capacity the buffer may grow to.
+    ///
+    /// Once set, operations that would need to grow the allocation beyond
+    /// `max` fail with `GapBufferError::CapacityExceeded` instead. A limit
+    /// below the current capacity does not shrink the buffer, it only
+    /// prevents further growth. `None` removes the limit.
+    pub fn set_max_capacity(&mut self, max: Option<usize>) {
+        self.raw.set_max_capacity(max);
+    }
+
+    /// Makes the buffer read-only, or writable again.
+    ///
+    /// While read-only, the fallible mutating methods fail with
+    /// `GapBufferError::ReadOnly` and the others panic.
+    pub fn set_readonly(&mut self, readonly: bool) {
+        self.readonly = readonly;
+    }
+
+    /// Sets the policy deciding when the gap is shrunk after removals.
+    ///
+    /// Defaults to `ShrinkPolicy::Manual`.
+    pub fn set_shrink_policy(&mut self, policy: ShrinkPolicy) {
+        self.raw.set_shrink_policy(policy);
+    }
+
+    /// Returns the policy deciding when the gap is shrunk after removals.
+    pub fn shrink_policy(&self) -> ShrinkPolicy {
+        self.raw.shrink_policy()
+    }
+
+    /// Shrinks the allocation to at least `min_capacity` bytes, and not below
+    /// the content length.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.raw.shrink_to(min_capacity);
+    }
+
+    /// Shrinks the allocation to fit the content, removing the gap.
+    pub fn shrink_to_fit(&mut self) {
+        self.raw.shrink_to_fit();
+    }
+
+    /// Returns the content within `range`.
+    ///
+    /// Borrows from the buffer when `range` lies entirely on one side of the
+    /// gap, and only allocates when it spans the gap.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is out of bounds.
+    /// * If `range` does not start and end on char boundaries.
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        let buf_len = self.len();
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        assert!(range.end <= buf_len, "Range {:?} out of bounds", range);
+        assert!(self.is_char_boundary(range.start)
+                && self.is_char_boundary(range.end),
+                "Range {:?} is not on char boundaries", range);
+
+        self.slice_unchecked(range)
+    }
+
+    /// Truncates the buffer at `offset` and returns the content after it as
+    /// a new buffer, copying it only once.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    /// * If the buffer is read-only or the split off content overlaps a
+    ///   protected range.
+    pub fn split_off(&mut self, offset: usize) -> GapString {
+        let buf_len = self.len();
+        let result = self.check_offset(offset)
+            .and_then(|_| self.begin_edit(offset, buf_len - offset, 0));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+
+        let other = GapString::from_raw(self.raw.split_off(offset));
+        self.edited(offset, buf_len - offset, 0);
+        other
+    }
+
+    /// Shortens the buffer to `len` bytes. Does nothing if the buffer is not
+    /// longer than `len`.
+    ///
+    /// Takes O(1) time when the gap is at or after `len`, and a single
+    /// memmove of the kept part of the tail otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `len` is not on a char boundary.
+    /// * If the buffer is read-only or the truncated content overlaps a
+    ///   protected range.
+    pub fn truncate(&mut self, len: usize) {
+        let buf_len = self.len();
+        if len >= buf_len {
+            return;
+        }
+
+        let result = self.check_offset(len)
+            .and_then(|_| self.begin_edit(len, buf_len - len, 0));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+
+        self.raw.truncate(len);
+        self.edited(len, buf_len - len, 0);
+    }
+
+    /// Inserts `bytes` into the buffer at `offset` and returns the change
+    /// made.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * `InvalidUtf8` if `bytes` is not valid UTF-8.
+    /// * Same as `try_insert_str` otherwise.
+    pub fn try_insert_bytes(&mut self, offset: usize, bytes: &[u8])
+        -> Result<EditDelta, GapBufferError>
+    {
+        match str::from_utf8(bytes) {
+            Ok(s) => self.try_insert_str(offset, s),
+            Err(_) => Err(GapBufferError::InvalidUtf8)
+        }
+    }
+
+    /// Inserts `s` into the buffer at `offset` and returns the change made.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * `OutOfBounds` if `offset` is past the end of the content.
+    /// * `NotCharBoundary` if `offset` is inside a char.
+    /// * `CapacityExceeded` if the insertion would exceed the maximum
+    ///   capacity.
+    /// * `AllocationFailed` if growing the allocation failed.
+    /// * `ReadOnly` if the buffer is read-only.
+    /// * `Protected` if `offset` is inside a protected range.
+    pub fn try_insert_str(&mut self, offset: usize, s: &str)
+        -> Result<EditDelta, GapBufferError>
+    {
+        self.check_offset(offset)?;
+        self.begin_edit(offset, 0, s.len())?;
+        self.insert_raw(offset, s)?;
+        self.edited(offset, 0, s.len());
+        Ok(EditDelta { offset, removed_len: 0, inserted_len: s.len() })
+    }
+
+    /// Removes `range` from the buffer and returns the change made. Removing
+    /// an empty range does nothing.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * `OutOfBounds` if `range` is reversed or ends past the content.
+    /// * `NotCharBoundary` if `range` starts or ends inside a char.
+    /// * `ReadOnly` if the buffer is read-only.
+    /// * `Protected` if `range` overlaps a protected range.
+    pub fn try_remove(&mut self, range: Range<usize>)
+        -> Result<EditDelta, GapBufferError>
+    {
+        if range.start > range.end {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        self.check_offset(range.start)?;
+        self.check_offset(range.end)?;
+        let delta = EditDelta { offset: range.start, removed_len: range.len(), inserted_len: 0 };
+        if range.start == range.end {
+            return Ok(delta);
+        }
+        self.begin_edit(range.start, range.len(), 0)?;
+
+        self.remove_raw(range);
+        Ok(delta)
+    }
+
+    /// Removes `range` from the buffer and returns the removed text, copied
+    /// out once the edit is known to succeed. Removing an empty range does
+    /// nothing.
+    ///
+    /// # Errors
+    ///
+    /// * Same as `try_remove`.
+    pub fn try_remove_returning(&mut self, range: Range<usize>)
+        -> Result<String, GapBufferError>
+    {
+        if range.start > range.end {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        self.check_offset(range.start)?;
+        self.check_offset(range.end)?;
+        if range.start == range.end {
+            return Ok(String::new());
+        }
+        self.begin_edit(range.start, range.len(), 0)?;
+
+        let removed = self.slice_unchecked(range.clone()).into_owned();
+        self.remove_raw(range);
+        Ok(removed)
+    }
+
+    /// Replaces `range` with `s`, moving the gap only once, and returns the
+    /// change made.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * `OutOfBounds` if `range` is reversed or ends past the content.
+    /// * `NotCharBoundary` if `range` starts or ends inside a char.
+    /// * `CapacityExceeded` if the replacement would exceed the maximum
+    ///   capacity.
+    /// * `AllocationFailed` if growing the allocation failed.
+    /// * `ReadOnly` if the buffer is read-only.
+    /// * `Protected` if `range` overlaps a protected range.
+    pub fn try_replace_range(&mut self, range: Range<usize>, s: &str)
+        -> Result<EditDelta, GapBufferError>
+    {
+        if range.start > range.end {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        self.check_offset(range.start)?;
+        self.check_offset(range.end)?;
+        self.begin_edit(range.start, range.len(), s.len())?;
+
+        let delta = EditDelta {
+            offset: range.start,
+            removed_len: range.len(),
+            inserted_len: s.len()
+        };
+        self.replace_raw(range, s)?;
+        Ok(delta)
+    }
+
+    /// Creates a new buffer with a `capacity` sized allocation.
+    ///
+    /// # Panics
+    ///
+    /// * If the allocation fails.
+    pub fn with_capacity(capacity: usize) -> GapString {
+        GapString::from_raw(GapBuffer::with_capacity(capacity))
+    }
+
+    /// Creates a buffer holding `content` with a `gap` byte gap at `offset`,
+    /// allocating everything at once.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    /// * If the allocation fails.
+    pub fn with_gap_capacity(content: &str, gap: usize, offset: usize)
+        -> GapString
+    {
+        assert!(content.is_char_boundary(offset), "{}",
+                if offset > content.len() {
+                    GapBufferError::OutOfBounds
+                } else {
+                    GapBufferError::NotCharBoundary
+                });
+
+        let (head, tail) = content.as_bytes().split_at(offset);
+        GapString::from_raw(GapBuffer::from_parts(head, gap, tail))
+    }
+
+    /// Creates a new, empty buffer growing its gap as `policy` decides.
+    pub fn with_growth_policy(policy: GrowthPolicy) -> GapString {
+        GapString::from_raw(GapBuffer::with_growth_policy(policy))
+    }
+
+    /// Fails like `check_edit`, or if `inserted` bytes would not fit, and
+    /// otherwise prepares replacing `removed` bytes at `offset`, which must
+    /// be valid, with `inserted` bytes.
+    ///
+    /// Room for the inserted bytes is reserved first, so that nothing is
+    /// changed on error and the edit cannot fail once prepared. An edit
+    /// overlapping the preedit text commits it, so that the history,
+    /// operations and listeners see it before it is edited.
+    fn begin_edit(&mut self, offset: usize, removed: usize, inserted: usize)
+        -> Result<(), GapBufferError>
+    {
+        self.check_edit(offset, removed)?;
+        self.raw.try_reserve(inserted.saturating_sub(removed))?;
+        if self.preedit_overlaps(offset, removed) {
+            self.commit_preedit();
+        }
+        self.save_removed(offset, removed);
+        Ok(())
+    }
+
+    /// Fails if `offset` is out of bounds or not on a char boundary.
+    fn check_offset(&self, offset: usize) -> Result<(), GapBufferError> {
+        if offset > self.len() {
+            Err(GapBufferError::OutOfBounds)
+        } else if !self.is_char_boundary(offset) {
+            Err(GapBufferError::NotCharBoundary)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails if replacing `removed` bytes at `offset` is not allowed.
+    fn check_edit(&self, offset: usize, removed: usize)
+        -> Result<(), GapBufferError>
+    {
+        self.check_writable()?;
+        self.check_protected(offset, removed)
+    }
+
+    fn check_writable(&self) -> Result<(), GapBufferError> {
+        if self.readonly {
+            Err(GapBufferError::ReadOnly)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Updates everything tracking positions after `removed` bytes at
+    /// `offset` were replaced by `inserted` bytes, and reports the edit.
+    fn edited(&mut self, offset: usize, removed: usize, inserted: usize) {
+        self.edited_unreported(offset, removed, inserted);
+        self.report_edit(offset, removed, inserted);
+    }
+
+    /// Updates everything tracking positions after an edit, without
+    /// reporting it to the operation log and listeners, as for preedit text.
+    fn edited_unreported(&mut self, offset: usize, removed: usize, inserted: usize) {
+        self.record_edit(offset, inserted);
+        self.adjust_preedit(offset, removed, inserted);
+        self.adjust_protected(offset, removed, inserted);
+        self.adjust_marks(offset, removed, inserted);
+        self.adjust_spans(offset, removed, inserted);
+        self.adjust_lines(offset, removed, inserted);
+        self.adjust_snapshots(offset, inserted);
+    }
+
+    /// Takes over `bytes` if they are valid UTF-8.
+    fn from_bytes(bytes: Vec<u8>) -> io::Result<GapString> {
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(GapString::from(s)),
+            Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e))
+        }
+    }
+
+    fn from_raw(raw: GapBuffer<u8>) -> GapString {
+        GapString {
+            raw,
+            preedit: None,
+            protection: Protection::default(),
+            marks: Marks::default(),
+            spans: AttachedSpans::default(),
+            lines: None,
+            history: History::default(),
+            ops: OpLog::default(),
+            listeners: Listeners::default(),
+            snapshots: RefCell::default(),
+            #[cfg(feature = "encoding")]
+            encoding: SourceEncoding::default(),
+            line_ending: LineEnding::default(),
+            readonly: false
+        }
+    }
+
+    fn head(&self) -> &str {
+        self.as_strs().0
+    }
+
+    /// Inserts `s` without notifying anything tracking positions.
+    fn insert_raw(&mut self, offset: usize, s: &str)
+        -> Result<(), GapBufferError>
+    {
+        self.raw.try_insert_slice(offset, s.as_bytes())
+    }
+
+    fn is_char_boundary(&self, offset: usize) -> bool {
+        match self.byte_at(offset) {
+            Some(b) => b & 0xc0 != 0x80,
+            None => offset == self.len()
+        }
+    }
+
+    /// Removes `range`, which must be valid, and applies the shrink policy.
+    fn remove_raw(&mut self, range: Range<usize>) {
+        self.raw.remove_range(range.clone());
+        self.edited(range.start, range.len(), 0);
+    }
+
+    /// Replaces `range`, which must be valid, with `s`.
+    fn replace_raw(&mut self, range: Range<usize>, s: &str)
+        -> Result<(), GapBufferError>
+    {
+        self.raw.try_replace_range(range.clone(), s.as_bytes())?;
+        self.edited(range.start, range.len(), s.len());
+        Ok(())
+    }
+
+    /// Reports an edit at `offset` to the operation log and listeners, in
+    /// offsets of the committed content.
+    fn report_edit(&mut self, offset: usize, removed: usize, inserted: usize) {
+        self.record_op(offset, removed, inserted);
+        self.notify_listeners(offset, removed, inserted);
+    }
+
+    /// Returns the content within `range`, which must be in bounds and on
+    /// char boundaries.
+    fn slice_unchecked(&self, range: Range<usize>) -> Cow<'_, str> {
+        let (head, tail) = self.as_slices();
+        let bytes = if range.end <= head.len() {
+            Cow::Borrowed(&head[range])
+        } else if range.start >= head.len() {
+            Cow::Borrowed(&tail[range.start - head.len()..range.end - head.len()])
+        } else {
+            let mut bytes = Vec::with_capacity(range.len());
+            bytes.extend_from_slice(&head[range.start..]);
+            bytes.extend_from_slice(&tail[..range.end - head.len()]);
+            Cow::Owned(bytes)
+        };
+
+        match bytes {
+            Cow::Borrowed(b) => Cow::Borrowed(unsafe { str::from_utf8_unchecked(b) }),
+            Cow::Owned(b) => Cow::Owned(unsafe { String::from_utf8_unchecked(b) })
+        }
+    }
+
+    fn tail(&self) -> &str {
+        self.as_strs().1
+    }
+}
+
+impl Clone for GapString {
+    /// Copies the content into a new allocation of the same capacity, with
+    /// the gap at the same position. Change listeners are not copied.
+    fn clone(&self) -> GapString {
+        GapString {
+            raw: self.raw.clone(),
+            preedit: self.preedit.clone(),
+            protection: self.protection.clone(),
+            marks: self.marks.clone(),
+            spans: self.spans.clone(),
+            lines: self.lines.clone(),
+            history: self.history.clone(),
+            ops: self.ops.clone(),
+            listeners: Listeners::default(),
+            snapshots: RefCell::default(),
+            #[cfg(feature = "encoding")]
+            encoding: self.encoding,
+            line_ending: self.line_ending,
+            readonly: self.readonly
+        }
+    }
+}
+
+impl<'a> From<&'a str> for GapString {
+    /// Creates a buffer holding `s`, allocated to fit it exactly.
+    fn from(s: &'a str) -> GapString {
+        GapString::from_raw(GapBuffer::from_parts(s.as_bytes(), 0, &[]))
+    }
+}
+
+impl From<String> for GapString {
+    /// Creates a buffer holding `s`, taking over its allocation with the
+    /// spare capacity as the gap at the end.
+    fn from(s: String) -> GapString {
+        GapString::from_raw(GapBuffer::from(s.into_bytes()))
+    }
+}
+
+impl Default for GapString {
+    /// Creates a new, empty buffer without allocating.
+    fn default() -> GapString {
+        GapString::new()
+    }
+}
+
+impl Extend<char> for GapString {
+    /// Appends each char like `push_char`.
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for ch in iter {
+            self.push_char(ch);
+        }
+    }
+}
+
+impl<'a> Extend<&'a str> for GapString {
+    /// Appends each string like `push_str`.
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(s);
+        }
+    }
+}
+
+impl Extend<String> for GapString {
+    /// Appends each string like `push_str`.
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        for s in iter {
+            self.push_str(&s);
+        }
+    }
+}
+
+impl FromIterator<char> for GapString {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> GapString {
+        let mut buf = GapString::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl<'a> FromIterator<&'a str> for GapString {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> GapString {
+        let mut buf = GapString::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl FromIterator<String> for GapString {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> GapString {
+        let mut buf = GapString::new();
+        buf.extend(iter);
+        buf
+    }
+}
+
+impl fmt::Debug for GapString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (head, tail) = self.as_slices();
+        f.debug_struct("GapString")
+            .field("head", &String::from_utf8_lossy(head))
+            .field("gap", &self.raw.gap_len())
+            .field("tail", &String::from_utf8_lossy(tail))
+            .finish()
+    }
+}
+
+impl fmt::Display for GapString {
+    /// Writes both segments straight to the formatter, without allocating.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.head())?;
+        f.write_str(self.tail())
+    }
+}
+
+impl fmt::Write for GapString {
+    /// Appends `s` like `push_str`, failing instead of panicking.
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let offset = self.len();
+        self.begin_edit(offset, 0, s.len())
+            .and_then(|_| self.raw.try_push_slice(s.as_bytes()))
+            .map_err(|_| fmt::Error)?;
+        self.edited(offset, 0, s.len());
+        Ok(())
+    }
+}
+
+impl Hash for GapString {
+    /// Hashes the head and then the tail bytes the way `str` hashes its
+    /// content, so a buffer hashes like the equal `&str` with hashers that
+    /// treat consecutive writes as one stream, such as `DefaultHasher`.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let (head, tail) = self.as_slices();
+        state.write(head);
+        state.write(tail);
+        state.write_u8(0xff);
+    }
+}
+
+impl Index<Range<usize>> for GapString {
+    type Output = str;
+
+    /// Returns the content within `range`.
+    ///
+    /// A `&str` can only be borrowed from one side of the gap, use `slice`
+    /// or `get` for ranges that may span it.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is out of bounds or not on char boundaries.
+    /// * If `range` spans the gap.
+    fn index(&self, range: Range<usize>) -> &str {
+        match self.slice(range.clone()) {
+            Cow::Borrowed(s) => s,
+            Cow::Owned(_) => panic!("Range {:?} spans the gap, use slice()", range)
+        }
+    }
+}
+
+impl PartialEq for GapString {
+    fn eq(&self, other: &GapString) -> bool {
+        segments_eq(self.as_slices(), other.as_slices())
+    }
+}
+
+impl Eq for GapString {}
+
+impl PartialOrd for GapString {
+    fn partial_cmp(&self, other: &GapString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GapString {
+    /// Compares the content lexicographically like `str`, segment by segment
+    /// without allocating.
+    fn cmp(&self, other: &GapString) -> Ordering {
+        segments_cmp(self.as_slices(), other.as_slices())
+    }
+}
+
+impl PartialEq<str> for GapString {
+    fn eq(&self, other: &str) -> bool {
+        let bytes = other.as_bytes();
+        segments_eq(self.as_slices(), (bytes, &[]))
+    }
+}
+
+impl<'a> PartialEq<&'a str> for GapString {
+    fn eq(&self, other: &&'a str) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<String> for GapString {
+    fn eq(&self, other: &String) -> bool {
+        *self == **other
+    }
+}
+
+impl PartialEq<GapString> for str {
+    fn eq(&self, other: &GapString) -> bool {
+        *other == *self
+    }
+}
+
+impl PartialEq<GapString> for &str {
+    fn eq(&self, other: &GapString) -> bool {
+        *other == **self
+    }
+}
+
+impl PartialEq<GapString> for String {
+    fn eq(&self, other: &GapString) -> bool {
+        *other == **self
+    }
+}
+
+/// Returns the number of bytes in `bytes` that are not UTF-8 continuation
+/// bytes.
+fn count_char_starts(bytes: &[u8]) -> usize {
+    bytes.iter().filter(|&&b| b & 0xc0 != 0x80).count()
+}
+
+/// Compares content split into two segments at arbitrary points.
+fn segments_eq(a: (&[u8], &[u8]), b: (&[u8], &[u8])) -> bool {
+    if a.0.len() + a.1.len() != b.0.len() + b.1.len() {
+        return false;
+    }
+
+    let (mut a, mut b) = ([a.0, a.1], [b.0, b.1]);
+    let (mut i, mut j) = (0, 0);
+    while i < 2 && j < 2 {
+        let n = a[i].len().min(b[j].len());
+        if a[i][..n] != b[j][..n] {
+            return false;
+        }
+        a[i] = &a[i][n..];
+        b[j] = &b[j][n..];
+        if a[i].is_empty() { i += 1; }
+        if b[j].is_empty() { j += 1; }
+    }
+    true
+}
+
+/// Orders content split into two segments at arbitrary points by its bytes,
+/// which for UTF-8 is the order of the chars.
+fn segments_cmp(a: (&[u8], &[u8]), b: (&[u8], &[u8])) -> Ordering {
+    let (mut a, mut b) = ([a.0, a.1], [b.0, b.1]);
+    let (mut i, mut j) = (0, 0);
+    loop {
+        while i < 2 && a[i].is_empty() { i += 1; }
+        while j < 2 && b[j].is_empty() { j += 1; }
+        if i == 2 || j == 2 {
+            return (j == 2).cmp(&(i == 2));
+        }
+
+        let n = a[i].len().min(b[j].len());
+        match a[i][..n].cmp(&b[j][..n]) {
+            Ordering::Equal => {}
+            ordering => return ordering
+        }
+        a[i] = &a[i][n..];
+        b[j] = &b[j][n..];
+    }
+}
+
+/// Returns a buffer holding `s`, inserted into an empty one.
+#[cfg(test)]
+pub(crate) fn buf_from_str(s: &str) -> GapString {
+    let mut buf = GapString::with_capacity(s.len());
+    buf.insert_str(0, s);
+    buf
 }
 
-impl GapBuffer {
-    /// Inserts `s` into the buffer at `offset`.
-    pub fn insert_str(&mut self, offset: usize, s: &str) {
-        let s_len = s.len() as isize;
-        if s_len > self.gap_len() {
-            self.grow_gap(s_len);
-        }
 
-        self.move_gap_to(offset as isize);
+#[cfg(test)]
+#[allow(clippy::comparison_to_empty)]
+mod tests {
+    use std::{env, fs, io, mem, process, thread};
+    use std::borrow::Cow;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::fmt::Write;
+    use std::hash::{Hash, Hasher};
+    use std::path::PathBuf;
+
+    use buffer::CHUNK_SIZE;
+    use super::{buf_from_str, Bias, GapBytes, GapString, GapBufferError, GrowthPolicy,
+                InvariantError, LineEnding, ShrinkPolicy};
+
+
+    #[test]
+    fn char_len_1() {
+        let mut gap_buf = buf_from_str("añ€😀");
+        assert!(gap_buf.char_len() == 4);
+        gap_buf.insert_str(3, "bc");
+        assert!(gap_buf.char_len() == 6);
+        assert!(GapString::new().char_len() == 0);
+    }
+
+    #[test]
+    fn cmp_1() {
+        let mut a = buf_from_str("apple pie");
+        let mut b = buf_from_str("apple tart");
+        for gap in 0..10 {
+            a.cursor(gap);
+            b.cursor(9 - gap);
+            assert!(a < b);
+            assert!(a.cmp(&a.clone()) == Ordering::Equal);
+        }
+        assert!(buf_from_str("apple") < a);
+        assert!(GapString::new() < buf_from_str("a"));
+        assert!(buf_from_str("é") > buf_from_str("z"));
+    }
+
+    #[test]
+    fn insert_str_1() {
+        let gap_buf = buf_from_str("12345678");
+        let text = gap_buf.to_string();
+        assert!(text == "12345678");
+    }
+
+    #[test]
+    fn insert_str_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(7, "9");
+
+        let text = gap_buf.to_string();
+        assert!(text == "123456798");
+    }
+
+    #[test]
+    fn insert_str_3() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(0, "0");
+
+        let text = gap_buf.to_string();
+        assert!(text == "012345678");
+    }
+
+    #[test]
+    fn insert_str_4() {
+        let mut gap_buf = buf_from_str("0123456789.0123456789.0123456789");
+        gap_buf.insert_str(0, "9876543210.");
+
+        let text = gap_buf.to_string();
+        assert!(text == "9876543210.0123456789.0123456789.0123456789");
+    }
+
+    #[test]
+    fn insert_str_5() {
+        let mut gap_buf = buf_from_str("0123456789.0123456789.0123456789");
+        gap_buf.insert_str(11, "9876543210.");
+
+        let text = gap_buf.to_string();
+        assert!(text == "0123456789.9876543210.0123456789.0123456789");
+    }
+
+    #[test]
+    fn insert_str_6() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(0, "0");
+        gap_buf.insert_str(9, "9");
+        gap_buf.insert_str(5, "-");
+
+        let text = gap_buf.to_string();
+        assert!(text == "01234-56789");
+    }
+
+    #[test]
+    fn append_1() {
+        let mut gap_buf = buf_from_str("1234");
+        gap_buf.insert_str(2, "-");
+        let mut other = buf_from_str("5678");
+        other.insert_str(2, "+");
+
+        gap_buf.append(&mut other);
+        assert!(gap_buf == "12-3456+78");
+        assert!(other.is_empty());
+
+        other.insert_str(0, "x");
+        assert!(other == "x");
+    }
+
+    #[test]
+    fn as_strs_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "é");
+
+        assert!(gap_buf.as_strs() == ("1234é", "5678"));
+        assert!(gap_buf.as_slices() == ("1234é".as_bytes(), &b"5678"[..]));
+    }
+
+    #[test]
+    fn byte_at_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        assert!(gap_buf.byte_at(0) == Some(b'1'));
+        assert!(gap_buf.byte_at(4) == Some(b'-'));
+        assert!(gap_buf.byte_at(8) == Some(b'8'));
+        assert!(gap_buf.byte_at(9).is_none());
+    }
+
+    #[test]
+    fn char_at_1() {
+        let mut gap_buf = buf_from_str("a€b");
+        gap_buf.raw.move_gap_to(3);
+
+        assert!(gap_buf.char_at(0) == Some('a'));
+        assert!(gap_buf.char_at(1) == Some('€'));
+        assert!(gap_buf.char_at(2).is_none());
+        assert!(gap_buf.char_at(4) == Some('b'));
+        assert!(gap_buf.char_at(5).is_none());
+    }
+
+    #[test]
+    fn check_invariants_1() {
+        let mut gap_buf = buf_from_str("añb");
+        gap_buf.cursor(1);
+        assert!(gap_buf.check_invariants().is_ok());
+
+        gap_buf.raw.move_gap_to(2);
+        assert!(gap_buf.check_invariants() == Err(InvariantError::InvalidUtf8));
+        gap_buf.raw.move_gap_to(3);
+        assert!(gap_buf.check_invariants().is_ok());
+    }
+
+    #[test]
+    fn clone_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        let mut copy = gap_buf.clone();
+        assert!(copy.capacity() == gap_buf.capacity());
+        copy.insert_str(0, "0");
+        copy.remove(5..6);
+
+        assert!(gap_buf == "1234-5678");
+        assert!(copy == "012345678");
+    }
+
+    #[test]
+    fn debug_1() {
+        let mut gap_buf = GapString::with_capacity(16);
+        gap_buf.insert_str(0, "abcdef");
+        gap_buf.insert_str(3, "");
+
+        let text = format!("{:?}", gap_buf);
+        assert!(text == r#"GapString { head: "abc", gap: 10, tail: "def" }"#);
+    }
+
+    #[test]
+    fn display_1() {
+        let mut gap_buf = GapString::with_capacity(16);
+        gap_buf.insert_str(0, "añdef");
+        gap_buf.insert_str(3, "");
+        assert!(gap_buf.as_strs() == ("añ", "def"));
+        assert!(format!("<{}>", gap_buf) == "<añdef>");
+    }
+
+    #[test]
+    fn from_1() {
+        let mut gap_buf = GapString::from("12345678");
+        assert!(gap_buf.capacity() == 8);
+        gap_buf.insert_str(8, "9");
+        assert!(gap_buf == "123456789");
+
+        let gap_buf = GapString::from(String::from("abc"));
+        assert!(gap_buf == "abc");
+    }
+
+    #[test]
+    fn into_string_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        let capacity = gap_buf.capacity();
+
+        let text = gap_buf.into_string();
+        assert!(text == "1234-5678");
+        assert!(text.capacity() == capacity);
+    }
+
+    #[test]
+    fn len_1() {
+        let mut gap_buf = GapString::with_capacity(4);
+        assert!(gap_buf.is_empty());
+        assert!(gap_buf.capacity() == 4);
+
+        gap_buf.insert_str(0, "12345678");
+        assert!(gap_buf.len() == 8);
+        assert!(!gap_buf.is_empty());
+        assert!(gap_buf.capacity() >= 8);
+
+        gap_buf.remove(2..5);
+        assert!(gap_buf.len() == 5);
+    }
+
+    #[test]
+    fn clear_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        let capacity = gap_buf.capacity();
+
+        gap_buf.clear();
+        assert!(gap_buf.is_empty());
+        assert!(gap_buf.capacity() == capacity);
+
+        gap_buf.insert_str(0, "new");
+        assert!(gap_buf == "new");
+    }
+
+    #[test]
+    fn default_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        let taken = mem::take(&mut gap_buf);
+        assert!(taken == "12345678");
+        assert!(gap_buf.is_empty() && gap_buf.capacity() == 0);
+
+        gap_buf.push_str("new");
+        assert!(gap_buf == "new");
+    }
+
+    #[test]
+    fn drain_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
 
-        let src_ptr = s.as_bytes().as_ptr();
-        unsafe {
-            libc::memcpy(self.gap_start as *mut libc::c_void,
-                         src_ptr as *const libc::c_void,
-                         s_len as usize);
-            self.gap_start = self.gap_start.offset(s_len);
-        }
+        assert!(gap_buf.drain(3..6) == "4-5");
+        assert!(gap_buf.drain(0..0).is_empty());
+        assert!(gap_buf == "123678");
     }
 
-    /// Removes `range` from the buffer.
-    pub fn remove(&mut self, range: Range<usize>) {
-        let buf_len = self.buf_len() as usize;
-        assert!(range.start < range.end, "Invalid range: {:?}", range);
-        assert!(range.start < buf_len);
-        assert!(range.end <= buf_len);
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn drain_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.drain(4..9);
+    }
 
-        let s = self.to_string();
-        let head = &s[0..range.start];
-        let tail = &s[range.end..];
+    #[test]
+    fn eq_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(3, "-");
 
-        self.clear();
-        self.insert_str(0, &head);
-        self.insert_str(head.len(), &tail);
+        assert!(gap_buf == "123-45678");
+        assert!(gap_buf == *"123-45678");
+        let owned = String::from("123-45678");
+        assert!(gap_buf == owned);
+        assert!("123-45678" == gap_buf);
+        assert!(gap_buf != "123-4567");
+        assert!(gap_buf != "123-45679");
     }
 
-    /// Creates a new buffer with a `capacity` sized allocation.
-    ///
-    /// # Panics
-    ///
-    /// * If `malloc` returns `NULL`.
-    pub fn with_capacity(capacity: usize) -> GapBuffer {
-        let buffer = unsafe {
-            let size = mem::size_of::<u8>() * capacity;
-            libc::malloc(size) as *mut u8
-        };
+    #[test]
+    fn eq_2() {
+        let mut a = buf_from_str("12345678");
+        a.insert_str(2, "-");
+        let mut b = buf_from_str("12-345678");
+        b.insert_str(7, "");
 
-        // malloc will return NULL if called with zero.
-        if buffer.is_null() && capacity != 0 {
-            panic!("Unable to allocate requested capacity");
-        }
+        assert!(a == b);
+        b.remove(0..1);
+        assert!(a != b);
+    }
 
-        GapBuffer {
-            buf_start: buffer,
-            gap_start: buffer,
-            gap_end: unsafe { buffer.offset(capacity as isize) },
-            buf_end: unsafe { buffer.offset(capacity as isize) }
-        }
+    #[test]
+    fn from_iter_1() {
+        let gap_buf: GapString = "a-b-c".split('-').collect();
+        assert!(gap_buf == "abc");
+
+        let gap_buf: GapString = "a\nb".lines().map(|l| l.to_uppercase()).collect();
+        assert!(gap_buf == "AB");
+
+        let mut gap_buf: GapString = "héllo".chars().rev().collect();
+        gap_buf.extend(vec!["!", "?"]);
+        gap_buf.extend("12".chars());
+        assert!(gap_buf == "olléh!?12");
     }
 
-    fn allocate_extra(&mut self, extra: isize) {
-        let current_size = ptr_diff(self.buf_end, self.buf_start);
-        let new_size = mem::size_of::<u8>()
-            * extra as usize
-            + current_size as usize;
+    #[test]
+    fn fmt_write_1() {
+        let mut gap_buf = buf_from_str("a");
+        let (key, value) = (1, 'b');
+        write!(gap_buf, "{}: {}", key, value).unwrap();
+        assert!(gap_buf == "a1: b");
 
-        let new_buf = unsafe {
-            libc::realloc(self.buf_start as *mut libc::c_void,
-                          new_size) as *mut u8
-        };
+        gap_buf.set_readonly(true);
+        assert!(write!(gap_buf, "c").is_err());
+        assert!(gap_buf == "a1: b");
+    }
 
-        assert!(!new_buf.is_null(), "Out of memory");
+    #[test]
+    fn get_1() {
+        let mut gap_buf = buf_from_str("a€bc");
+        gap_buf.insert_str(4, "-");
 
-        self.buf_start = new_buf;
+        assert!(gap_buf.get(0..4).unwrap() == "a€");
+        assert!(gap_buf.get(4..6).unwrap() == "-b");
+        assert!(gap_buf.get(0..2).is_none());
+        assert!(gap_buf.get(4..8).is_none());
+        assert!(gap_buf.get(7..8).is_none());
     }
 
-    fn buf_len(&self) -> isize {
-        let head_len = ptr_diff(self.gap_start, self.buf_start);
-        let tail_len = ptr_diff(self.buf_end, self.gap_end);
-        head_len + tail_len
+    #[test]
+    fn hash_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        assert!(hash(&gap_buf) == hash("1234-5678"));
+        assert!(hash(&gap_buf) != hash("1234-567"));
     }
 
-    fn clear(&mut self) {
-        self.gap_start = self.buf_start;
-        self.gap_end = self.buf_end;
+    #[test]
+    fn index_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        assert!(&gap_buf[0..5] == "1234-");
+        assert!(&gap_buf[5..9] == "5678");
     }
 
-    fn gap_len(&self) -> isize {
-        ptr_diff(self.gap_end, self.gap_start)
+    #[test]
+    #[should_panic(expected = "spans the gap")]
+    fn index_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        let _ = &gap_buf[3..6];
     }
 
-    fn grow_gap(&mut self, size: isize) {
-        let available = self.gap_len();
-        let needed = size - available;
+    #[test]
+    fn insert_char_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_char(4, '€');
+        gap_buf.insert_char(0, 'a');
+        let delta = gap_buf.insert_char(12, '\n');
+
+        assert!(gap_buf == "a1234€5678\n");
+        assert!(delta.offset == 12 && delta.removed_len == 0 && delta.inserted_len == 1);
+    }
 
-        let mut chunk = (needed as f32 / CHUNK_SIZE as f32).ceil() as isize;
-        chunk *= CHUNK_SIZE;
+    #[test]
+    #[should_panic(expected = "char boundary")]
+    fn insert_str_7() {
+        let mut gap_buf = buf_from_str("a€b");
+        gap_buf.insert_str(3, "x");
+    }
 
-        let head_len = ptr_diff(self.gap_start, self.buf_start);
-        let tail_len = ptr_diff(self.buf_end, self.gap_end);
-        let new_gap_size = self.gap_len() + chunk;
-        let buf_len = head_len + tail_len;
+    #[test]
+    fn remove_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.remove(0..8);
 
-        self.allocate_extra(chunk);
-        unsafe {
-            libc::memmove(self.gap_start as *mut libc::c_void,
-                          self.gap_end as *const libc::c_void,
-                          tail_len as usize);
-            self.gap_start = self.buf_start.offset(buf_len);
-            self.gap_end = self.gap_start.offset(new_gap_size);
-            self.buf_end = self.gap_end;
-        }
+        let text = gap_buf.to_string();
+        assert!(text == "");
     }
 
-    fn head(&self) -> String {
-        let head_len = ptr_diff(self.gap_start, self.buf_start) as usize;
-        string_from_segment(self.buf_start, head_len)
+    #[test]
+    fn remove_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.remove(0..1);
+
+        let text = gap_buf.to_string();
+        assert!(text == "2345678");
     }
 
-    fn move_gap_to(&mut self, offset: isize) {
-        let gap_len = self.gap_len();
-        let new_pos = unsafe { self.buf_start.offset(offset) };
+    #[test]
+    fn remove_3() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.remove(7..8);
 
-        let diff = ptr_diff(new_pos, self.gap_start);
+        let text = gap_buf.to_string();
+        assert!(text == "1234567");
+    }
 
-        if diff == 0 { return; }
+    #[test]
+    fn remove_4() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.remove(3..6);
 
-        if diff < 0 {
-            unsafe {
-                self.gap_start = new_pos;
-                self.gap_end = self.gap_start.offset(gap_len);
-                libc::memmove(self.gap_end as *mut libc::c_void,
-                              self.gap_start as *mut libc::c_void,
-                              diff.abs() as usize);
-            }
-        } else {
-            unsafe {
-                self.gap_end = self.gap_end.offset(diff);
-                self.gap_start = self.gap_start.offset(diff);
-                libc::memmove(new_pos as *mut libc::c_void,
-                              self.gap_start as *mut libc::c_void,
-                              diff as usize);
-            }
-        }
+        let text = gap_buf.to_string();
+        assert!(text == "12378");
     }
 
-    fn tail(&self) -> String {
-        let tail_len = ptr_diff(self.buf_end, self.gap_end) as usize;
-        string_from_segment(self.gap_end, tail_len)
+    #[test]
+    #[should_panic]
+    fn remove_5() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.remove(0..9);
     }
-}
 
-impl fmt::Display for GapBuffer {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}{}", self.head(), self.tail())
+    #[test]
+    fn remove_6() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        gap_buf.remove(2..7);
+        gap_buf.remove(0..1);
+
+        let text = gap_buf.to_string();
+        assert!(text == "278");
     }
-}
 
-impl Drop for GapBuffer {
-    fn drop(&mut self) {
-        unsafe { libc::free(self.buf_start as *mut libc::c_void); }
+    #[test]
+    #[should_panic(expected = "char boundary")]
+    fn remove_7() {
+        let mut gap_buf = buf_from_str("a€b");
+        gap_buf.remove(0..2);
     }
-}
 
-fn ptr_to_isize(p: *const u8) -> isize {
-    unsafe { mem::transmute::<*const u8, isize>(p) }
-}
+    #[test]
+    fn new_1() {
+        let mut gap_buf = GapString::new();
+        assert!(gap_buf.is_empty());
+        gap_buf.insert_str(0, "abc");
+        assert!(gap_buf == "abc");
+    }
 
-fn ptr_diff(p: *const u8, q: *const u8) -> isize {
-    ptr_to_isize(p) - ptr_to_isize(q)
-}
+    #[test]
+    fn pop_char_1() {
+        let mut gap_buf = buf_from_str("aé€");
+        gap_buf.insert_str(1, "b");
+        assert!(gap_buf.pop_char() == Some('€'));
+        assert!(gap_buf.pop_char() == Some('é'));
+        assert!(gap_buf == "ab");
+        gap_buf.pop_char();
+        gap_buf.pop_char();
+        assert!(gap_buf.pop_char().is_none());
+    }
 
-fn string_from_segment(start: *mut u8, len: usize) -> String {
-    let mut s = String::with_capacity(len);
-    let tmp = unsafe { String::from_raw_parts(start, len, len) };
-    s.push_str(&tmp);
-    mem::forget(tmp);
-    s
-}
+    #[test]
+    fn push_str_1() {
+        let mut gap_buf = GapString::new();
+        for _ in 0..100 {
+            gap_buf.push_str("abc");
+        }
+        gap_buf.push_char('é');
 
+        assert!(gap_buf.len() == 302);
+        assert!(gap_buf == format!("{}é", "abc".repeat(100)));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::GapBuffer;
+    #[test]
+    fn push_str_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(0, "0");
+        let delta = gap_buf.push_str("9");
+        assert!(delta.offset == 9 && delta.inserted_len == 1);
+        let delta = gap_buf.push_char('€');
+        assert!(delta.offset == 10 && delta.inserted_len == 3);
 
+        assert!(gap_buf == "0123456789€");
+    }
 
     #[test]
-    fn insert_str_1() {
-        let gap_buf = buf_from_str("12345678");
-        let text = gap_buf.to_string();
-        assert!(text == "12345678");
+    fn reserve_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        gap_buf.reserve(100);
+        let capacity = gap_buf.capacity();
+        assert!(capacity >= 109);
+
+        gap_buf.push_str(&"x".repeat(100));
+        assert!(gap_buf.capacity() == capacity);
+        assert!(gap_buf.slice(0..9) == "1234-5678");
     }
 
     #[test]
-    fn insert_str_2() {
+    fn reserve_exact_1() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.insert_str(7, "9");
+        gap_buf.insert_str(4, "-");
+        let capacity = gap_buf.capacity();
+        let gap_len = capacity - gap_buf.len();
 
-        let text = gap_buf.to_string();
-        assert!(text == "123456798");
+        gap_buf.reserve_exact(gap_len + 10);
+        assert!(gap_buf.capacity() == capacity + 10);
+        assert!(gap_buf == "1234-5678");
+
+        gap_buf.reserve_exact(1);
+        assert!(gap_buf.capacity() == capacity + 10);
     }
 
     #[test]
-    fn insert_str_3() {
+    fn shrink_to_1() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.insert_str(0, "0");
+        gap_buf.insert_str(4, "-");
+        gap_buf.reserve(100);
 
-        let text = gap_buf.to_string();
-        assert!(text == "012345678");
+        gap_buf.shrink_to(20);
+        assert!(gap_buf.capacity() == 20);
+        assert!(gap_buf == "1234-5678");
+
+        gap_buf.shrink_to(50);
+        assert!(gap_buf.capacity() == 20);
+        gap_buf.shrink_to(0);
+        assert!(gap_buf.capacity() == 9);
+        assert!(gap_buf == "1234-5678");
     }
 
     #[test]
-    fn insert_str_4() {
-        let mut gap_buf = buf_from_str("0123456789.0123456789.0123456789");
-        gap_buf.insert_str(0, "9876543210.");
+    fn split_off_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        let tail = gap_buf.split_off(3);
+        assert!(gap_buf == "123");
+        assert!(tail == "4-5678");
+
+        let tail = gap_buf.split_off(3);
+        assert!(tail.is_empty());
+
+        let mut tail = tail;
+        tail.insert_str(0, "x");
+        assert!(tail == "x");
+    }
+
+    #[test]
+    fn truncate_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        gap_buf.truncate(7);
+        assert!(gap_buf == "1234-56");
+        gap_buf.truncate(3);
+        assert!(gap_buf == "123");
+        gap_buf.truncate(5);
+        assert!(gap_buf == "123");
+
+        gap_buf.insert_str(3, "4");
+        assert!(gap_buf == "1234");
+    }
+
+    #[test]
+    fn try_insert_str_1() {
+        let mut gap_buf = buf_from_str("a€b");
+
+        assert!(gap_buf.try_insert_str(6, "x") == Err(GapBufferError::OutOfBounds));
+        assert!(gap_buf.try_insert_str(2, "x") == Err(GapBufferError::NotCharBoundary));
+        gap_buf.try_insert_str(4, "x").unwrap();
+        assert!(gap_buf == "a€xb");
+    }
+
+    #[test]
+    fn remove_returning_1() {
+        let mut gap_buf = buf_from_str("12€45678");
+        gap_buf.enable_history(10);
+        gap_buf.cursor(2);
+        assert!(gap_buf.remove_returning(1..5) == "2€");
+        assert!(gap_buf == "145678");
+        assert!(gap_buf.try_remove_returning(0..0).unwrap().is_empty());
+        assert!(gap_buf.try_remove_returning(4..7) == Err(GapBufferError::OutOfBounds));
+
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "12€45678");
+    }
+
+    #[test]
+    fn try_insert_bytes_1() {
+        let mut gap_buf = buf_from_str("ab");
+        gap_buf.insert_bytes(1, "é".as_bytes());
+        assert!(gap_buf == "aéb");
+        assert!(gap_buf.try_insert_bytes(0, b"\xc3") == Err(GapBufferError::InvalidUtf8));
+        assert!(gap_buf == "aéb");
+    }
+
+    #[test]
+    fn try_remove_1() {
+        let mut gap_buf = buf_from_str("a€b");
+
+        assert!(gap_buf.try_remove(0..6) == Err(GapBufferError::OutOfBounds));
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 3..1;
+        assert!(gap_buf.try_remove(reversed) == Err(GapBufferError::OutOfBounds));
+        assert!(gap_buf.try_remove(0..2) == Err(GapBufferError::NotCharBoundary));
+        gap_buf.try_remove(2..2).unwrap_err();
+        gap_buf.try_remove(1..1).unwrap();
+        gap_buf.try_remove(1..4).unwrap();
+        assert!(gap_buf == "ab");
+    }
+
+    #[test]
+    fn from_file_1() {
+        let path = temp_file("from_file_1", "one\ntwo\n");
+        let gap_buf = GapString::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(gap_buf == "one\ntwo\n");
+    }
+
+    #[test]
+    fn from_utf8_1() {
+        let mut bytes = GapBytes::from("naïve".as_bytes().to_vec());
+        bytes.move_gap_to(3);
+        let gap_buf = GapString::from_utf8(bytes).unwrap();
+        assert!(gap_buf == "naïve");
+        assert!(gap_buf.as_bytes().len() == 6);
+
+        let mut bytes = gap_buf.into_bytes();
+        bytes.insert(3, b'x');
+        let err = GapString::from_utf8(bytes).unwrap_err();
+        assert!(err.utf8_error().valid_up_to() == 2);
+        assert!(err.into_bytes().len() == 7);
+    }
+
+    #[test]
+    fn from_reader_1() {
+        let gap_buf = GapString::from_reader(&b"naive caf\xc3\xa9"[..]).unwrap();
+        assert!(gap_buf == "naive café");
+
+        let result = GapString::from_reader(&b"caf\xc3"[..]);
+        assert!(result.unwrap_err().kind() == io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn save_to_file_1() {
+        let path = temp_file("save_to_file_1", "old content");
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        gap_buf.save_to_file(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(text == "1234-5678");
+    }
+
+    #[test]
+    fn reload_from_file_1() {
+        let path = temp_file("reload_from_file_1", "one\nthree\nfour\n");
+        let mut gap_buf = buf_from_str("one\ntwo\nthree\n");
+        gap_buf.reload_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
         let text = gap_buf.to_string();
-        assert!(text == "9876543210.0123456789.0123456789.0123456789");
+        assert!(text == "one\nthree\nfour\n");
     }
 
     #[test]
-    fn insert_str_5() {
-        let mut gap_buf = buf_from_str("0123456789.0123456789.0123456789");
-        gap_buf.insert_str(11, "9876543210.");
+    fn reload_from_file_2() {
+        let path = temp_file("reload_from_file_2", "naïve café");
+        let mut gap_buf = buf_from_str("naïve cafè");
+        gap_buf.reload_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
         let text = gap_buf.to_string();
-        assert!(text == "0123456789.9876543210.0123456789.0123456789");
+        assert!(text == "naïve café");
     }
 
     #[test]
-    fn remove_1() {
+    fn reload_from_file_3() {
+        let path = temp_file("reload_from_file_3", "");
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.remove(0..8);
+        gap_buf.reload_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
 
         let text = gap_buf.to_string();
-        assert!(text == "");
+        assert!(text.is_empty());
     }
 
     #[test]
-    fn remove_2() {
+    fn reload_from_file_4() {
+        let path = temp_file("reload_from_file_4", "zero\none\ntwo\nthree\nfour\n");
+        let mut gap_buf = buf_from_str("one\ntwo\nthree\n");
+        let mark = gap_buf.create_mark(5, Bias::Left);
+        gap_buf.reload_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(gap_buf == "zero\none\ntwo\nthree\nfour\n");
+        assert!(gap_buf.mark_offset(mark) == Some(10));
+    }
+
+    #[test]
+    fn reload_from_file_5() {
+        let path = temp_file("reload_from_file_5", "one\r\ntwo\r\n2\r\n");
+        let text = "one\r\ntwo\r\n";
+        let mut gap_buf = GapString::from_reader_normalized(text.as_bytes()).unwrap();
+        let mark = gap_buf.create_mark(5, Bias::Left);
+        gap_buf.reload_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(gap_buf == "one\ntwo\n2\n");
+        assert!(gap_buf.line_ending() == LineEnding::CrLf);
+        assert!(gap_buf.mark_offset(mark) == Some(5));
+    }
+
+    #[test]
+    fn make_contiguous_1() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.remove(0..1);
+        gap_buf.insert_str(4, "-");
 
-        let text = gap_buf.to_string();
-        assert!(text == "2345678");
+        assert!(gap_buf.make_contiguous() == "1234-5678");
+        assert!(gap_buf.as_strs() == ("1234-5678", ""));
     }
 
     #[test]
-    fn remove_3() {
+    fn max_capacity_1() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.remove(7..8);
+        gap_buf.set_max_capacity(Some(12));
+
+        assert!(gap_buf.try_insert_str(4, "abcde").is_err());
+        assert!(gap_buf == "12345678");
+
+        gap_buf.try_insert_str(4, "abcd").unwrap();
+        assert!(gap_buf == "1234abcd5678");
+    }
+
+    #[test]
+    #[should_panic(expected = "Maximum capacity exceeded")]
+    fn max_capacity_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.set_max_capacity(Some(8));
+        gap_buf.insert_str(0, "0");
+    }
+
+    #[test]
+    fn replace_range_1() {
+        let mut gap_buf = buf_from_str("let x = 1;");
+        gap_buf.replace_range(4..5, "value");
+        assert!(gap_buf == "let value = 1;");
+
+        gap_buf.replace_range(12..13, "");
+        gap_buf.replace_range(12..12, "42");
+        assert!(gap_buf == "let value = 42;");
+    }
+
+    #[test]
+    fn replace_range_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.set_max_capacity(Some(8));
+
+        let result = gap_buf.try_replace_range(0..1, "ab");
+        assert!(result == Err(GapBufferError::CapacityExceeded));
+        assert!(gap_buf == "12345678");
+
+        gap_buf.replace_range(0..2, "ab");
+        assert!(gap_buf == "ab345678");
+    }
+
+    #[test]
+    fn shrink_to_fit_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        gap_buf.shrink_to_fit();
+        assert!(gap_buf.raw.gap_len() == 0);
 
+        gap_buf.insert_str(0, "0");
         let text = gap_buf.to_string();
-        assert!(text == "1234567");
+        assert!(text == "01234-5678");
     }
 
     #[test]
-    fn remove_4() {
+    fn shrink_to_fit_2() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.remove(3..6);
+        gap_buf.remove(0..8);
+        gap_buf.shrink_to_fit();
+        assert!(gap_buf.to_string().is_empty());
+        gap_buf.insert_str(0, "abc");
 
         let text = gap_buf.to_string();
-        assert!(text == "12378");
+        assert!(text == "abc");
+    }
+
+    #[test]
+    fn with_gap_capacity_1() {
+        let mut gap_buf = GapString::with_gap_capacity("12345678", 16, 4);
+        assert!(gap_buf.capacity() == 24);
+        assert!(gap_buf.raw.gap_len() == 16);
+        assert!(gap_buf == "12345678");
+
+        gap_buf.insert_str(4, &"-".repeat(16));
+        assert!(gap_buf.capacity() == 24);
+        assert!(gap_buf.slice(3..21) == format!("4{}5", "-".repeat(16)));
     }
 
     #[test]
     #[should_panic]
-    fn remove_5() {
+    fn with_gap_capacity_2() {
+        GapString::with_gap_capacity("é", 16, 1);
+    }
+
+    #[test]
+    fn growth_policy_1() {
+        let mut gap_buf = GapString::new();
+        gap_buf.push_str("12345678");
+        gap_buf.push_str("9");
+        assert!(gap_buf.capacity() == 16);
+
+        let mut gap_buf = GapString::with_growth_policy(GrowthPolicy::Chunk(32));
+        gap_buf.push_str("12345678");
+        assert!(gap_buf.capacity() == 32);
+        gap_buf.insert_str(0, &"x".repeat(30));
+        assert!(gap_buf.capacity() == 64);
+
+        fn exact(_: usize, needed: usize) -> usize { needed }
+        let mut gap_buf = GapString::with_growth_policy(GrowthPolicy::Custom(exact));
+        gap_buf.push_str("12345678");
+        gap_buf.push_str("9");
+        assert!(gap_buf.capacity() == 9);
+        assert!(gap_buf == "123456789");
+    }
+
+    #[test]
+    fn shrink_policy_1() {
+        let mut gap_buf = buf_from_str(&"x".repeat(1024));
+        gap_buf.remove(10..1024);
+        assert!(gap_buf.raw.gap_len() > 1000);
+
+        let mut gap_buf = buf_from_str(&"x".repeat(1024));
+        gap_buf.set_shrink_policy(ShrinkPolicy::Ratio(2.0));
+        gap_buf.remove(10..1024);
+        assert!(gap_buf.raw.gap_len() == CHUNK_SIZE);
+        assert!(gap_buf == "x".repeat(10));
+
+        let mut gap_buf = buf_from_str(&"x".repeat(1024));
+        gap_buf.set_shrink_policy(ShrinkPolicy::Ratio(2.0));
+        gap_buf.replace_range(10..1024, "y");
+        assert!(gap_buf.raw.gap_len() == CHUNK_SIZE);
+        assert!(gap_buf == format!("{}y", "x".repeat(10)));
+    }
+
+    #[test]
+    fn readonly_1() {
         let mut gap_buf = buf_from_str("12345678");
-        gap_buf.remove(0..9);
+        gap_buf.set_readonly(true);
+        assert!(gap_buf.try_insert_str(0, "0") == Err(GapBufferError::ReadOnly));
+
+        gap_buf.set_readonly(false);
+        gap_buf.try_insert_str(0, "0").unwrap();
+        assert!(gap_buf == "012345678");
     }
 
-    fn buf_from_str(s: &str) -> GapBuffer {
-        let mut buf = GapBuffer::with_capacity(s.len());
-        buf.insert_str(0, s);
-        buf
+    #[test]
+    #[should_panic(expected = "Buffer is read-only")]
+    fn readonly_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.set_readonly(true);
+        gap_buf.remove(0..1);
+    }
+
+    #[test]
+    fn slice_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        assert!(gap_buf.slice(0..3) == "123");
+        assert!(gap_buf.slice(6..9) == "678");
+        assert!(gap_buf.slice(3..6) == "4-5");
+        assert!(gap_buf.slice(9..9).is_empty());
+        assert!(matches!(gap_buf.slice(0..5), Cow::Borrowed(_)));
+        assert!(matches!(gap_buf.slice(2..7), Cow::Owned(_)));
+    }
+
+    #[test]
+    #[should_panic(expected = "char boundaries")]
+    fn slice_2() {
+        let gap_buf = buf_from_str("€");
+        gap_buf.slice(0..1);
+    }
+
+    #[test]
+    fn send_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(10);
+        gap_buf.on_change(|_| {});
+        let snapshot = gap_buf.snapshot();
+
+        let gap_buf = thread::spawn(move || {
+            gap_buf.insert_str(4, "-");
+            gap_buf
+        }).join().unwrap();
+        assert!(gap_buf == "1234-5678");
+        assert!(snapshot.as_str() == "12345678");
+        thread::spawn(move || drop(gap_buf)).join().unwrap();
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("gap-buffer-{}-{}", name, process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
     }
 }
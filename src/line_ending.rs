@@ -0,0 +1,216 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::io::{self, Read};
+
+use {Edit, GapString};
+
+
+/// Style of line break a buffer's content is written with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineEnding {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    CrLf,
+    /// `\r`, as on classic Mac OS.
+    Cr
+}
+
+impl GapString {
+    /// Returns the line ending most lines of the content end with, or `None`
+    /// if it has no line breaks.
+    ///
+    /// Ties go to `Lf`, then to `CrLf`.
+    pub fn detect_line_ending(&self) -> Option<LineEnding> {
+        dominant(self.bytes())
+    }
+
+    /// Creates a buffer holding everything read from `reader`, with every
+    /// line ending normalized to `\n`.
+    ///
+    /// The line ending most lines ended with becomes that of the buffer,
+    /// so that `write_to` writes them back the same way.
+    ///
+    /// # Errors
+    ///
+    /// * If reading fails.
+    /// * If the content is not valid UTF-8.
+    pub fn from_reader_normalized<R: Read>(mut reader: R) -> io::Result<GapString> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let line_ending = dominant(bytes.iter().cloned());
+        normalize(&mut bytes);
+
+        let mut buf = GapString::from_bytes(bytes)?;
+        buf.line_ending = line_ending.unwrap_or_default();
+        Ok(buf)
+    }
+
+    /// Returns the line ending every `\n` of the content is written as by
+    /// `write_to`, `Lf` unless set otherwise.
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Replaces every `\r\n` and lone `\r` of the content with `\n`, as a
+    /// batch undone in a single step if the history is enabled.
+    ///
+    /// Unless the content already only used `\n`, the line ending most
+    /// lines ended with becomes that of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `apply_edits`.
+    pub fn normalize_line_endings(&mut self) {
+        if !self.contains('\r') {
+            return;
+        }
+        self.line_ending = self.detect_line_ending().unwrap_or_default();
+
+        let mut edits = Vec::new();
+        let mut bytes = self.bytes().enumerate().peekable();
+        while let Some((i, b)) = bytes.next() {
+            if b != b'\r' {
+                continue;
+            }
+            // The `\n` of a `\r\n` stays, along with any mark on it.
+            let text = match bytes.peek() {
+                Some(&(_, b'\n')) => "",
+                _ => "\n"
+            };
+            edits.push(Edit { range: i..i + 1, text: text.to_string() });
+        }
+        self.apply_edits(&edits);
+    }
+
+    /// Sets the line ending every `\n` of the content is written as by
+    /// `write_to`.
+    ///
+    /// The content is expected to be normalized: a `\r\n` left in it is
+    /// written with a `\r` in front of the line ending.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// Returns the content as written, with every `\n` replaced by the line
+    /// ending.
+    pub(crate) fn output_chunks(&self) -> impl Iterator<Item = &str> {
+        let line_ending = self.line_ending;
+        let translate = line_ending != LineEnding::Lf;
+        self.chunks()
+            .flat_map(|chunk| chunk.split_inclusive('\n'))
+            .flat_map(move |piece| match piece.strip_suffix('\n') {
+                Some(line) if translate => [line, line_ending.as_str()],
+                _ => [piece, ""]
+            })
+    }
+}
+
+impl LineEnding {
+    /// Returns the line ending as text.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r"
+        }
+    }
+}
+
+/// Returns the line ending `bytes` use most.
+fn dominant<I: Iterator<Item = u8>>(bytes: I) -> Option<LineEnding> {
+    let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+    let mut bytes = bytes.peekable();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\n' => lf += 1,
+            b'\r' if bytes.peek() == Some(&b'\n') => {
+                bytes.next();
+                crlf += 1;
+            }
+            b'\r' => cr += 1,
+            _ => {}
+        }
+    }
+
+    // The last of equal counts wins.
+    [(cr, LineEnding::Cr), (crlf, LineEnding::CrLf), (lf, LineEnding::Lf)].iter()
+        .filter(|&&(count, _)| count > 0)
+        .max_by_key(|&&(count, _)| count)
+        .map(|&(_, line_ending)| line_ending)
+}
+
+/// Replaces every `\r\n` and lone `\r` in `bytes` with `\n`, in place.
+fn normalize(bytes: &mut Vec<u8>) {
+    let mut len = 0;
+    for i in 0..bytes.len() {
+        bytes[len] = match bytes[i] {
+            b'\r' if bytes.get(i + 1) == Some(&b'\n') => continue,
+            b'\r' => b'\n',
+            b => b
+        };
+        len += 1;
+    }
+    bytes.truncate(len);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, Bias, GapString};
+    use super::LineEnding;
+
+
+    #[test]
+    fn detect_line_ending_1() {
+        assert!(GapString::from("a\r\nb\r\nc\n").detect_line_ending() == Some(LineEnding::CrLf));
+        assert!(GapString::from("a\rb\r\nc\n").detect_line_ending() == Some(LineEnding::Lf));
+        assert!(GapString::from("a\rb").detect_line_ending() == Some(LineEnding::Cr));
+        assert!(GapString::from("ab").detect_line_ending().is_none());
+    }
+
+    #[test]
+    fn from_reader_normalized_1() {
+        let text = "one\r\ntwo\r\nthree\r";
+        let gap_buf = GapString::from_reader_normalized(text.as_bytes()).unwrap();
+        assert!(gap_buf == "one\ntwo\nthree\n");
+        assert!(gap_buf.line_ending() == LineEnding::CrLf);
+
+        let mut out = Vec::new();
+        gap_buf.write_to(&mut out).unwrap();
+        assert!(out == b"one\r\ntwo\r\nthree\r\n");
+    }
+
+    #[test]
+    fn normalize_line_endings_1() {
+        let mut gap_buf = buf_from_str("a\rb\r\nc\r\n");
+        gap_buf.enable_history(10);
+        let mark = gap_buf.create_mark(4, Bias::Right);
+        gap_buf.normalize_line_endings();
+        assert!(gap_buf == "a\nb\nc\n");
+        assert!(gap_buf.line_ending() == LineEnding::CrLf);
+        assert!(gap_buf.mark_offset(mark) == Some(3));
+
+        gap_buf.set_line_ending(LineEnding::Cr);
+        gap_buf.normalize_line_endings();
+        assert!(gap_buf.line_ending() == LineEnding::Cr);
+        gap_buf.undo();
+        assert!(gap_buf == "a\rb\r\nc\r\n");
+    }
+
+    #[test]
+    fn normalize_line_endings_2() {
+        let mut gap_buf = GapString::from("a\r\nb\nc\n");
+        gap_buf.set_line_ending(LineEnding::Cr);
+        gap_buf.normalize_line_endings();
+        assert!(gap_buf == "a\nb\nc\n");
+        assert!(gap_buf.line_ending() == LineEnding::Lf);
+    }
+}
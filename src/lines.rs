@@ -0,0 +1,613 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::borrow::Cow;
+use std::mem;
+use std::ops::Range;
+
+use GapString;
+
+
+/// Length the line index splits the content into when (re)building chunks.
+const CHUNK_LEN: usize = 1024;
+
+
+/// Newline and UTF-16 code unit counts of the content, split into chunks of
+/// around `CHUNK_LEN` bytes with Fenwick trees over their lengths and counts.
+///
+/// Chunks are built with an empty one after each, and edits update the
+/// chunks they touch in O(log n) each. A chunk growing past `2 * CHUNK_LEN`
+/// is split into the empty chunks after it, and only when there are none
+/// left are the chunks rebuilt along with the trees.
+#[derive(Clone, Debug)]
+pub(crate) struct LineIndex {
+    lens: Vec<usize>,
+    newlines: Vec<usize>,
+    utf16: Vec<usize>,
+    len_tree: Fenwick,
+    newline_tree: Fenwick,
+    utf16_tree: Fenwick
+}
+
+/// Fenwick tree over a sequence of counts.
+#[derive(Clone, Debug)]
+struct Fenwick {
+    tree: Vec<usize>
+}
+
+impl GapString {
+    /// Returns the line containing `offset`, counting from 0. A line ends
+    /// after its `'\n'`.
+    ///
+    /// Takes O(log n) time with the line index enabled, and O(n) otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds.
+    pub fn byte_to_line(&self, offset: usize) -> usize {
+        assert!(offset <= self.len(), "Offset {} out of bounds", offset);
+        match self.lines {
+            Some(ref index) => index.byte_to_line(self, offset),
+            None => count_newlines(self, 0..offset)
+        }
+    }
+
+    /// Returns the number of UTF-16 code units before `offset`.
+    ///
+    /// Takes O(log n) time with the line index enabled, and O(n) otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn byte_to_utf16_cu(&self, offset: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+
+        let (start, units) = match self.lines {
+            Some(ref index) => {
+                let chunk = index.chunk_at(offset);
+                (index.len_tree.prefix(chunk), index.utf16_tree.prefix(chunk))
+            }
+            None => (0, 0)
+        };
+        let (a, b) = byte_range(self, start..offset);
+        units + a.iter().chain(b).map(|b| utf16_len(*b)).sum::<usize>()
+    }
+
+    /// Drops the line index, see `enable_line_index`.
+    pub fn disable_line_index(&mut self) {
+        self.lines = None;
+    }
+
+    /// Builds a line index that is kept up to date by every edit, making
+    /// line and UTF-16 offset lookups take O(log n) instead of O(n) time.
+    pub fn enable_line_index(&mut self) {
+        if self.lines.is_none() {
+            self.lines = Some(LineIndex::new(self));
+        }
+    }
+
+    /// Returns line `line`, including its `'\n'` if it has one.
+    ///
+    /// Borrows from the buffer unless the line spans the gap, like `slice`.
+    ///
+    /// # Panics
+    ///
+    /// * If there is no such line.
+    pub fn line(&self, line: usize) -> Cow<'_, str> {
+        self.line_slice(line..line + 1)
+    }
+
+    /// Returns the number of lines, which is one more than the number of
+    /// `'\n'`s.
+    pub fn line_count(&self) -> usize {
+        match self.lines {
+            Some(ref index) => index.newline_tree.prefix(index.lens.len()) + 1,
+            None => count_newlines(self, 0..self.len()) + 1
+        }
+    }
+
+    /// Returns the content of `lines`, such as the lines in a viewport,
+    /// including the `'\n'` of the last one if it has one.
+    ///
+    /// Only the lines asked for are read, and the range is found in O(log n)
+    /// time with the line index enabled.
+    ///
+    /// # Panics
+    ///
+    /// * If `lines` is reversed or ends past the last line.
+    pub fn line_slice(&self, lines: Range<usize>) -> Cow<'_, str> {
+        assert!(lines.start <= lines.end, "Invalid range: {:?}", lines);
+        assert!(lines.end <= self.line_count(), "Lines {:?} out of bounds", lines);
+
+        let start = self.line_to_byte(lines.start).unwrap_or(self.len());
+        let end = self.line_to_byte(lines.end).unwrap_or(self.len());
+        self.slice(start..end)
+    }
+
+    /// Returns the offset `line` starts at, or `None` if there is no such
+    /// line.
+    ///
+    /// Takes O(log n) time with the line index enabled, and O(n) otherwise.
+    pub fn line_to_byte(&self, line: usize) -> Option<usize> {
+        if line == 0 {
+            return Some(0);
+        }
+        match self.lines {
+            Some(ref index) => index.line_to_byte(self, line),
+            None => nth_line_start(self, 0..self.len(), line)
+        }
+    }
+
+    /// Returns the line and column of `offset`, with the column counted in
+    /// chars.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn offset_to_position(&self, offset: usize) -> (usize, usize) {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+
+        let line = self.byte_to_line(offset);
+        let start = self.line_to_byte(line).unwrap();
+        let (a, b) = byte_range(self, start..offset);
+        let col = a.iter().chain(b).filter(|b| is_char_start(**b)).count();
+        (line, col)
+    }
+
+    /// Returns the offset of column `col`, counted in chars, within `line`,
+    /// or `None` if the line doesn't exist or is shorter than `col`. The
+    /// column after the last char of a line, before its `"\n"` or `"\r\n"`,
+    /// is valid.
+    pub fn position_to_offset(&self, line: usize, col: usize) -> Option<usize> {
+        let start = self.line_to_byte(line)?;
+        let end = match self.line_to_byte(line + 1) {
+            Some(next) if next - 1 > start && self.byte_at(next - 2) == Some(b'\r') => next - 2,
+            Some(next) => next - 1,
+            None => self.len()
+        };
+        let (a, b) = byte_range(self, start..end);
+        a.iter()
+            .chain(b)
+            .enumerate()
+            .filter(|&(_, b)| is_char_start(*b))
+            .map(|(i, _)| start + i)
+            .chain(Some(end))
+            .nth(col)
+    }
+
+    /// Returns the offset `units` UTF-16 code units into the content, or
+    /// `None` if that is past the end or within a surrogate pair.
+    ///
+    /// Takes O(log n) time with the line index enabled, and O(n) otherwise.
+    pub fn utf16_cu_to_byte(&self, units: usize) -> Option<usize> {
+        let (start, mut counted) = match self.lines {
+            Some(ref index) => {
+                let chunk = index.utf16_tree.position(units).min(index.lens.len() - 1);
+                (index.len_tree.prefix(chunk), index.utf16_tree.prefix(chunk))
+            }
+            None => (0, 0)
+        };
+
+        // A chunk can start within a char counted by the previous chunk, so
+        // only char starts are considered.
+        let (a, b) = byte_range(self, start..self.len());
+        for (i, byte) in a.iter().chain(b).enumerate() {
+            if is_char_start(*byte) {
+                if counted >= units {
+                    return if counted == units { Some(start + i) } else { None };
+                }
+                counted += utf16_len(*byte);
+            }
+        }
+        if counted == units { Some(self.len()) } else { None }
+    }
+
+    /// Updates the line index after `removed` bytes at `offset` were
+    /// replaced by `inserted` bytes.
+    pub(crate) fn adjust_lines(&mut self,
+                               offset: usize,
+                               removed: usize,
+                               inserted: usize)
+    {
+        if let Some(mut index) = self.lines.take() {
+            index.update(self, offset, removed, inserted);
+            self.lines = Some(index);
+        }
+    }
+}
+
+impl LineIndex {
+    /// Returns the number of bytes allocated for the index.
+    pub(crate) fn heap_size(&self) -> usize {
+        let counts = self.lens.capacity() + self.newlines.capacity() + self.utf16.capacity();
+        let trees = self.len_tree.tree.capacity()
+            + self.newline_tree.tree.capacity()
+            + self.utf16_tree.tree.capacity();
+        (counts + trees) * mem::size_of::<usize>()
+    }
+
+    fn new(buf: &GapString) -> LineIndex {
+        let mut index = LineIndex {
+            lens: Vec::new(),
+            newlines: Vec::new(),
+            utf16: Vec::new(),
+            len_tree: Fenwick::new(&[]),
+            newline_tree: Fenwick::new(&[]),
+            utf16_tree: Fenwick::new(&[])
+        };
+        index.rechunk(buf, 0..0, 0..buf.len());
+        index
+    }
+
+    fn byte_to_line(&self, buf: &GapString, offset: usize) -> usize {
+        let chunk = self.chunk_at(offset);
+        let start = self.len_tree.prefix(chunk);
+        self.newline_tree.prefix(chunk) + count_newlines(buf, start..offset)
+    }
+
+    /// Returns the chunk holding the byte at `offset`, or the last chunk for
+    /// the end of the content.
+    fn chunk_at(&self, offset: usize) -> usize {
+        self.len_tree.position(offset).min(self.lens.len() - 1)
+    }
+
+    fn line_to_byte(&self, buf: &GapString, line: usize) -> Option<usize> {
+        if line > self.newline_tree.prefix(self.lens.len()) {
+            return None;
+        }
+
+        let chunk = self.newline_tree.position(line - 1);
+        let start = self.len_tree.prefix(chunk);
+        let nth = line - self.newline_tree.prefix(chunk);
+        nth_line_start(buf, start..start + self.lens[chunk], nth)
+    }
+
+    /// Replaces the chunks within `chunks` by new chunks covering `bytes`,
+    /// each followed by an empty one, and rebuilds the trees.
+    fn rechunk(&mut self, buf: &GapString, chunks: Range<usize>, bytes: Range<usize>) {
+        let mut lens = Vec::new();
+        let mut newlines = Vec::new();
+        let mut utf16 = Vec::new();
+        let mut start = bytes.start;
+        while start < bytes.end {
+            let end = bytes.end.min(start + CHUNK_LEN);
+            let (chunk_newlines, chunk_utf16) = count_chunk(buf, start..end);
+            lens.extend(&[end - start, 0]);
+            newlines.extend(&[chunk_newlines, 0]);
+            utf16.extend(&[chunk_utf16, 0]);
+            start = end;
+        }
+
+        self.lens.splice(chunks.clone(), lens);
+        self.newlines.splice(chunks.clone(), newlines);
+        self.utf16.splice(chunks, utf16);
+        if self.lens.is_empty() {
+            self.lens.push(0);
+            self.newlines.push(0);
+            self.utf16.push(0);
+        }
+        self.len_tree = Fenwick::new(&self.lens);
+        self.newline_tree = Fenwick::new(&self.newlines);
+        self.utf16_tree = Fenwick::new(&self.utf16);
+    }
+
+    /// Sets the length and counts of `chunk`, updating the trees.
+    fn set_chunk(&mut self, chunk: usize, len: usize, newlines: usize, utf16: usize) {
+        self.len_tree.add(chunk, len as isize - self.lens[chunk] as isize);
+        self.newline_tree.add(chunk, newlines as isize - self.newlines[chunk] as isize);
+        self.utf16_tree.add(chunk, utf16 as isize - self.utf16[chunk] as isize);
+        self.lens[chunk] = len;
+        self.newlines[chunk] = newlines;
+        self.utf16[chunk] = utf16;
+    }
+
+    fn update(&mut self, buf: &GapString, offset: usize, removed: usize, inserted: usize) {
+        // Chunks are located with the lengths from before the edit.
+        let first = self.chunk_at(offset);
+        let mut last = if removed == 0 {
+            first
+        } else {
+            self.chunk_at(offset + removed - 1).max(first)
+        };
+        let start = self.len_tree.prefix(first);
+        let end = self.len_tree.prefix(last + 1) - removed + inserted;
+
+        // The bytes are split evenly into as few chunks of at most
+        // `2 * CHUNK_LEN` bytes as possible, the chunks left over emptied.
+        let len = end - start;
+        let needed = len.div_ceil(2 * CHUNK_LEN).max(1);
+        let piece = len.div_ceil(needed);
+        while last - first + 1 < needed && last + 1 < self.lens.len() && self.lens[last + 1] == 0 {
+            last += 1;
+        }
+        if last - first + 1 < needed {
+            self.rechunk(buf, first..last + 1, start..end);
+            return;
+        }
+
+        let mut chunk_start = start;
+        for chunk in first..last + 1 {
+            let chunk_end = end.min(chunk_start + piece);
+            let (newlines, utf16) = count_chunk(buf, chunk_start..chunk_end);
+            self.set_chunk(chunk, chunk_end - chunk_start, newlines, utf16);
+            chunk_start = chunk_end;
+        }
+    }
+}
+
+impl Fenwick {
+    fn new(values: &[usize]) -> Fenwick {
+        let mut tree = values.to_vec();
+        for i in 0..tree.len() {
+            let parent = i | (i + 1);
+            if parent < tree.len() {
+                tree[parent] += tree[i];
+            }
+        }
+        Fenwick { tree }
+    }
+
+    fn add(&mut self, index: usize, delta: isize) {
+        let mut i = index;
+        while i < self.tree.len() {
+            self.tree[i] = self.tree[i].wrapping_add(delta as usize);
+            i |= i + 1;
+        }
+    }
+
+    /// Returns the largest `count` with `prefix(count) <= target`.
+    fn position(&self, target: usize) -> usize {
+        let mut pos = 0;
+        let mut remaining = target;
+        let mut step = self.tree.len().next_power_of_two();
+        while step > 0 {
+            if pos + step <= self.tree.len() && self.tree[pos + step - 1] <= remaining {
+                pos += step;
+                remaining -= self.tree[pos - 1];
+            }
+            step >>= 1;
+        }
+        pos
+    }
+
+    /// Returns the sum of the first `count` values.
+    fn prefix(&self, count: usize) -> usize {
+        let mut sum = 0;
+        let mut i = count;
+        while i > 0 {
+            sum += self.tree[i - 1];
+            i &= i - 1;
+        }
+        sum
+    }
+}
+
+/// Returns the bytes within `range`, split at the gap.
+fn byte_range(buf: &GapString, range: Range<usize>) -> (&[u8], &[u8]) {
+    let (head, tail) = buf.as_slices();
+    let split = head.len();
+    (&head[range.start.min(split)..range.end.min(split)],
+     &tail[range.start.max(split) - split..range.end.max(split) - split])
+}
+
+/// Returns the newlines and UTF-16 code units within `range`.
+fn count_chunk(buf: &GapString, range: Range<usize>) -> (usize, usize) {
+    let (a, b) = byte_range(buf, range);
+    a.iter().chain(b).fold((0, 0), |(newlines, units), b| {
+        ((*b == b'\n') as usize + newlines, utf16_len(*b) + units)
+    })
+}
+
+fn count_newlines(buf: &GapString, range: Range<usize>) -> usize {
+    let (a, b) = byte_range(buf, range);
+    a.iter().chain(b).filter(|b| **b == b'\n').count()
+}
+
+fn is_char_start(byte: u8) -> bool {
+    byte & 0xc0 != 0x80
+}
+
+/// Returns the UTF-16 code units of the char starting with `byte`, or 0 for
+/// bytes within a char.
+fn utf16_len(byte: u8) -> usize {
+    match byte {
+        0x80..=0xbf => 0,
+        0xf0..=0xff => 2,
+        _ => 1
+    }
+}
+
+/// Returns the offset after the `nth` newline within `range`.
+fn nth_line_start(buf: &GapString, range: Range<usize>, nth: usize) -> Option<usize> {
+    let start = range.start;
+    let (a, b) = byte_range(buf, range);
+    a.iter()
+        .chain(b)
+        .enumerate()
+        .filter(|&(_, b)| *b == b'\n')
+        .nth(nth - 1)
+        .map(|(i, _)| start + i + 1)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use GapString;
+    use super::CHUNK_LEN;
+
+
+    #[test]
+    fn offset_to_position_1() {
+        let mut gap_buf = GapString::from("ab\nçd\n\nef");
+        assert!(gap_buf.offset_to_position(0) == (0, 0));
+        assert!(gap_buf.offset_to_position(5) == (1, 1));
+        assert!(gap_buf.offset_to_position(7) == (2, 0));
+        gap_buf.enable_line_index();
+        assert!(gap_buf.offset_to_position(6) == (1, 2));
+        assert!(gap_buf.offset_to_position(10) == (3, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn offset_to_position_2() {
+        let gap_buf = GapString::from("ab\nçd");
+        gap_buf.offset_to_position(4);
+    }
+
+    #[test]
+    fn position_to_offset_1() {
+        let mut gap_buf = GapString::from("ab\nçd\n\nef");
+        assert!(gap_buf.position_to_offset(0, 2) == Some(2));
+        assert!(gap_buf.position_to_offset(0, 3).is_none());
+        assert!(gap_buf.position_to_offset(1, 1) == Some(5));
+        gap_buf.enable_line_index();
+        assert!(gap_buf.position_to_offset(1, 2) == Some(6));
+        assert!(gap_buf.position_to_offset(2, 0) == Some(7));
+        assert!(gap_buf.position_to_offset(3, 2) == Some(10));
+        assert!(gap_buf.position_to_offset(4, 0).is_none());
+    }
+
+    #[test]
+    fn position_to_offset_2() {
+        let gap_buf = GapString::from("ab\r\n\r\ncd\r");
+        assert!(gap_buf.position_to_offset(0, 2) == Some(2));
+        assert!(gap_buf.position_to_offset(0, 3).is_none());
+        assert!(gap_buf.position_to_offset(1, 0) == Some(4));
+        assert!(gap_buf.position_to_offset(1, 1).is_none());
+        assert!(gap_buf.position_to_offset(2, 3) == Some(9));
+    }
+
+    #[test]
+    fn byte_to_utf16_cu_1() {
+        let mut gap_buf = GapString::from("aé😀b\n😀");
+        assert!(gap_buf.byte_to_utf16_cu(3) == 2);
+        assert!(gap_buf.byte_to_utf16_cu(7) == 4);
+        gap_buf.enable_line_index();
+        assert!(gap_buf.byte_to_utf16_cu(8) == 5);
+        assert!(gap_buf.byte_to_utf16_cu(13) == 8);
+    }
+
+    #[test]
+    fn utf16_cu_to_byte_1() {
+        let mut gap_buf = GapString::from("aé😀b\n😀");
+        for indexed in [false, true] {
+            if indexed {
+                gap_buf.enable_line_index();
+            }
+            assert!(gap_buf.utf16_cu_to_byte(0) == Some(0));
+            assert!(gap_buf.utf16_cu_to_byte(2) == Some(3));
+            assert!(gap_buf.utf16_cu_to_byte(3).is_none());
+            assert!(gap_buf.utf16_cu_to_byte(4) == Some(7));
+            assert!(gap_buf.utf16_cu_to_byte(8) == Some(13));
+            assert!(gap_buf.utf16_cu_to_byte(9).is_none());
+        }
+    }
+
+    #[test]
+    fn line_1() {
+        let mut gap_buf = GapString::from("one\ntwo\nthree");
+        for indexed in [false, true] {
+            if indexed {
+                gap_buf.enable_line_index();
+            }
+            gap_buf.cursor(5);
+            assert!(gap_buf.line(0) == "one\n");
+            assert!(gap_buf.line(1) == "two\n");
+            assert!(gap_buf.line(2) == "three");
+            assert!(gap_buf.line_slice(1..3) == "two\nthree");
+            assert!(gap_buf.line_slice(2..2).is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn line_2() {
+        let gap_buf = GapString::from("one\n");
+        gap_buf.line(2);
+    }
+
+    #[test]
+    fn line_index_1() {
+        let mut gap_buf = GapString::from("ab\ncd\n\nef");
+        for indexed in [false, true] {
+            if indexed {
+                gap_buf.enable_line_index();
+            }
+            assert!(gap_buf.line_count() == 4);
+            assert!(gap_buf.line_to_byte(1) == Some(3));
+            assert!(gap_buf.line_to_byte(3) == Some(7));
+            assert!(gap_buf.line_to_byte(4).is_none());
+            assert!(gap_buf.byte_to_line(2) == 0);
+            assert!(gap_buf.byte_to_line(3) == 1);
+            assert!(gap_buf.byte_to_line(9) == 3);
+        }
+    }
+
+    #[test]
+    fn line_index_2() {
+        let line = "0123456789abcdefghijklmnopqrstuvwxyz\n";
+        let mut gap_buf = GapString::from(line.repeat(100));
+        gap_buf.enable_line_index();
+
+        // Pseudo-random edits, checked against a buffer without an index.
+        let mut seed = 7u32;
+        for _ in 0..100 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let mut offset = seed as usize % (gap_buf.len() + 1);
+            while !gap_buf.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            match seed % 4 {
+                0 => gap_buf.insert_str(offset, "é\n😀"),
+                1 => gap_buf.insert_str(offset, &line.repeat(30)),
+                2 => {
+                    let mut end = (offset + seed as usize % 3000).min(gap_buf.len());
+                    while !gap_buf.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    gap_buf.try_remove(offset..end).unwrap()
+                }
+                _ => gap_buf.insert_str(gap_buf.len(), "\n")
+            };
+
+            let plain = GapString::from(gap_buf.to_string());
+            assert!(gap_buf.line_count() == plain.line_count());
+            for line in (0..plain.line_count() + 1).step_by(plain.line_count() / 20 + 1) {
+                assert!(gap_buf.line_to_byte(line) == plain.line_to_byte(line));
+            }
+            for offset in (0..plain.len()).step_by(plain.len() / 20 + 1) {
+                assert!(gap_buf.byte_to_line(offset) == plain.byte_to_line(offset));
+                if gap_buf.is_char_boundary(offset) {
+                    let units = plain.byte_to_utf16_cu(offset);
+                    assert!(gap_buf.byte_to_utf16_cu(offset) == units);
+                    assert!(gap_buf.utf16_cu_to_byte(units) == Some(offset));
+                }
+            }
+        }
+    }
+    #[test]
+    fn line_index_3() {
+        let mut gap_buf = GapString::from("a\n".repeat(2 * CHUNK_LEN));
+        gap_buf.enable_line_index();
+        let chunks = gap_buf.lines.as_ref().unwrap().lens.len();
+        assert!(chunks == 8);
+
+        // A chunk growing past `2 * CHUNK_LEN` is split into the empty chunk
+        // after it, rebuilding nothing.
+        gap_buf.insert_str(10, &"b\n".repeat(CHUNK_LEN));
+        let index = gap_buf.lines.as_ref().unwrap();
+        assert!(index.lens.len() == chunks);
+        assert!(index.lens[..2] == [3 * CHUNK_LEN / 2, 3 * CHUNK_LEN / 2]);
+        assert!(gap_buf.line_count() == 3 * CHUNK_LEN + 1);
+        assert!(gap_buf.line_to_byte(CHUNK_LEN + 1) == Some(2 * CHUNK_LEN + 2));
+    }
+}
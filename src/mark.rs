@@ -0,0 +1,141 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::mem;
+
+use GapString;
+
+
+/// Handle to a position created by `GapString::create_mark`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Mark(usize);
+
+/// Side a mark sticks to when text is inserted right at it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Bias {
+    /// The mark stays before the inserted text.
+    Left,
+    /// The mark moves past the inserted text.
+    Right
+}
+
+/// Marks of a buffer, kept in the order they were created.
+#[derive(Clone, Default)]
+pub(crate) struct Marks {
+    marks: Vec<(Mark, usize, Bias)>,
+    next_id: usize
+}
+
+impl GapString {
+    /// Creates a mark at `offset` that moves along with edits.
+    ///
+    /// Edits before the mark shift it, and removing text around it moves it
+    /// to where the text was. Text inserted right at the mark, or replacing
+    /// text around it, ends up after the mark with `Bias::Left` and before
+    /// it with `Bias::Right`.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn create_mark(&mut self, offset: usize, bias: Bias) -> Mark {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+
+        let mark = Mark(self.marks.next_id);
+        self.marks.next_id += 1;
+        self.marks.marks.push((mark, offset, bias));
+        mark
+    }
+
+    /// Returns the current offset of a mark, or `None` if it has been
+    /// removed.
+    pub fn mark_offset(&self, mark: Mark) -> Option<usize> {
+        self.marks.marks
+            .iter()
+            .find(|&&(other, _, _)| other == mark)
+            .map(|&(_, offset, _)| offset)
+    }
+
+    /// Removes a mark.
+    pub fn remove_mark(&mut self, mark: Mark) {
+        self.marks.marks.retain(|&(other, _, _)| other != mark);
+    }
+
+    /// Moves marks to account for an edit at `offset`.
+    pub(crate) fn adjust_marks(&mut self,
+                               offset: usize,
+                               removed: usize,
+                               inserted: usize)
+    {
+        let end = offset + removed;
+        for &mut (_, ref mut pos, bias) in &mut self.marks.marks {
+            if *pos < offset {
+                continue;
+            }
+
+            *pos = if *pos > end {
+                *pos - removed + inserted
+            } else if removed > 0 && *pos == end {
+                offset + inserted
+            } else if (removed > 0 && *pos == offset) || bias == Bias::Left {
+                offset
+            } else {
+                offset + inserted
+            };
+        }
+    }
+}
+
+impl Marks {
+    /// Returns the number of bytes allocated for the marks.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.marks.capacity() * mem::size_of::<(Mark, usize, Bias)>()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+    use super::Bias;
+
+
+    #[test]
+    fn create_mark_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        let left = gap_buf.create_mark(4, Bias::Left);
+        let right = gap_buf.create_mark(4, Bias::Right);
+        let end = gap_buf.create_mark(8, Bias::Left);
+
+        gap_buf.insert_str(4, "-");
+        assert!(gap_buf.mark_offset(left) == Some(4));
+        assert!(gap_buf.mark_offset(right) == Some(5));
+        assert!(gap_buf.mark_offset(end) == Some(9));
+
+        gap_buf.remove(0..2);
+        assert!(gap_buf.mark_offset(left) == Some(2));
+        assert!(gap_buf.mark_offset(end) == Some(7));
+
+        gap_buf.replace_range(1..4, "abcd");
+        assert!(gap_buf.mark_offset(left) == Some(1));
+        assert!(gap_buf.mark_offset(right) == Some(5));
+
+        gap_buf.remove_mark(left);
+        assert!(gap_buf.mark_offset(left).is_none());
+    }
+
+    #[test]
+    fn create_mark_2() {
+        let mut gap_buf = buf_from_str("abc");
+        let mark = gap_buf.create_mark(2, Bias::Right);
+        gap_buf.set_preedit(1, "か");
+        assert!(gap_buf.mark_offset(mark) == Some(5));
+        gap_buf.clear_preedit();
+        assert!(gap_buf.mark_offset(mark) == Some(2));
+    }
+}
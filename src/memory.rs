@@ -0,0 +1,75 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use GapString;
+
+
+/// Memory used by a buffer, in bytes, as returned by
+/// `GapString::memory_usage`.
+///
+/// `allocated` is always `content + gap`. A gap much larger than the content
+/// is memory a `shrink_to_fit` would give back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MemStats {
+    /// Size of the allocation holding the text, gap included.
+    pub allocated: usize,
+    /// Length of the content.
+    pub content: usize,
+    /// Size of the gap, allocated but unused.
+    pub gap: usize,
+    /// Memory allocated besides the text, by marks, attached spans, protected
+    /// ranges, the line index, the undo history, recorded operations and
+    /// change listeners.
+    pub overhead: usize
+}
+
+impl GapString {
+    /// Returns the memory used by the buffer, such as for deciding when to
+    /// call `shrink_to_fit` in a long-running editor.
+    ///
+    /// Takes O(n) time in the number of recorded edits, other bookkeeping is
+    /// counted by capacity.
+    pub fn memory_usage(&self) -> MemStats {
+        let overhead = self.marks.heap_size()
+            + self.spans.heap_size()
+            + self.protection.heap_size()
+            + self.lines.as_ref().map_or(0, |lines| lines.heap_size())
+            + self.history.heap_size()
+            + self.ops.heap_size()
+            + self.listeners.heap_size();
+        MemStats {
+            allocated: self.capacity(),
+            content: self.len(),
+            gap: self.capacity() - self.len(),
+            overhead
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+    use super::MemStats;
+
+
+    #[test]
+    fn memory_usage_1() {
+        let mut gap_buf = buf_from_str("hello world");
+        gap_buf.reserve(21);
+        let stats = gap_buf.memory_usage();
+        assert!(stats.content == 11 && stats.gap == stats.allocated - 11);
+        assert!(stats.overhead == 0);
+
+        gap_buf.enable_history(10);
+        gap_buf.insert_str(5, ",");
+        gap_buf.shrink_to_fit();
+        let stats = gap_buf.memory_usage();
+        assert!(stats == MemStats { allocated: 12, content: 12, gap: 0, ..stats });
+        assert!(stats.overhead > 0);
+    }
+}
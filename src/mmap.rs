@@ -0,0 +1,174 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::{io, ptr, str};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use libc;
+
+use GapString;
+use buffer::GapBuffer;
+
+
+/// Size of the gap in front of a mapped file, rounded up to whole pages.
+const MAPPED_GAP: usize = 1 << 20;
+
+/// Private mapping backing a `GapBuffer`, unmapped when dropped.
+///
+/// The gap is anonymous memory directly in front of a copy-on-write mapping
+/// of the file, so pages are read from the file when first touched and only
+/// copied once written to.
+pub(crate) struct Mapping {
+    ptr: *mut u8,
+    len: usize
+}
+
+impl GapString {
+    /// Creates a buffer whose content is a memory-mapped file.
+    ///
+    /// Nothing is copied up front: the file is mapped privately behind the
+    /// gap, and pages are read as they are first accessed and copied only
+    /// once an edit moves or writes bytes in them. Edits near the start of
+    /// a large file stay cheap, moving the gap towards its end copies
+    /// everything on the way like any gap movement. The content moves to a
+    /// regular allocation once the gap needs to grow. Empty files are read
+    /// like `from_file`.
+    ///
+    /// The content is still checked to be valid UTF-8, which reads the whole
+    /// file once.
+    ///
+    /// # Errors
+    ///
+    /// * If opening or mapping the file fails.
+    /// * If the content is not valid UTF-8.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified while the buffer is alive. Pages not yet
+    /// copied show such changes, which can break the UTF-8 invariant.
+    pub unsafe fn from_file_mmap<P: AsRef<Path>>(path: P) -> io::Result<GapString> {
+        let file = File::open(&path)?;
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return GapString::from_file(path);
+        }
+
+        let mapping = Mapping::new(&file, len)?;
+        let raw = GapBuffer::from_mapping(mapping, len);
+        let (_, content) = raw.as_slices();
+        if let Err(e) = str::from_utf8(content) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+        Ok(GapString::from_raw(raw))
+    }
+}
+
+impl Mapping {
+    /// Maps `len` bytes of `file` after a gap of `Mapping::gap()` bytes.
+    unsafe fn new(file: &File, len: usize) -> io::Result<Mapping> {
+        let gap = Mapping::gap();
+        let total = gap + round_to_page(len);
+        let ptr = libc::mmap(ptr::null_mut(),
+                             total,
+                             libc::PROT_READ | libc::PROT_WRITE,
+                             libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                             -1,
+                             0);
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mapping = Mapping { ptr: ptr as *mut u8, len: total };
+
+        // Replaces the anonymous pages behind the gap, the gap being a whole
+        // number of pages keeps the file mapping aligned.
+        let file_ptr = libc::mmap(mapping.ptr.add(gap) as *mut libc::c_void,
+                                  len,
+                                  libc::PROT_READ | libc::PROT_WRITE,
+                                  libc::MAP_PRIVATE | libc::MAP_FIXED,
+                                  file.as_raw_fd(),
+                                  0);
+        if file_ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(mapping)
+    }
+
+    /// Returns the size of the gap in front of the file.
+    pub(crate) fn gap() -> usize {
+        round_to_page(MAPPED_GAP)
+    }
+
+    /// Returns the start of the mapping.
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len); }
+    }
+}
+
+// Safety: the mapping is private to the process and owned by a single
+// `GapBuffer`, and `munmap` may be called from any thread.
+unsafe impl Send for Mapping {}
+
+fn round_to_page(len: usize) -> usize {
+    let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+    len.div_ceil(page) * page
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, io, process};
+    use std::path::PathBuf;
+
+    use GapString;
+
+
+    #[test]
+    fn from_file_mmap_1() {
+        let content = "line\n".repeat(2000);
+        let path = temp_file("from_file_mmap_1", &content);
+        let mut gap_buf = unsafe { GapString::from_file_mmap(&path).unwrap() };
+        fs::remove_file(&path).unwrap();
+        assert!(gap_buf == content.as_str());
+
+        gap_buf.insert_str(5, "inserted\n");
+        gap_buf.remove(0..5);
+        gap_buf.insert_str(gap_buf.len(), "end");
+        assert!(gap_buf.len() == content.len() + 7);
+        assert!(gap_buf.slice(0..14) == "inserted\nline\n");
+        assert!(gap_buf.to_string().ends_with("line\nend"));
+
+        gap_buf.insert_str(0, &"x".repeat(2 << 20));
+        assert!(gap_buf.len() == content.len() + 7 + (2 << 20));
+    }
+
+    #[test]
+    fn from_file_mmap_2() {
+        let path = temp_file("from_file_mmap_2", "");
+        let gap_buf = unsafe { GapString::from_file_mmap(&path).unwrap() };
+        assert!(gap_buf.is_empty());
+
+        fs::write(&path, b"caf\xc3").unwrap();
+        let result = unsafe { GapString::from_file_mmap(&path) };
+        fs::remove_file(&path).unwrap();
+        assert!(result.unwrap_err().kind() == io::ErrorKind::InvalidData);
+    }
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let mut path = env::temp_dir();
+        path.push(format!("gap-buffer-{}-{}", name, process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::mem;
+
+use {Edit, GapString, OpError};
+
+
+/// Edit of a whole document as a sequence of components, for sending to the
+/// other peers of a collaborative editing session.
+///
+/// The components walk the content from the start: the lengths retained and
+/// deleted add up to the length of the content the operation applies to.
+/// Lengths are in bytes. Transforming concurrent operations against each
+/// other is left to the transport, which delivers them in revision order.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Operation {
+    /// Revision of the buffer once the operation is applied, one more than
+    /// that of the buffer it applies to.
+    pub revision: u64,
+    pub components: Vec<OpComponent>
+}
+
+/// Part of an `Operation`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpComponent {
+    /// Keeps the next bytes as they are.
+    Retain(usize),
+    /// Inserts text before the next byte.
+    Insert(String),
+    /// Removes the next bytes.
+    Delete(usize)
+}
+
+/// Operations recorded by a `GapString`, see `enable_ops`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OpLog {
+    enabled: bool,
+    revision: u64,
+    /// Operations not yet taken by `take_ops`.
+    pending: Vec<Operation>
+}
+
+impl GapString {
+    /// Applies an operation received from another peer.
+    ///
+    /// The operation is applied as a batch, like `apply_edits`, and is not
+    /// recorded by `enable_ops`: the buffer takes on its revision instead.
+    ///
+    /// # Errors
+    ///
+    /// The buffer is left unchanged on error.
+    ///
+    /// * `OutOfOrder` if the revision of `op` does not follow that of the
+    ///   buffer.
+    /// * `LengthMismatch` if `op` does not span the whole content.
+    /// * `Edit` if one of its deletions or insertions cannot be made, as for
+    ///   `try_replace_range`.
+    pub fn apply_op(&mut self, op: &Operation) -> Result<(), OpError> {
+        if op.revision != self.ops.revision + 1 {
+            return Err(OpError::OutOfOrder);
+        }
+        if op.base_len() != self.len() {
+            return Err(OpError::LengthMismatch);
+        }
+
+        let edits = op.edits();
+        for edit in &edits {
+            let range = &edit.range;
+            self.check_offset(range.start)
+                .and_then(|_| self.check_offset(range.end))
+                .and_then(|_| self.check_edit(range.start, range.len()))
+                .map_err(OpError::Edit)?;
+        }
+
+        let enabled = mem::replace(&mut self.ops.enabled, false);
+        self.apply_edits(&edits);
+        self.ops.enabled = enabled;
+        self.ops.revision = op.revision;
+        Ok(())
+    }
+
+    /// Starts recording every edit as an `Operation`, to be collected with
+    /// `take_ops`.
+    ///
+    /// Each edit increments the revision of the buffer. Edits made while
+    /// recording is off are not exported, leaving other peers out of sync.
+    /// Preedit text is left out until committed, when it is recorded as a
+    /// single insertion, and offsets are those of the committed content.
+    pub fn enable_ops(&mut self) {
+        self.ops.enabled = true;
+    }
+
+    /// Returns the revision of the buffer, the number of operations recorded
+    /// or applied so far.
+    pub fn revision(&self) -> u64 {
+        self.ops.revision
+    }
+
+    /// Returns the operations recorded since the last call, in revision
+    /// order.
+    pub fn take_ops(&mut self) -> Vec<Operation> {
+        mem::take(&mut self.ops.pending)
+    }
+
+    /// Records the replacement of `removed` bytes at `offset` by `inserted`
+    /// bytes as an operation.
+    pub(crate) fn record_op(&mut self, offset: usize, removed: usize, inserted: usize) {
+        if !self.ops.enabled || removed == 0 && inserted == 0 {
+            return;
+        }
+
+        // Peers only see the committed content.
+        let text = self.slice_unchecked(offset..offset + inserted).into_owned();
+        let len = self.len() - self.preedit.as_ref().map_or(0, |range| range.len());
+        let offset = self.committed_offset(offset);
+
+        let mut components = Vec::with_capacity(4);
+        if offset > 0 {
+            components.push(OpComponent::Retain(offset));
+        }
+        if removed > 0 {
+            components.push(OpComponent::Delete(removed));
+        }
+        if inserted > 0 {
+            components.push(OpComponent::Insert(text));
+        }
+        let rest = len - offset - inserted;
+        if rest > 0 {
+            components.push(OpComponent::Retain(rest));
+        }
+
+        self.ops.revision += 1;
+        let revision = self.ops.revision;
+        self.ops.pending.push(Operation { revision, components });
+    }
+}
+
+impl Operation {
+    /// Returns the length of the content the operation applies to.
+    pub fn base_len(&self) -> usize {
+        self.components.iter()
+            .map(|component| match *component {
+                OpComponent::Retain(n) | OpComponent::Delete(n) => n,
+                OpComponent::Insert(_) => 0
+            })
+            .sum()
+    }
+
+    /// Returns the length of the content once the operation is applied.
+    pub fn target_len(&self) -> usize {
+        self.components.iter()
+            .map(|component| match *component {
+                OpComponent::Retain(n) => n,
+                OpComponent::Insert(ref s) => s.len(),
+                OpComponent::Delete(_) => 0
+            })
+            .sum()
+    }
+
+    /// Returns the operation as edits of the content it applies to, merging
+    /// adjacent deletions and insertions.
+    fn edits(&self) -> Vec<Edit> {
+        let mut edits: Vec<Edit> = Vec::new();
+        let mut offset = 0;
+        for component in &self.components {
+            let extends_last = edits.last().is_some_and(|edit| edit.range.end == offset);
+            match *component {
+                OpComponent::Retain(n) => offset += n,
+                OpComponent::Insert(ref s) if extends_last => {
+                    edits.last_mut().unwrap().text.push_str(s);
+                }
+                OpComponent::Insert(ref s) => {
+                    edits.push(Edit { range: offset..offset, text: s.clone() });
+                }
+                OpComponent::Delete(n) if extends_last => {
+                    edits.last_mut().unwrap().range.end += n;
+                    offset += n;
+                }
+                OpComponent::Delete(n) => {
+                    edits.push(Edit { range: offset..offset + n, text: String::new() });
+                    offset += n;
+                }
+            }
+        }
+        edits
+    }
+}
+
+impl OpLog {
+    /// Returns the number of bytes allocated for the pending operations.
+    pub(crate) fn heap_size(&self) -> usize {
+        let components: usize = self.pending.iter()
+            .map(|op| {
+                let text: usize = op.components.iter()
+                    .map(|component| match *component {
+                        OpComponent::Insert(ref s) => s.capacity(),
+                        _ => 0
+                    })
+                    .sum();
+                op.components.capacity() * mem::size_of::<OpComponent>() + text
+            })
+            .sum();
+        self.pending.capacity() * mem::size_of::<Operation>() + components
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, GapBufferError, OpError};
+    use super::{OpComponent, Operation};
+
+
+    #[test]
+    fn apply_op_1() {
+        let mut local = buf_from_str("hello world");
+        let mut remote = local.clone();
+        local.enable_ops();
+        local.insert_str(5, ",");
+        local.replace_range(0..1, "H");
+        local.set_readonly(true);
+
+        let ops = local.take_ops();
+        assert!(ops.len() == 2 && local.take_ops().is_empty());
+        assert!(ops[0].components == [OpComponent::Retain(5),
+                                      OpComponent::Insert(",".to_string()),
+                                      OpComponent::Retain(6)]);
+        assert!(ops[1].base_len() == 12 && ops[1].target_len() == 12);
+        for op in &ops {
+            remote.apply_op(op).unwrap();
+        }
+        assert!(remote == "Hello, world");
+        assert!(remote.revision() == 2 && local.revision() == 2);
+    }
+
+    #[test]
+    fn apply_op_2() {
+        let mut gap_buf = buf_from_str("añb");
+        gap_buf.enable_ops();
+        let op = |revision, components| Operation { revision, components };
+
+        let delete = op(2, vec![OpComponent::Delete(4)]);
+        assert!(gap_buf.apply_op(&delete) == Err(OpError::OutOfOrder));
+        let delete = op(1, vec![OpComponent::Delete(3)]);
+        assert!(gap_buf.apply_op(&delete) == Err(OpError::LengthMismatch));
+        let split = op(1, vec![OpComponent::Retain(2),
+                               OpComponent::Insert("x".to_string()),
+                               OpComponent::Retain(2)]);
+        assert!(gap_buf.apply_op(&split) == Err(OpError::Edit(GapBufferError::NotCharBoundary)));
+        assert!(gap_buf == "añb" && gap_buf.revision() == 0);
+
+        let replace = op(1, vec![OpComponent::Retain(1),
+                                 OpComponent::Delete(2),
+                                 OpComponent::Insert("n".to_string()),
+                                 OpComponent::Retain(1)]);
+        gap_buf.apply_op(&replace).unwrap();
+        assert!(gap_buf == "anb" && gap_buf.revision() == 1);
+        assert!(gap_buf.take_ops().is_empty());
+    }
+
+    #[test]
+    fn take_ops_1() {
+        let mut gap_buf = buf_from_str("ab");
+        let mut remote = gap_buf.clone();
+        gap_buf.enable_ops();
+        gap_buf.set_preedit(1, "か");
+        gap_buf.set_preedit(1, "かな");
+        gap_buf.insert_str(8, "!");
+        gap_buf.commit_preedit();
+
+        let ops = gap_buf.take_ops();
+        assert!(ops.len() == 2 && gap_buf.revision() == 2);
+        assert!(ops[0].components == [OpComponent::Retain(2),
+                                      OpComponent::Insert("!".to_string())]);
+        assert!(ops[1].components == [OpComponent::Retain(1),
+                                      OpComponent::Insert("かな".to_string()),
+                                      OpComponent::Retain(2)]);
+        for op in &ops {
+            remote.apply_op(op).unwrap();
+        }
+        assert!(remote == "aかなb!");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_1() {
+        use serde_test::{assert_tokens, Token};
+
+        let op = Operation { revision: 3, components: vec![OpComponent::Delete(2)] };
+        assert_tokens(&op, &[Token::Struct { name: "Operation", len: 2 },
+                             Token::Str("revision"),
+                             Token::U64(3),
+                             Token::Str("components"),
+                             Token::Seq { len: Some(1) },
+                             Token::NewtypeVariant { name: "OpComponent", variant: "Delete" },
+                             Token::U64(2),
+                             Token::SeqEnd,
+                             Token::StructEnd]);
+    }
+}
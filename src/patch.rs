@@ -0,0 +1,212 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::iter::{Enumerate, Peekable};
+use std::str::SplitTerminator;
+
+use {Edit, EditDelta, GapString, PatchError};
+
+
+type PatchLines<'a> = Peekable<Enumerate<SplitTerminator<'a, char>>>;
+
+impl GapString {
+    /// Applies a unified diff of a single file, as printed by `diff -u` or
+    /// `git diff`, and returns the change made by each run of removed and
+    /// added lines.
+    ///
+    /// Lines before the first hunk, such as the file names, are skipped.
+    /// Every hunk must apply at the line numbers it gives, as the hunks are
+    /// turned into edits of the content before the patch and applied as one
+    /// batch by `apply_patch`. Context lines are left in place, along with
+    /// any marks in them.
+    ///
+    /// # Errors
+    ///
+    /// No edit is applied on error.
+    ///
+    /// * `Malformed` if the patch cannot be parsed or covers another file.
+    /// * `Mismatch` if the lines a hunk keeps or removes are not those of
+    ///   the content.
+    /// * Same as `apply_patch` otherwise.
+    pub fn apply_unified_diff(&mut self, patch: &str)
+        -> Result<Vec<EditDelta>, PatchError>
+    {
+        let edits = self.unified_diff_edits(patch)?;
+        self.apply_patch(&edits)
+    }
+
+    /// Returns the edits a unified diff makes to the content.
+    fn unified_diff_edits(&self, patch: &str) -> Result<Vec<Edit>, PatchError> {
+        let starts = self.line_starts();
+        let mut lines: PatchLines = patch.split_terminator('\n').enumerate().peekable();
+        let end = PatchError::Malformed(patch.lines().count() + 1);
+        let mut edits = Vec::new();
+        let mut in_hunks = false;
+        while let Some((i, line)) = lines.next() {
+            if line.starts_with("@@") {
+                in_hunks = true;
+                let header = parse_header(line).ok_or(PatchError::Malformed(i + 1))?;
+                self.hunk_edits(&starts, header, &mut lines, &mut edits)
+                    .map_err(|e| e.unwrap_or(end))?;
+            } else if in_hunks && (line.starts_with("--- ") || line.starts_with("diff ")) {
+                return Err(PatchError::Malformed(i + 1));
+            }
+        }
+        Ok(edits)
+    }
+
+    /// Pushes the edits of the hunk at `lines`, returning `None` as error if
+    /// the patch ends within it.
+    fn hunk_edits(&self,
+                  starts: &[usize],
+                  (old_start, old_count, new_count): (usize, usize, usize),
+                  lines: &mut PatchLines,
+                  edits: &mut Vec<Edit>)
+        -> Result<(), Option<PatchError>>
+    {
+        // A hunk adding lines to an empty range starts after `old_start`.
+        let mut old_line = if old_count == 0 { old_start } else { old_start - 1 };
+        let (mut old_left, mut new_left) = (old_count, new_count);
+        let mut run: Option<Edit> = None;
+        while old_left > 0 || new_left > 0 {
+            let (i, line) = lines.next().ok_or(None)?;
+            let malformed = Some(PatchError::Malformed(i + 1));
+            let newline = !matches!(lines.peek(), Some(&(_, next)) if next.starts_with('\\'));
+            let (kind, text) = match line.chars().next() {
+                Some(kind) => (kind, &line[kind.len_utf8()..]),
+                None => (' ', "")
+            };
+
+            match kind {
+                ' ' | '-' => {
+                    if old_left == 0 || kind == ' ' && new_left == 0 {
+                        return Err(malformed);
+                    }
+                    let range = match starts.get(old_line + 1) {
+                        Some(&end) => starts[old_line]..end,
+                        None => return Err(Some(PatchError::Mismatch(i + 1)))
+                    };
+                    let content = self.slice_unchecked(range.clone());
+                    let matches = if newline {
+                        content.strip_suffix('\n') == Some(text)
+                    } else {
+                        content == text
+                    };
+                    if !matches {
+                        return Err(Some(PatchError::Mismatch(i + 1)));
+                    }
+
+                    if kind == ' ' {
+                        edits.extend(run.take());
+                        new_left -= 1;
+                    } else {
+                        let start = range.start;
+                        run.get_or_insert(Edit { range: start..start, text: String::new() })
+                            .range.end = range.end;
+                    }
+                    old_left -= 1;
+                    old_line += 1;
+                }
+                '+' if new_left > 0 => {
+                    let start = starts.get(old_line).cloned().ok_or(malformed)?;
+                    let edit = run.get_or_insert(Edit { range: start..start,
+                                                        text: String::new() });
+                    edit.text.push_str(text);
+                    if newline {
+                        edit.text.push('\n');
+                    }
+                    new_left -= 1;
+                }
+                _ => return Err(malformed)
+            }
+            if !newline {
+                lines.next();
+            }
+        }
+        edits.extend(run);
+        Ok(())
+    }
+
+    /// Returns the offset of every line, plus the end of the content if the
+    /// last line does not end with a newline.
+    fn line_starts(&self) -> Vec<usize> {
+        let mut starts = vec![0];
+        let newlines = self.bytes().enumerate().filter(|&(_, b)| b == b'\n');
+        starts.extend(newlines.map(|(i, _)| i + 1));
+        if starts[starts.len() - 1] != self.len() {
+            starts.push(self.len());
+        }
+        starts
+    }
+}
+
+/// Parses `@@ -old_start,old_count +new_start,new_count @@`, returning the
+/// start and counts, a count of 1 being left out.
+fn parse_header(line: &str) -> Option<(usize, usize, usize)> {
+    let mut parts = line.strip_prefix("@@ ")?.split(' ');
+    let (old_start, old_count) = parse_range(parts.next()?, '-')?;
+    let (_, new_count) = parse_range(parts.next()?, '+')?;
+    if parts.next() != Some("@@") || old_start == 0 && old_count > 0 {
+        return None;
+    }
+    Some((old_start, old_count, new_count))
+}
+
+fn parse_range(s: &str, sign: char) -> Option<(usize, usize)> {
+    let s = s.strip_prefix(sign)?;
+    match s.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((s.parse().ok()?, 1))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, PatchError};
+
+
+    #[test]
+    fn apply_unified_diff_1() {
+        let mut gap_buf = buf_from_str("one\ntwo\nthree\nfour\nfive\nsix\nseven\neight");
+        let patch = "--- a/numbers.txt\n\
+                     +++ b/numbers.txt\n\
+                     @@ -1,3 +1,3 @@\n\
+                     -one\n\
+                     +ONE\n\
+                     \x20two\n\
+                     \x20three\n\
+                     @@ -6,3 +6,4 @@ five\n\
+                     \x20six\n\
+                     \x20seven\n\
+                     -eight\n\
+                     \\ No newline at end of file\n\
+                     +eight\n\
+                     +nine\n";
+        let deltas = gap_buf.apply_unified_diff(patch).unwrap();
+        assert!(gap_buf == "ONE\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\n");
+        assert!(deltas.len() == 2 && deltas[1].old_range() == (34..39));
+    }
+
+    #[test]
+    fn apply_unified_diff_2() {
+        let mut gap_buf = buf_from_str("a\nb\n");
+        assert!(gap_buf.apply_unified_diff("@@ -0,0 +1 @@\n+z\n").is_ok());
+        assert!(gap_buf == "z\na\nb\n");
+
+        let mismatch = "@@ -1,2 +1,1 @@\n z\n-b\n";
+        assert!(gap_buf.apply_unified_diff(mismatch) == Err(PatchError::Mismatch(3)));
+        let truncated = "@@ -1,2 +1,2 @@\n z\n";
+        assert!(gap_buf.apply_unified_diff(truncated) == Err(PatchError::Malformed(3)));
+        let overlapping = "@@ -1 +1 @@\n-z\n+y\n@@ -1 +1 @@\n-z\n+x\n";
+        assert!(gap_buf.apply_unified_diff(overlapping) == Err(PatchError::Overlapping));
+        assert!(gap_buf.apply_unified_diff("@@ -1 +1\n") == Err(PatchError::Malformed(1)));
+        let multibyte = "@@ -1,1 +1,1 @@\né\n";
+        assert!(gap_buf.apply_unified_diff(multibyte) == Err(PatchError::Malformed(2)));
+        assert!(gap_buf == "z\na\nb\n");
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::fmt;
+use std::borrow::Cow;
+use std::ops::Range;
+
+use TextBuffer;
+
+
+/// Text buffer keeping the original content untouched and every inserted
+/// string appended to a second buffer, the content being described by a list
+/// of pieces of either.
+///
+/// Inserting never moves existing text, and appending at the end of the
+/// last insertion only extends its piece. This suits append-heavy use such
+/// as logs and transcripts, where a `GapString` would keep growing its
+/// allocation. Finding an offset walks the piece list, so random edits get
+/// slower as the number of pieces grows.
+#[derive(Clone, Default)]
+pub struct PieceTable {
+    original: String,
+    added: String,
+    pieces: Vec<Piece>,
+    len: usize
+}
+
+/// Part of the content, `len` bytes at `start` of the original or the added
+/// buffer.
+#[derive(Clone, Copy, Debug)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source {
+    Original,
+    Added
+}
+
+impl PieceTable {
+    /// Returns an iterator over the chars of the content.
+    pub fn chars(&self) -> impl DoubleEndedIterator<Item = char> + '_ {
+        self.chunks().flat_map(str::chars)
+    }
+
+    /// Returns an iterator over the content as `&str` pieces, in order and
+    /// without copying.
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &str> + '_ {
+        self.pieces.iter().map(move |piece| self.text(piece))
+    }
+
+    /// Inserts `s` at `offset`.
+    ///
+    /// `s` is appended to the added buffer. Inserting right after the
+    /// previous insertion extends its piece, other offsets add a piece and
+    /// split at most one.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn insert_str(&mut self, offset: usize, s: &str) {
+        self.check_offset(offset);
+        if s.is_empty() {
+            return;
+        }
+
+        let start = self.added.len();
+        self.added.push_str(s);
+        self.len += s.len();
+
+        let index = self.split(offset);
+        if index > 0 {
+            let prev = &mut self.pieces[index - 1];
+            if prev.source == Source::Added && prev.start + prev.len == start {
+                prev.len += s.len();
+                return;
+            }
+        }
+        self.pieces.insert(index, Piece { source: Source::Added, start, len: s.len() });
+    }
+
+    /// Returns `true` if the content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the length of the content in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Creates a new, empty piece table.
+    pub fn new() -> PieceTable {
+        PieceTable::default()
+    }
+
+    /// Appends `s` to the end of the content.
+    ///
+    /// Takes amortized O(1) time per byte when appending repeatedly.
+    pub fn push_str(&mut self, s: &str) {
+        let len = self.len;
+        self.insert_str(len, s);
+    }
+
+    /// Removes `range`.
+    ///
+    /// Only the piece list changes, the removed text stays in its buffer.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    /// * If `range` starts or ends inside a char.
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.check_range(&range);
+        if range.start == range.end {
+            return;
+        }
+
+        let start = self.split(range.start);
+        let end = self.split(range.end);
+        self.pieces.drain(start..end);
+        self.len -= range.len();
+    }
+
+    /// Returns the content within `range`.
+    ///
+    /// Borrows from the buffers if `range` lies within a single piece,
+    /// copies it otherwise.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed or out of bounds.
+    /// * If `range` starts or ends inside a char.
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        self.check_range(&range);
+
+        let mut result = Cow::Borrowed("");
+        let mut piece_start = 0;
+        for text in self.chunks() {
+            let piece_end = piece_start + text.len();
+            let start = range.start.max(piece_start);
+            let end = range.end.min(piece_end);
+            if start < end {
+                let part = &text[start - piece_start..end - piece_start];
+                if result.is_empty() {
+                    result = Cow::Borrowed(part);
+                } else {
+                    result.to_mut().push_str(part);
+                }
+            }
+            if piece_end >= range.end {
+                break;
+            }
+            piece_start = piece_end;
+        }
+        result
+    }
+
+    fn check_offset(&self, offset: usize) {
+        assert!(offset <= self.len, "Offset {} out of bounds", offset);
+        assert!(self.is_char_boundary(offset), "Offset {} is not on a char boundary", offset);
+    }
+
+    fn check_range(&self, range: &Range<usize>) {
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        self.check_offset(range.start);
+        self.check_offset(range.end);
+    }
+
+    fn is_char_boundary(&self, offset: usize) -> bool {
+        let mut piece_start = 0;
+        for text in self.chunks() {
+            if offset < piece_start + text.len() {
+                return text.is_char_boundary(offset - piece_start);
+            }
+            piece_start += text.len();
+        }
+        true
+    }
+
+    /// Splits the piece holding `offset`, which must be in bounds, so that a
+    /// piece starts there and returns its index, or the number of pieces for
+    /// the end of the content.
+    fn split(&mut self, offset: usize) -> usize {
+        let mut piece_start = 0;
+        for index in 0..self.pieces.len() {
+            if piece_start == offset {
+                return index;
+            }
+            let piece = self.pieces[index];
+            if offset < piece_start + piece.len {
+                let head = offset - piece_start;
+                self.pieces[index].len = head;
+                self.pieces.insert(index + 1, Piece {
+                    source: piece.source,
+                    start: piece.start + head,
+                    len: piece.len - head
+                });
+                return index + 1;
+            }
+            piece_start += piece.len;
+        }
+        self.pieces.len()
+    }
+
+    fn text(&self, piece: &Piece) -> &str {
+        let buffer = match piece.source {
+            Source::Original => &self.original,
+            Source::Added => &self.added
+        };
+        &buffer[piece.start..piece.start + piece.len]
+    }
+}
+
+impl TextBuffer for PieceTable {
+    fn chunks(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(PieceTable::chunks(self))
+    }
+
+    fn insert_str(&mut self, offset: usize, s: &str) {
+        PieceTable::insert_str(self, offset, s);
+    }
+
+    fn len(&self) -> usize {
+        PieceTable::len(self)
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        PieceTable::remove(self, range);
+    }
+
+    fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        PieceTable::slice(self, range)
+    }
+}
+
+impl<'a> From<&'a str> for PieceTable {
+    /// Creates a piece table with `s` as its original content.
+    fn from(s: &'a str) -> PieceTable {
+        PieceTable::from(s.to_string())
+    }
+}
+
+impl From<String> for PieceTable {
+    /// Creates a piece table with `s` as its original content, without
+    /// copying it.
+    fn from(s: String) -> PieceTable {
+        let len = s.len();
+        let pieces = if len > 0 {
+            vec![Piece { source: Source::Original, start: 0, len }]
+        } else {
+            Vec::new()
+        };
+        PieceTable { original: s, added: String::new(), pieces, len }
+    }
+}
+
+impl fmt::Debug for PieceTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.chunks()).finish()
+    }
+}
+
+impl fmt::Display for PieceTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for text in self.chunks() {
+            f.write_str(text)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq<str> for PieceTable {
+    fn eq(&self, other: &str) -> bool {
+        if self.len != other.len() {
+            return false;
+        }
+        // Pieces need not end on char boundaries of `other`.
+        let mut rest = other.as_bytes();
+        self.chunks().all(|text| {
+            let (head, tail) = rest.split_at(text.len());
+            rest = tail;
+            head == text.as_bytes()
+        })
+    }
+}
+
+impl<'a> PartialEq<&'a str> for PieceTable {
+    fn eq(&self, other: &&'a str) -> bool {
+        *self == **other
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::PieceTable;
+
+
+    #[test]
+    fn eq_1() {
+        let mut table = PieceTable::from("a");
+        table.push_str("b");
+        assert!(table == "ab");
+        assert!(table != "é" && table != "ba");
+    }
+
+    #[test]
+    fn insert_str_1() {
+        let mut table = PieceTable::from("hello world");
+        table.insert_str(5, ",");
+        table.insert_str(6, " dear");
+        table.push_str("!");
+        assert!(table == "hello, dear world!");
+        assert!(table.pieces.len() == 4);
+        assert!(table.added == ", dear!");
+    }
+
+    #[test]
+    fn remove_1() {
+        let mut table = PieceTable::from("héllo world");
+        table.insert_str(6, " there");
+        table.remove(3..6);
+        assert!(table == "hé there world");
+        table.remove(0..15);
+        assert!(table.is_empty() && table.pieces.is_empty());
+    }
+
+    #[test]
+    fn slice_1() {
+        let mut table = PieceTable::from("abcdef");
+        table.insert_str(3, "é");
+        assert!(table.slice(1..3) == "bc");
+        assert!(table.slice(2..5) == "cé");
+        assert!(table.chars().rev().collect::<String>() == "fedécba");
+    }
+
+    #[test]
+    #[should_panic]
+    fn slice_2() {
+        let table = PieceTable::from("é");
+        table.slice(0..1);
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+/// Decides how much a `GapBuffer` grows its gap when it runs out of room.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum GrowthPolicy {
+    /// Grow by the needed size rounded up to a multiple of the given chunk.
+    Chunk(usize),
+    /// Grow by at least the current capacity, doubling the allocation.
+    #[default]
+    Doubling,
+    /// Grow by the number of bytes returned for the current capacity and
+    /// the needed size. Values below the needed size are raised to it.
+    Custom(fn(capacity: usize, needed: usize) -> usize)
+}
+
+/// Decides when a `GapBuffer` gives memory held by its gap back to the
+/// allocator.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ShrinkPolicy {
+    /// Only shrink when `shrink_to_fit` is called.
+    #[default]
+    Manual,
+    /// Shrink after a removal once the gap is more than the given multiple
+    /// of the content length.
+    Ratio(f32)
+}
+
+impl GrowthPolicy {
+    /// Returns the number of bytes to add to an allocation of `capacity`
+    /// bytes that needs `needed` more.
+    pub(crate) fn extra(&self, capacity: usize, needed: usize) -> usize {
+        match *self {
+            GrowthPolicy::Chunk(chunk) => {
+                let chunk = chunk.max(1);
+                needed.div_ceil(chunk) * chunk
+            }
+            GrowthPolicy::Doubling => needed.max(capacity),
+            GrowthPolicy::Custom(f) => f(capacity, needed).max(needed)
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+use GapString;
+
+
+impl GapString {
+    /// Removes the preedit text, ending the composition without committing.
+    pub fn clear_preedit(&mut self) {
+        if let Some(range) = self.preedit.take() {
+            self.raw.remove_range(range.clone());
+            self.edited_unreported(range.start, range.len(), 0);
+        }
+    }
+
+    /// Ends the composition, making the preedit text part of the committed
+    /// content.
+    ///
+    /// The text is reported as a single insertion to change listeners and
+    /// the operation log, which see nothing of the composition before.
+    pub fn commit_preedit(&mut self) {
+        if let Some(range) = self.preedit.take() {
+            self.record_commit(range.clone());
+            self.report_edit(range.start, 0, range.len());
+        }
+    }
+
+    /// Returns the buffer's content without the preedit text.
+    pub fn committed_string(&self) -> String {
+        let mut s = self.to_string();
+        if let Some(ref range) = self.preedit {
+            s.drain(range.clone());
+        }
+        s
+    }
+
+    /// Returns the range currently occupied by preedit text, if any.
+    pub fn preedit(&self) -> Option<Range<usize>> {
+        self.preedit.clone()
+    }
+
+    /// Shows `text` as input method composition text at `offset`, replacing
+    /// any previous preedit text.
+    ///
+    /// Preedit text is read back like any other content, but is not part of
+    /// the committed content until `commit_preedit` is called. Until then it
+    /// is not reported to change listeners nor recorded as an operation.
+    /// `offset` is relative to the content without the previous preedit
+    /// text. An empty `text` clears the preedit. Any other edit overlapping
+    /// the preedit text ends the composition and leaves the text committed.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `insert_str`.
+    pub fn set_preedit(&mut self, offset: usize, text: &str) {
+        self.clear_preedit();
+
+        let result = self.check_offset(offset)
+            .and_then(|_| self.check_edit(offset, 0));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+        if text.is_empty() {
+            return;
+        }
+
+        self.insert_raw(offset, text).unwrap_or_else(|e| panic!("{}", e));
+        self.edited_unreported(offset, 0, text.len());
+        self.preedit = Some(offset..offset + text.len());
+    }
+
+    /// Moves the preedit range to account for an edit at `offset`.
+    pub(crate) fn adjust_preedit(&mut self,
+                                 offset: usize,
+                                 removed: usize,
+                                 inserted: usize)
+    {
+        if self.preedit_overlaps(offset, removed) {
+            self.preedit = None;
+        } else if let Some(range) = self.preedit.take() {
+            if offset < range.start {
+                let start = range.start - removed + inserted;
+                self.preedit = Some(start..start + range.len());
+            } else {
+                self.preedit = Some(range);
+            }
+        }
+    }
+
+    /// Returns `offset` with the preedit text before it left out.
+    pub(crate) fn committed_offset(&self, offset: usize) -> usize {
+        match self.preedit {
+            Some(ref range) if range.end <= offset => offset - range.len(),
+            _ => offset
+        }
+    }
+
+    /// Returns `true` if replacing `removed` bytes at `offset` ends the
+    /// composition.
+    pub(crate) fn preedit_overlaps(&self, offset: usize, removed: usize) -> bool {
+        match self.preedit {
+            Some(ref range) => {
+                !(offset + removed <= range.start && offset < range.start)
+                    && offset < range.end
+            }
+            None => false
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, GapBufferError};
+
+
+    #[test]
+    fn preedit_1() {
+        let mut gap_buf = buf_from_str("日本");
+        gap_buf.set_preedit(6, "ご");
+        gap_buf.set_preedit(6, "ごは");
+        assert!(gap_buf == "日本ごは");
+        assert!(gap_buf.committed_string() == "日本");
+
+        gap_buf.commit_preedit();
+        assert!(gap_buf.preedit().is_none());
+        assert!(gap_buf.committed_string() == "日本ごは");
+    }
+
+    #[test]
+    fn preedit_2() {
+        let mut gap_buf = buf_from_str("abc");
+        gap_buf.set_preedit(3, "か");
+        gap_buf.insert_str(0, "12");
+        assert!(gap_buf.preedit() == Some(5..8));
+
+        gap_buf.clear_preedit();
+        assert!(gap_buf == "12abc");
+    }
+
+    #[test]
+    fn preedit_3() {
+        let mut gap_buf = buf_from_str("abc");
+        gap_buf.set_preedit(1, "か");
+        gap_buf.remove(0..4);
+        assert!(gap_buf.preedit().is_none());
+        assert!(gap_buf == "bc");
+    }
+
+    #[test]
+    fn preedit_4() {
+        let mut gap_buf = buf_from_str("ab");
+        gap_buf.enable_history(10);
+        gap_buf.set_preedit(1, "か");
+        let capacity = gap_buf.capacity();
+        gap_buf.set_max_capacity(Some(capacity));
+
+        let long = "x".repeat(capacity);
+        let result = gap_buf.try_insert_str(0, &long);
+        assert!(result == Err(GapBufferError::CapacityExceeded));
+        let result = gap_buf.try_insert_str(1, &long);
+        assert!(result == Err(GapBufferError::CapacityExceeded));
+        assert!(gap_buf.preedit() == Some(1..4));
+        let result = gap_buf.try_replace_range(0..1, &long);
+        assert!(result == Err(GapBufferError::CapacityExceeded));
+
+        gap_buf.clear_preedit();
+        assert!(gap_buf == "ab");
+        assert!(!gap_buf.can_undo());
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::{Deref, Range};
+
+use GapString;
+
+
+/// Overlay of speculative edits on top of a `GapString`.
+///
+/// Created by [`GapString::begin_preview`](struct.GapString.html#method.begin_preview).
+/// Edits are made to a copy of the buffer, which can be read through `Deref`.
+/// The underlying buffer is only touched by `commit`, dropping the session
+/// discards its edits.
+pub struct PreviewSession<'a> {
+    buf: &'a mut GapString,
+    overlay: GapString,
+    edits: Vec<PreviewEdit>
+}
+
+enum PreviewEdit {
+    Insert(usize, String),
+    Remove(Range<usize>)
+}
+
+impl GapString {
+    /// Starts a preview session on top of the buffer's current content.
+    pub fn begin_preview(&mut self) -> PreviewSession<'_> {
+        PreviewSession {
+            overlay: self.clone(),
+            buf: self,
+            edits: Vec::new()
+        }
+    }
+}
+
+impl<'a> PreviewSession<'a> {
+    /// Applies the previewed edits to the underlying buffer.
+    ///
+    /// The edits already succeeded against the preview, so they are applied
+    /// to the buffer as a whole, and undone as a single step.
+    pub fn commit(self) {
+        let mut transaction = self.buf.begin_transaction();
+        for edit in self.edits {
+            match edit {
+                PreviewEdit::Insert(offset, s) => transaction.insert_str(offset, &s),
+                PreviewEdit::Remove(range) => transaction.remove(range)
+            };
+        }
+        transaction.commit();
+    }
+
+    /// Discards the previewed edits, leaving the underlying buffer as is.
+    pub fn discard(self) {}
+
+    /// Inserts `s` into the preview at `offset`.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `GapString::insert_str`.
+    pub fn insert_str(&mut self, offset: usize, s: &str) {
+        self.overlay.insert_str(offset, s);
+        self.edits.push(PreviewEdit::Insert(offset, s.to_owned()));
+    }
+
+    /// Removes `range` from the preview.
+    ///
+    /// # Panics
+    ///
+    /// * Same as `GapString::remove`.
+    pub fn remove(&mut self, range: Range<usize>) {
+        self.overlay.remove(range.clone());
+        self.edits.push(PreviewEdit::Remove(range));
+    }
+}
+
+impl<'a> Deref for PreviewSession<'a> {
+    type Target = GapString;
+
+    fn deref(&self) -> &GapString {
+        &self.overlay
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+
+
+    #[test]
+    fn preview_1() {
+        let mut gap_buf = buf_from_str("fn foo() {}");
+        {
+            let mut preview = gap_buf.begin_preview();
+            preview.remove(3..6);
+            preview.insert_str(3, "bar");
+            assert!(preview.to_string() == "fn bar() {}");
+            preview.commit();
+        }
+
+        let text = gap_buf.to_string();
+        assert!(text == "fn bar() {}");
+    }
+
+    #[test]
+    fn preview_2() {
+        let mut gap_buf = buf_from_str("fn foo() {}");
+        {
+            let mut preview = gap_buf.begin_preview();
+            preview.insert_str(10, " todo!() ");
+            assert!(preview.to_string() == "fn foo() { todo!() }");
+            preview.discard();
+        }
+
+        let text = gap_buf.to_string();
+        assert!(text == "fn foo() {}");
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::mem;
+use std::ops::Range;
+
+use {GapString, GapBufferError};
+
+
+/// Handle to a range protected by `GapString::protect`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ProtectedRange(usize);
+
+/// Protected ranges of a buffer, kept in the order they were created.
+#[derive(Clone, Default)]
+pub(crate) struct Protection {
+    ranges: Vec<(ProtectedRange, Range<usize>)>,
+    next_id: usize
+}
+
+impl GapString {
+    /// Protects `range` from edits.
+    ///
+    /// Inserting strictly inside a protected range, or removing anything
+    /// overlapping it, fails with `GapBufferError::Protected` (or panics for
+    /// the infallible methods). Text inserted at either edge is allowed and
+    /// ends up outside the range. The range moves along with edits before it.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed, out of bounds or not on char boundaries.
+    pub fn protect(&mut self, range: Range<usize>) -> ProtectedRange {
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        let result = self.check_offset(range.start)
+            .and_then(|_| self.check_offset(range.end));
+        if let Err(e) = result {
+            panic!("{}", e);
+        }
+
+        let id = ProtectedRange(self.protection.next_id);
+        self.protection.next_id += 1;
+        self.protection.ranges.push((id, range));
+        id
+    }
+
+    /// Returns the current extent of a protected range, or `None` if it has
+    /// been unprotected.
+    pub fn protected_range(&self, id: ProtectedRange) -> Option<Range<usize>> {
+        self.protection.ranges
+            .iter()
+            .find(|&&(other, _)| other == id)
+            .map(|(_, range)| range.clone())
+    }
+
+    /// Lifts the protection of a range.
+    pub fn unprotect(&mut self, id: ProtectedRange) {
+        self.protection.ranges.retain(|&(other, _)| other != id);
+    }
+
+    /// Fails if replacing `removed` bytes at `offset` touches a protected
+    /// range.
+    pub(crate) fn check_protected(&self, offset: usize, removed: usize)
+        -> Result<(), GapBufferError>
+    {
+        if self.protection.ranges.iter().any(|(_, range)| touches(range, offset, removed)) {
+            Err(GapBufferError::Protected)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails like `check_protected` if any of `edits`, each replacing
+    /// `removed` bytes at `offset` by `inserted` bytes after the edits before
+    /// it were applied, touches a protected range.
+    pub(crate) fn check_protected_all<I>(&self, edits: I) -> Result<(), GapBufferError>
+        where I: IntoIterator<Item = (usize, usize, usize)>
+    {
+        let mut ranges: Vec<_> = self.protection.ranges
+            .iter()
+            .map(|(_, range)| range.clone())
+            .collect();
+        for (offset, removed, inserted) in edits {
+            if ranges.iter().any(|range| touches(range, offset, removed)) {
+                return Err(GapBufferError::Protected);
+            }
+            for range in &mut ranges {
+                shift(range, offset, removed, inserted);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves protected ranges to account for an edit at `offset`.
+    pub(crate) fn adjust_protected(&mut self,
+                                   offset: usize,
+                                   removed: usize,
+                                   inserted: usize)
+    {
+        for (_, range) in &mut self.protection.ranges {
+            shift(range, offset, removed, inserted);
+        }
+    }
+}
+
+/// Returns `true` if replacing `removed` bytes at `offset` touches `range`.
+fn touches(range: &Range<usize>, offset: usize, removed: usize) -> bool {
+    if removed == 0 {
+        range.start < offset && offset < range.end
+    } else {
+        offset < range.end && range.start < offset + removed
+    }
+}
+
+/// Moves `range` to account for an edit at `offset` that does not touch it.
+fn shift(range: &mut Range<usize>, offset: usize, removed: usize, inserted: usize) {
+    if offset + removed <= range.start {
+        range.start = range.start - removed + inserted;
+        range.end = range.end - removed + inserted;
+    }
+}
+
+impl Protection {
+    /// Returns the number of bytes allocated for the protected ranges.
+    pub(crate) fn heap_size(&self) -> usize {
+        self.ranges.capacity() * mem::size_of::<(ProtectedRange, Range<usize>)>()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {buf_from_str, GapBufferError};
+
+
+    #[test]
+    fn protect_1() {
+        let mut gap_buf = buf_from_str("> input");
+        let prompt = gap_buf.protect(0..2);
+
+        assert!(gap_buf.try_insert_str(1, "x") == Err(GapBufferError::Protected));
+        gap_buf.insert_str(7, "!");
+        gap_buf.insert_str(2, "more ");
+        assert!(gap_buf == "> more input!");
+
+        gap_buf.insert_str(0, "$");
+        assert!(gap_buf.protected_range(prompt) == Some(1..3));
+    }
+
+    #[test]
+    #[should_panic(expected = "protected")]
+    fn protect_2() {
+        let mut gap_buf = buf_from_str("> input");
+        gap_buf.protect(0..2);
+        gap_buf.remove(1..4);
+    }
+
+    #[test]
+    fn protect_3() {
+        let mut gap_buf = buf_from_str("> input");
+        let prompt = gap_buf.protect(0..2);
+        gap_buf.unprotect(prompt);
+        gap_buf.remove(0..2);
+
+        assert!(gap_buf.protected_range(prompt).is_none());
+        assert!(gap_buf == "input");
+    }
+
+    #[test]
+    #[should_panic(expected = "char boundary")]
+    fn protect_4() {
+        let mut gap_buf = buf_from_str("> é");
+        gap_buf.protect(0..3);
+    }
+}
@@ -0,0 +1,339 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::{cmp, io, str};
+use std::io::Write;
+use std::ops::Range;
+
+use {GapString, LineEnding};
+
+
+/// Reader over a logical range of a `GapString`.
+///
+/// Created by [`GapString::reader`](struct.GapString.html#method.reader) and
+/// [`GapString::range_reader`](struct.GapString.html#method.range_reader).
+pub struct RangeReader<'a> {
+    buf: &'a GapString,
+    pos: usize,
+    end: usize
+}
+
+/// Writer that replaces a logical range of a `GapString`.
+///
+/// Created by [`GapString::writer`](struct.GapString.html#method.writer) and
+/// [`GapString::range_writer`](struct.GapString.html#method.range_writer).
+/// Bytes written are inserted where the replaced range used to start, and
+/// have to form valid UTF-8 once all of them have been written. Sequences
+/// that are split across calls to `write` are held back until complete.
+pub struct RangeWriter<'a> {
+    buf: &'a mut GapString,
+    start: usize,
+    pos: usize,
+    pending: Vec<u8>
+}
+
+impl GapString {
+    /// Returns a reader yielding the bytes within `range`.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is out of bounds.
+    pub fn range_reader(&self, range: Range<usize>) -> RangeReader<'_> {
+        check_range(self, &range);
+        RangeReader {
+            buf: self,
+            pos: range.start,
+            end: range.end
+        }
+    }
+
+    /// Removes `range` from the buffer and returns a writer inserting
+    /// everything written to it in its place.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is out of bounds.
+    pub fn range_writer(&mut self, range: Range<usize>) -> RangeWriter<'_> {
+        check_range(self, &range);
+        if range.start < range.end {
+            self.remove(range.clone());
+        }
+
+        RangeWriter {
+            buf: self,
+            start: range.start,
+            pos: range.start,
+            pending: Vec::new()
+        }
+    }
+
+    /// Returns a reader yielding the whole content, the head and then the
+    /// tail, without copying it into a `String`.
+    pub fn reader(&self) -> RangeReader<'_> {
+        self.range_reader(0..self.len())
+    }
+
+    /// Returns a writer appending everything written to it, so the buffer
+    /// can be the sink of `io::copy` and similar.
+    ///
+    /// The writer holds back chars split across writes, which is why the
+    /// buffer does not implement `io::Write` itself.
+    pub fn writer(&mut self) -> RangeWriter<'_> {
+        let len = self.len();
+        self.range_writer(len..len)
+    }
+
+    /// Writes the bytes within `range` to `w`, straight from the buffer in
+    /// at most two writes.
+    ///
+    /// # Errors
+    ///
+    /// * If writing fails.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is out of bounds.
+    pub fn write_range_to<W: io::Write>(&self, range: Range<usize>, mut w: W)
+        -> io::Result<()>
+    {
+        let mut reader = self.range_reader(range);
+        while reader.remaining() > 0 {
+            let segment = reader.segment();
+            w.write_all(segment)?;
+            reader.pos += segment.len();
+        }
+        Ok(())
+    }
+
+    /// Writes the whole content to `w`, the head and then the tail, without
+    /// copying it into a `String`.
+    ///
+    /// Every `\n` is written as the buffer's line ending, see `line_ending`.
+    /// Other line endings are written through a `BufWriter`.
+    ///
+    /// # Errors
+    ///
+    /// * If writing fails.
+    pub fn write_to<W: io::Write>(&self, w: W) -> io::Result<()> {
+        if self.line_ending() == LineEnding::Lf {
+            return self.write_range_to(0..self.len(), w);
+        }
+
+        let mut w = io::BufWriter::new(w);
+        for piece in self.output_chunks() {
+            w.write_all(piece.as_bytes())?;
+        }
+        w.flush()
+    }
+}
+
+impl<'a> RangeReader<'a> {
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.end - self.pos
+    }
+
+    /// Returns the rest of the segment `pos` is in, up to `end`.
+    fn segment(&self) -> &'a [u8] {
+        let (head, tail) = self.buf.as_slices();
+        if self.pos < head.len() {
+            &head[self.pos..cmp::min(head.len(), self.end)]
+        } else {
+            &tail[self.pos - head.len()..self.end - head.len()]
+        }
+    }
+}
+
+impl<'a> io::Read for RangeReader<'a> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+
+        while read < out.len() && self.pos < self.end {
+            let segment = self.segment();
+            let n = cmp::min(out.len() - read, segment.len());
+            out[read..read + n].copy_from_slice(&segment[..n]);
+            read += n;
+            self.pos += n;
+        }
+
+        Ok(read)
+    }
+}
+
+impl<'a> io::BufRead for RangeReader<'a> {
+    /// Returns the rest of the current side of the gap, so the content is
+    /// read in at most two chunks.
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.segment())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.end);
+    }
+}
+
+impl<'a> RangeWriter<'a> {
+    /// Returns the range of the buffer written so far.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.pos
+    }
+}
+
+impl<'a> io::Write for RangeWriter<'a> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let held = self.pending.len();
+        self.pending.extend_from_slice(data);
+
+        let valid = match str::from_utf8(&self.pending) {
+            Ok(s) => s.len(),
+            Err(ref e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => {
+                self.pending.truncate(held);
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        };
+
+        if valid > 0 {
+            let s = unsafe { str::from_utf8_unchecked(&self.pending[..valid]) };
+            self.buf.try_insert_str(self.pos, s)?;
+            self.pos += valid;
+        }
+        self.pending.drain(..valid);
+
+        Ok(data.len())
+    }
+
+    /// Fails with `InvalidData` if an incomplete UTF-8 sequence is held back.
+    fn flush(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData,
+                               "incomplete UTF-8 sequence"))
+        }
+    }
+}
+
+fn check_range(buf: &GapString, range: &Range<usize>) {
+    let buf_len = buf.len();
+    assert!(range.start <= range.end, "Invalid range: {:?}", range);
+    assert!(range.end <= buf_len);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufRead, Read, Write};
+
+    use buf_from_str;
+
+
+    #[test]
+    fn range_reader_1() {
+        let gap_buf = buf_from_str("12345678");
+        let mut text = String::new();
+        gap_buf.range_reader(2..6).read_to_string(&mut text).unwrap();
+        assert!(text == "3456");
+    }
+
+    #[test]
+    fn range_reader_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        let mut text = String::new();
+        gap_buf.range_reader(2..7).read_to_string(&mut text).unwrap();
+        assert!(text == "34-56");
+    }
+
+    #[test]
+    fn reader_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        let mut reader = gap_buf.reader();
+        assert!(reader.fill_buf().unwrap() == b"1234-");
+        reader.consume(2);
+
+        let mut out = Vec::new();
+        io::copy(&mut reader, &mut out).unwrap();
+        assert!(out == b"34-5678");
+
+        let lines: Vec<String> = buf_from_str("a\nb").reader()
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+        assert!(lines == ["a", "b"]);
+    }
+
+    #[test]
+    fn write_to_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+
+        let mut out = Vec::new();
+        gap_buf.write_to(&mut out).unwrap();
+        assert!(out == b"1234-5678");
+
+        out.clear();
+        gap_buf.write_range_to(2..7, &mut out).unwrap();
+        assert!(out == b"34-56");
+    }
+
+    #[test]
+    fn range_writer_1() {
+        let mut gap_buf = buf_from_str("<a>old</a>");
+        {
+            let mut writer = gap_buf.range_writer(3..6);
+            writer.write_all(b"new content").unwrap();
+            assert!(writer.range() == (3..14));
+        }
+
+        let text = gap_buf.to_string();
+        assert!(text == "<a>new content</a>");
+    }
+
+    #[test]
+    fn range_writer_2() {
+        let mut gap_buf = buf_from_str("[]");
+        {
+            let bytes = "é".as_bytes();
+            let mut writer = gap_buf.range_writer(1..1);
+            writer.write_all(&bytes[..1]).unwrap();
+            assert!(writer.flush().is_err());
+            writer.write_all(&bytes[1..]).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let text = gap_buf.to_string();
+        assert!(text == "[é]");
+    }
+
+    #[test]
+    fn range_writer_3() {
+        let mut gap_buf = buf_from_str("12345678");
+        let mut writer = gap_buf.range_writer(0..8);
+        assert!(writer.write(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn writer_1() {
+        let mut gap_buf = buf_from_str("> ");
+        {
+            let mut writer = gap_buf.writer();
+            let text = "héllo wörld".as_bytes();
+            for chunk in text.chunks(2) {
+                writer.write_all(chunk).unwrap();
+            }
+            writer.flush().unwrap();
+        }
+        assert!(gap_buf == "> héllo wörld");
+
+        io::copy(&mut &b" again"[..], &mut gap_buf.writer()).unwrap();
+        assert!(gap_buf == "> héllo wörld again");
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use GapString;
+
+
+impl GapString {
+    /// Returns the range of the first match of `re` starting at or after
+    /// `from`.
+    ///
+    /// Matches can be of any length, so the content is searched as a whole
+    /// after moving the gap to the end like `make_contiguous`. This moves
+    /// bytes instead of copying the buffer, and nothing at all while the gap
+    /// stays at the end between searches.
+    ///
+    /// # Panics
+    ///
+    /// * If `from` is out of bounds.
+    pub fn regex_find(&mut self, re: &Regex, from: usize) -> Option<Range<usize>> {
+        re.find_at(self.make_contiguous(), from).map(|m| m.range())
+    }
+
+    /// Returns an iterator over the ranges of the non-overlapping matches of
+    /// `re`.
+    ///
+    /// Moves the gap to the end like `regex_find`.
+    pub fn regex_find_iter<'a>(&'a mut self, re: &'a Regex)
+        -> impl Iterator<Item = Range<usize>> + 'a
+    {
+        re.find_iter(self.make_contiguous()).map(|m| m.range())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use buf_from_str;
+
+
+    #[test]
+    fn regex_find_1() {
+        let mut gap_buf = buf_from_str("foo = 12, bar = 345");
+        gap_buf.cursor(14);
+        let re = Regex::new(r"\d+").unwrap();
+        assert!(gap_buf.regex_find(&re, 0) == Some(6..8));
+        assert!(gap_buf.regex_find(&re, 8) == Some(16..19));
+        assert!(gap_buf.regex_find(&re, 19).is_none());
+    }
+
+    #[test]
+    fn regex_find_iter_1() {
+        let mut gap_buf = buf_from_str("a1b22c333");
+        gap_buf.cursor(4);
+        let re = Regex::new(r"\d+").unwrap();
+        let found: Vec<_> = gap_buf.regex_find_iter(&re).collect();
+        assert!(found == [1..2, 3..5, 6..9]);
+    }
+}
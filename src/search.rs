@@ -0,0 +1,511 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use GapString;
+
+
+/// Pattern searched for by `GapString::find` and the other search methods,
+/// like those taken by `str::find`.
+///
+/// Implemented for `&str` and `&String`, for `char`, for char slices and
+/// arrays, which match any of their chars, and for closures taking a `char`,
+/// which match the chars they return `true` for.
+pub trait Pattern {
+    /// Returns the range of the first match in `haystack`.
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>>;
+
+    /// Returns the range of the last match in `haystack`.
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>>;
+
+    /// Returns the length in bytes of the longest match spanning more than
+    /// one char, which could cross the gap, or 0 if every match is a single
+    /// char.
+    fn crossing_len(&self) -> usize {
+        0
+    }
+}
+
+/// Iterator over the offsets of the non-overlapping matches of a pattern in
+/// a `GapString`.
+///
+/// Created by [`GapString::find_iter`](struct.GapString.html#method.find_iter).
+#[derive(Clone)]
+pub struct Matches<'a, P> {
+    buf: &'a GapString,
+    pattern: P,
+    pos: Option<usize>
+}
+
+/// Iterator over the pieces of a `GapString` separated by the matches of a
+/// pattern.
+///
+/// Created by [`GapString::split`](struct.GapString.html#method.split).
+#[derive(Clone)]
+pub struct Split<'a, P> {
+    inner: SplitWithRanges<'a, P>
+}
+
+/// Iterator over the pieces of a `GapString` separated by the matches of a
+/// pattern, along with their ranges.
+///
+/// Created by [`GapString::split_with_ranges`](struct.GapString.html#method.split_with_ranges).
+#[derive(Clone)]
+pub struct SplitWithRanges<'a, P> {
+    buf: &'a GapString,
+    pattern: P,
+    /// Start of the next piece.
+    start: usize,
+    /// Offset to search for the next match from, `None` past the end.
+    pos: Option<usize>,
+    finished: bool
+}
+
+impl GapString {
+    /// Returns `true` if `pattern` matches anywhere in the buffer.
+    ///
+    /// Searches like `find`, without allocating unless a match could cross
+    /// the gap.
+    pub fn contains<P: Pattern>(&self, pattern: P) -> bool {
+        self.find(pattern, 0).is_some()
+    }
+
+    /// Returns `true` if the content ends with `suffix`.
+    ///
+    /// Compares the bytes in place on either side of the gap.
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        suffix.len() <= self.len() && self.bytes_eq(self.len() - suffix.len(), suffix.as_bytes())
+    }
+
+    /// Returns the offset of the first match of `pattern` starting at or
+    /// after `from`.
+    ///
+    /// Each segment is searched in place. Matches of a string crossing the
+    /// gap are searched for in a copy of the up to `2 * needle.len()` bytes
+    /// around it, while a char never crosses it.
+    ///
+    /// # Panics
+    ///
+    /// * If `from` is out of bounds or not on a char boundary.
+    pub fn find<P: Pattern>(&self, mut pattern: P, from: usize) -> Option<usize> {
+        self.find_match(&mut pattern, from).map(|found| found.start)
+    }
+
+    /// Returns an iterator over the offsets of the non-overlapping matches
+    /// of `pattern` starting at or after `from`.
+    ///
+    /// Each match is found like `find`, so only the few bytes around the gap
+    /// are ever copied, never the whole buffer. An empty string matches at
+    /// every char boundary.
+    ///
+    /// # Panics
+    ///
+    /// * If `from` is out of bounds or not on a char boundary.
+    pub fn find_iter<P: Pattern>(&self, pattern: P, from: usize) -> Matches<'_, P> {
+        if let Err(e) = self.check_offset(from) {
+            panic!("{}", e);
+        }
+        Matches { buf: self, pattern, pos: Some(from) }
+    }
+
+    /// Returns the offset of the last match of `pattern` ending at or before
+    /// `end`.
+    ///
+    /// Searches like `find`, from the back.
+    ///
+    /// # Panics
+    ///
+    /// * If `end` is out of bounds or not on a char boundary.
+    pub fn rfind<P: Pattern>(&self, mut pattern: P, end: usize) -> Option<usize> {
+        self.rfind_match(&mut pattern, end).map(|found| found.start)
+    }
+
+    /// Returns an iterator over the pieces of the content separated by the
+    /// matches of `pattern`, like `str::split`.
+    ///
+    /// Matches are found like `find_iter`. Pieces are borrowed from the
+    /// buffer unless they span the gap.
+    pub fn split<P: Pattern>(&self, pattern: P) -> Split<'_, P> {
+        Split { inner: self.split_with_ranges(pattern) }
+    }
+
+    /// Returns an iterator over the pieces of the content separated by the
+    /// matches of `pattern` along with their ranges, so that they can be
+    /// edited afterwards.
+    ///
+    /// Splits like `split`.
+    pub fn split_with_ranges<P: Pattern>(&self, pattern: P) -> SplitWithRanges<'_, P> {
+        SplitWithRanges {
+            buf: self,
+            pattern,
+            start: 0,
+            pos: Some(0),
+            finished: false
+        }
+    }
+
+    /// Returns `true` if the content starts with `prefix`.
+    ///
+    /// Compares the bytes in place on either side of the gap.
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        prefix.len() <= self.len() && self.bytes_eq(0, prefix.as_bytes())
+    }
+
+    /// Returns the range of the first match of `pattern` starting at or after
+    /// `from`.
+    pub(crate) fn find_match<P: Pattern>(&self, pattern: &mut P, from: usize)
+        -> Option<Range<usize>>
+    {
+        if let Err(e) = self.check_offset(from) {
+            panic!("{}", e);
+        }
+
+        let (head, tail) = self.as_strs();
+        let split = head.len();
+        if from < split {
+            if let Some(found) = pattern.find_in(&head[from..]) {
+                return Some(shift(found, from));
+            }
+
+            let len = pattern.crossing_len();
+            if len > 1 {
+                let mut start = from.max(split.saturating_sub(len - 1));
+                while !head.is_char_boundary(start) {
+                    start += 1;
+                }
+                let mut end = tail.len().min(len - 1);
+                while !tail.is_char_boundary(end) {
+                    end += 1;
+                }
+                if let Some(found) = pattern.find_in(&crossing(&head[start..], &tail[..end])) {
+                    return Some(shift(found, start));
+                }
+            }
+        }
+
+        let start = from.max(split);
+        pattern.find_in(&tail[start - split..]).map(|found| shift(found, start))
+    }
+
+    /// Returns the range of the last match of `pattern` ending at or before
+    /// `end`.
+    pub(crate) fn rfind_match<P: Pattern>(&self, pattern: &mut P, end: usize)
+        -> Option<Range<usize>>
+    {
+        if let Err(e) = self.check_offset(end) {
+            panic!("{}", e);
+        }
+
+        let (head, tail) = self.as_strs();
+        let split = head.len();
+        if end > split {
+            if let Some(found) = pattern.rfind_in(&tail[..end - split]) {
+                return Some(shift(found, split));
+            }
+
+            let len = pattern.crossing_len();
+            if len > 1 {
+                let mut start = split.saturating_sub(len - 1);
+                while !head.is_char_boundary(start) {
+                    start -= 1;
+                }
+                let mut tail_end = (end - split).min(len - 1);
+                while !tail.is_char_boundary(tail_end) {
+                    tail_end -= 1;
+                }
+                let crossing = crossing(&head[start..], &tail[..tail_end]);
+                if let Some(found) = pattern.rfind_in(&crossing) {
+                    return Some(shift(found, start));
+                }
+            }
+        }
+
+        pattern.rfind_in(&head[..end.min(split)])
+    }
+
+    /// Returns `true` if the content at `offset` is `bytes`, which must fit
+    /// into the content.
+    fn bytes_eq(&self, offset: usize, bytes: &[u8]) -> bool {
+        let (head, tail) = self.as_slices();
+        let in_head = head.len().saturating_sub(offset).min(bytes.len());
+        let tail_start = (offset + in_head).saturating_sub(head.len());
+        head[offset.min(head.len())..][..in_head] == bytes[..in_head]
+            && tail[tail_start..][..bytes.len() - in_head] == bytes[in_head..]
+    }
+}
+
+impl<'a, P: Pattern> Iterator for Matches<'a, P> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let found = self.buf.find_match(&mut self.pattern, self.pos?)?;
+        self.pos = next_search(self.buf, &found);
+        Some(found.start)
+    }
+}
+
+impl<'a, P: Pattern> Iterator for Split<'a, P> {
+    type Item = Cow<'a, str>;
+
+    fn next(&mut self) -> Option<Cow<'a, str>> {
+        self.inner.next().map(|(_, piece)| piece)
+    }
+}
+
+impl<'a, P: Pattern> Iterator for SplitWithRanges<'a, P> {
+    type Item = (Range<usize>, Cow<'a, str>);
+
+    fn next(&mut self) -> Option<(Range<usize>, Cow<'a, str>)> {
+        if self.finished {
+            return None;
+        }
+
+        let found = match self.pos {
+            Some(pos) => self.buf.find_match(&mut self.pattern, pos),
+            None => None
+        };
+        let range = match found {
+            Some(found) => {
+                self.pos = next_search(self.buf, &found);
+                let range = self.start..found.start;
+                self.start = found.end;
+                range
+            }
+            None => {
+                self.finished = true;
+                self.start..self.buf.len()
+            }
+        };
+        Some((range.clone(), self.buf.slice_unchecked(range)))
+    }
+}
+
+impl Pattern for &str {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.find(*self).map(|i| i..i + self.len())
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.rfind(*self).map(|i| i..i + self.len())
+    }
+
+    fn crossing_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Pattern for &String {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        self.as_str().find_in(haystack)
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        self.as_str().rfind_in(haystack)
+    }
+
+    fn crossing_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Pattern for char {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.find(*self).map(|i| i..i + self.len_utf8())
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.rfind(*self).map(|i| i..i + self.len_utf8())
+    }
+}
+
+impl Pattern for &[char] {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.find(*self).map(|i| char_range(haystack, i))
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.rfind(*self).map(|i| char_range(haystack, i))
+    }
+}
+
+impl<const N: usize> Pattern for [char; N] {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        (&self[..]).find_in(haystack)
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        (&self[..]).rfind_in(haystack)
+    }
+}
+
+impl<const N: usize> Pattern for &[char; N] {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        (&self[..]).find_in(haystack)
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        (&self[..]).rfind_in(haystack)
+    }
+}
+
+impl<F: FnMut(char) -> bool> Pattern for F {
+    fn find_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.find(&mut *self).map(|i| char_range(haystack, i))
+    }
+
+    fn rfind_in(&mut self, haystack: &str) -> Option<Range<usize>> {
+        haystack.rfind(&mut *self).map(|i| char_range(haystack, i))
+    }
+}
+
+/// Returns the offset to search for the match after `found` from, past the
+/// next char if `found` is empty, or `None` if that is past the end.
+fn next_search(buf: &GapString, found: &Range<usize>) -> Option<usize> {
+    if !found.is_empty() {
+        Some(found.end)
+    } else {
+        buf.char_at(found.start).map(|ch| found.start + ch.len_utf8())
+    }
+}
+
+/// Returns the range of the char at `offset` in `s`.
+fn char_range(s: &str, offset: usize) -> Range<usize> {
+    let len = s[offset..].chars().next().map_or(0, char::len_utf8);
+    offset..offset + len
+}
+
+/// Returns the range `found` at `offset` into the content.
+fn shift(found: Range<usize>, offset: usize) -> Range<usize> {
+    found.start + offset..found.end + offset
+}
+
+/// Returns the end of the head joined with the start of the tail, to search
+/// for matches crossing the gap.
+fn crossing(head: &str, tail: &str) -> String {
+    let mut s = String::with_capacity(head.len() + tail.len());
+    s.push_str(head);
+    s.push_str(tail);
+    s
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use {buf_from_str, Edit, GapString};
+
+
+    #[test]
+    fn contains_1() {
+        let mut gap_buf = buf_from_str("hello wörld");
+        for gap in [0, 3, 5, 6, 9, 12] {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.starts_with("hello w") && gap_buf.starts_with(""));
+            assert!(!gap_buf.starts_with("help") && !gap_buf.starts_with("hello wörld!"));
+            assert!(gap_buf.ends_with("o wörld") && gap_buf.ends_with("hello wörld"));
+            assert!(!gap_buf.ends_with("word") && !gap_buf.ends_with("!hello wörld"));
+            assert!(gap_buf.contains("lo wö") && !gap_buf.contains("low"));
+        }
+    }
+
+    #[test]
+    fn find_1() {
+        let mut gap_buf = buf_from_str("abcabcabc");
+        for gap in 0..10 {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.find("cab", 0) == Some(2));
+            assert!(gap_buf.find("cab", 3) == Some(5));
+            assert!(gap_buf.find("cab", 6).is_none());
+            assert!(gap_buf.find("abc", 6) == Some(6));
+            assert!(gap_buf.find("", 4) == Some(4));
+        }
+    }
+
+    #[test]
+    fn find_2() {
+        let mut gap_buf = buf_from_str("ééé");
+        gap_buf.cursor(2);
+        assert!(gap_buf.find("éé", 0) == Some(0));
+        assert!(gap_buf.find("éé", 2) == Some(2));
+        assert!(gap_buf.find("éé", 4).is_none());
+    }
+
+    #[test]
+    fn find_3() {
+        let mut gap_buf = buf_from_str("fn main() { é.x }");
+        for gap in [0, 3, 12, 14, 18] {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.find('(', 0) == Some(7) && gap_buf.rfind('x', 18) == Some(15));
+            assert!(gap_buf.find(&['{', '}'][..], 0) == Some(10));
+            assert!(gap_buf.rfind(['{', '}'], 17) == Some(10));
+            assert!(gap_buf.find(|ch: char| !ch.is_ascii(), 0) == Some(12));
+            assert!(gap_buf.rfind(char::is_whitespace, 12) == Some(11));
+            assert!(gap_buf.find(&"main".to_string(), 0) == Some(3));
+            assert!(gap_buf.contains('.') && !gap_buf.contains(char::is_uppercase));
+        }
+        let found: Vec<_> = gap_buf.find_iter(char::is_alphabetic, 5).collect();
+        assert!(found == [5, 6, 12, 15]);
+    }
+
+    #[test]
+    fn find_iter_1() {
+        let mut gap_buf = buf_from_str("aaaaa-aé");
+        gap_buf.cursor(3);
+        assert!(gap_buf.find_iter("aa", 0).collect::<Vec<_>>() == [0, 2]);
+        assert!(gap_buf.find_iter("a", 3).collect::<Vec<_>>() == [3, 4, 6]);
+        assert!(gap_buf.find_iter("", 5).collect::<Vec<_>>() == [5, 6, 7, 9]);
+        assert!(gap_buf.find_iter("b", 0).next().is_none());
+    }
+
+    #[test]
+    fn rfind_1() {
+        let mut gap_buf = buf_from_str("abcabcabc");
+        for gap in 0..10 {
+            gap_buf.cursor(gap);
+            assert!(gap_buf.rfind("cab", 9) == Some(5));
+            assert!(gap_buf.rfind("cab", 7) == Some(2));
+            assert!(gap_buf.rfind("cab", 4).is_none());
+            assert!(gap_buf.rfind("abc", 3) == Some(0));
+            assert!(gap_buf.rfind("", 4) == Some(4));
+        }
+
+        gap_buf.cursor(4);
+        assert!(gap_buf.rfind("éé", 9).is_none());
+    }
+
+    #[test]
+    fn split_1() {
+        let mut gap_buf = buf_from_str("a, b,, cé, d");
+        gap_buf.cursor(8);
+        let pieces: Vec<_> = gap_buf.split(", ").collect();
+        assert!(pieces == ["a", "b,", "cé", "d"]);
+        assert!(matches!(pieces[2], Cow::Owned(_)));
+        let pieces: Vec<_> = gap_buf.split(|ch| ch == ',' || ch == ' ').collect();
+        assert!(pieces == ["a", "", "b", "", "", "cé", "", "d"]);
+        assert!(gap_buf.split("").collect::<Vec<_>>().len() == gap_buf.chars().count() + 2);
+
+        let empty = GapString::new();
+        assert!(empty.split(',').collect::<Vec<_>>() == [""]);
+    }
+
+    #[test]
+    fn split_with_ranges_1() {
+        let mut gap_buf = buf_from_str("one\ntwo\n");
+        gap_buf.cursor(5);
+        let lines: Vec<_> = gap_buf.split_with_ranges('\n').collect();
+        assert!(lines == [(0..3, "one".into()), (4..7, "two".into()), (8..8, "".into())]);
+
+        let edits: Vec<_> = gap_buf.split_with_ranges('\n')
+            .map(|(range, line)| Edit { range, text: line.to_uppercase() })
+            .collect();
+        gap_buf.apply_edits(&edits);
+        assert!(gap_buf == "ONE\nTWO\n");
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::{self, Visitor};
+
+use GapString;
+
+
+/// Smallest gap a deserialized buffer starts out with.
+const MIN_GAP: usize = 64;
+
+impl Serialize for GapString {
+    /// Serializes the buffer as its text content.
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.as_strs() {
+            (head, "") => serializer.serialize_str(head),
+            ("", tail) => serializer.serialize_str(tail),
+            _ => serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for GapString {
+    /// Deserializes text into a buffer with the gap at the end, sized to an
+    /// eighth of the content but at least `MIN_GAP` bytes.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<GapString, D::Error> {
+        deserializer.deserialize_str(GapStringVisitor)
+    }
+}
+
+struct GapStringVisitor;
+
+impl<'de> Visitor<'de> for GapStringVisitor {
+    type Value = GapString;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<GapString, E> {
+        let gap = (s.len() / 8).max(MIN_GAP);
+        Ok(GapString::with_gap_capacity(s, gap, s.len()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use serde_test::{assert_tokens, Token};
+
+    use GapString;
+
+
+    #[test]
+    fn serde_1() {
+        let mut gap_buf = GapString::from("12345678");
+        gap_buf.insert_str(4, "-");
+        assert_tokens(&gap_buf, &[Token::Str("1234-5678")]);
+
+        let gap_buf = GapString::from("");
+        assert_tokens(&gap_buf, &[Token::Str("")]);
+    }
+}
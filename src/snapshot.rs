@@ -0,0 +1,296 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+// A snapshot holds the content as a list of shared, immutable blocks. The
+// buffer keeps the blocks of its last snapshot along with how much of the
+// content at either end was left alone since, so the next snapshot reuses
+// every block outside the edited region and only copies the bytes in it.
+
+use std::{fmt, slice};
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, OnceLock};
+
+use GapString;
+
+
+/// Largest block of a snapshot, in bytes, give or take a char.
+const BLOCK_SIZE: usize = 16 * 1024;
+
+
+/// Immutable copy of the content of a `GapString` at one point in time.
+///
+/// Created by [`GapString::snapshot`](struct.GapString.html#method.snapshot).
+/// The content is stored in shared blocks, so clones are cheap, snapshots
+/// of successive versions share the blocks an edit did not touch, and a
+/// snapshot can be read from other threads while the buffer keeps being
+/// edited.
+///
+/// `chunks` reads the blocks as they are. `as_str`, and the `str` methods
+/// reached through `Deref`, join them into one string the first time they
+/// are called on a snapshot of more than one block.
+#[derive(Clone)]
+pub struct Snapshot {
+    inner: Arc<Blocks>
+}
+
+/// Iterator over the blocks of a `Snapshot`, returned by `Snapshot::chunks`.
+#[derive(Clone, Debug)]
+pub struct SnapshotChunks<'a> {
+    iter: slice::Iter<'a, Arc<str>>
+}
+
+struct Blocks {
+    blocks: Vec<Arc<str>>,
+    len: usize,
+    joined: OnceLock<String>
+}
+
+/// Blocks of the last snapshot of a buffer, to reuse in the next one.
+#[derive(Default)]
+pub(crate) struct SnapshotCache {
+    last: Option<Snapshot>,
+    /// Bytes at the start and at the end of the content left unchanged since
+    /// the last snapshot, or `None` if there was no edit.
+    unchanged: Option<(usize, usize)>
+}
+
+impl GapString {
+    /// Returns a snapshot of the current content.
+    ///
+    /// Takes O(k) time, without moving the gap, where k is the number of
+    /// bytes edited since the last snapshot plus the number of blocks:
+    /// blocks of the last snapshot that no edit touched are shared rather
+    /// than copied.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut cache = self.snapshots.borrow_mut();
+        let snapshot = match (cache.last.take(), cache.unchanged) {
+            (Some(last), None) => last,
+            (Some(last), Some((prefix, suffix))) => self.snapshot_from(&last, prefix, suffix),
+            (None, _) => self.snapshot_from(&Snapshot::default(), 0, 0)
+        };
+        cache.last = Some(snapshot.clone());
+        cache.unchanged = None;
+        snapshot
+    }
+
+    /// Records that the content at `offset` was replaced by `inserted`
+    /// bytes, for the next snapshot.
+    pub(crate) fn adjust_snapshots(&mut self, offset: usize, inserted: usize) {
+        let cache = self.snapshots.get_mut();
+        if cache.last.is_none() {
+            return;
+        }
+
+        let suffix = self.raw.len() - offset - inserted;
+        cache.unchanged = Some(match cache.unchanged {
+            Some((prefix, old_suffix)) => (prefix.min(offset), old_suffix.min(suffix)),
+            None => (offset, suffix)
+        });
+    }
+
+    /// Builds a snapshot sharing the blocks of `last` within the first
+    /// `prefix` and the last `suffix` bytes of its content.
+    fn snapshot_from(&self, last: &Snapshot, prefix: usize, suffix: usize) -> Snapshot {
+        let old = &last.inner.blocks;
+        let mut head = 0;
+        let mut head_len = 0;
+        while head < old.len() && head_len + old[head].len() <= prefix {
+            head_len += old[head].len();
+            head += 1;
+        }
+        let mut tail = old.len();
+        let mut tail_len = 0;
+        while tail > head && tail_len + old[tail - 1].len() <= suffix {
+            tail_len += old[tail - 1].len();
+            tail -= 1;
+        }
+
+        // Takes small edited regions together with a neighbouring block, so
+        // that edits do not leave ever smaller blocks behind.
+        while self.len() - head_len - tail_len < BLOCK_SIZE / 2 && (head > 0 || tail < old.len()) {
+            if head > 0 {
+                head -= 1;
+                head_len -= old[head].len();
+            } else {
+                tail_len -= old[tail].len();
+                tail += 1;
+            }
+        }
+
+        let mut blocks = old[..head].to_vec();
+        let middle = self.slice_unchecked(head_len..self.len() - tail_len);
+        let count = middle.len().div_ceil(BLOCK_SIZE);
+        let mut rest = &*middle;
+        for i in (1..=count).rev() {
+            let mut end = if i == 1 { rest.len() } else { rest.len() / i };
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            blocks.push(Arc::from(&rest[..end]));
+            rest = &rest[end..];
+        }
+        blocks.extend_from_slice(&old[tail..]);
+        Snapshot::from_blocks(blocks)
+    }
+}
+
+impl Snapshot {
+    /// Returns the content of the snapshot, joining its blocks on the first
+    /// call.
+    pub fn as_str(&self) -> &str {
+        match *self.inner.blocks {
+            [] => "",
+            [ref block] => block,
+            _ => self.inner.joined.get_or_init(|| self.chunks().collect())
+        }
+    }
+
+    /// Returns an iterator over the blocks of the content, in order, such as
+    /// for searching it without joining them.
+    pub fn chunks(&self) -> SnapshotChunks<'_> {
+        SnapshotChunks { iter: self.inner.blocks.iter() }
+    }
+
+    /// Returns `true` if the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.inner.len == 0
+    }
+
+    /// Returns the length of the content in bytes.
+    pub fn len(&self) -> usize {
+        self.inner.len
+    }
+
+    fn from_blocks(blocks: Vec<Arc<str>>) -> Snapshot {
+        let len = blocks.iter().map(|block| block.len()).sum();
+        Snapshot { inner: Arc::new(Blocks { blocks, len, joined: OnceLock::new() }) }
+    }
+}
+
+impl AsRef<str> for Snapshot {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Default for Snapshot {
+    fn default() -> Snapshot {
+        Snapshot::from_blocks(Vec::new())
+    }
+}
+
+impl Deref for Snapshot {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.chunks().try_for_each(|chunk| f.write_str(chunk))
+    }
+}
+
+impl PartialEq for Snapshot {
+    fn eq(&self, other: &Snapshot) -> bool {
+        self.len() == other.len()
+            && self.chunks().flat_map(str::bytes).eq(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl Eq for Snapshot {}
+
+impl Hash for Snapshot {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialOrd for Snapshot {
+    fn partial_cmp(&self, other: &Snapshot) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Snapshot {
+    fn cmp(&self, other: &Snapshot) -> Ordering {
+        self.chunks().flat_map(str::bytes).cmp(other.chunks().flat_map(str::bytes))
+    }
+}
+
+impl<'a> Iterator for SnapshotChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.iter.next().map(|block| &**block)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use buf_from_str;
+    use super::BLOCK_SIZE;
+
+
+    #[test]
+    fn snapshot_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.insert_str(4, "-");
+        let snapshot = gap_buf.snapshot();
+        let copy = snapshot.clone();
+        gap_buf.remove(0..4);
+
+        let len = thread::spawn(move || copy.len()).join().unwrap();
+        assert!(len == 9);
+        assert!(&*snapshot == "1234-5678");
+        assert!(gap_buf == "-5678");
+    }
+
+    #[test]
+    fn snapshot_2() {
+        let text: String = (0..4 * BLOCK_SIZE).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let mut gap_buf = buf_from_str(&text);
+        let first = gap_buf.snapshot();
+        assert!(first.chunks().count() == 4);
+
+        gap_buf.insert_str(BLOCK_SIZE + 10, "é");
+        gap_buf.remove(BLOCK_SIZE + 20..BLOCK_SIZE + 30);
+        let second = gap_buf.snapshot();
+        let shared = first.inner.blocks.iter().zip(&second.inner.blocks)
+            .filter(|&(a, b)| Arc::ptr_eq(a, b))
+            .count();
+        assert!(shared == 3);
+        assert!(Arc::ptr_eq(&first.inner.blocks[3], second.inner.blocks.last().unwrap()));
+
+        let search = thread::spawn(move || {
+            second.chunks().map(|chunk| chunk.matches('é').count()).sum::<usize>()
+        });
+        gap_buf.insert_str(0, "é");
+        assert!(search.join().unwrap() == 1);
+        assert!(first.as_str() == text);
+        assert!(gap_buf == gap_buf.snapshot().as_str());
+        assert!(Arc::ptr_eq(&gap_buf.snapshot().inner, &gap_buf.snapshot().inner));
+    }
+}
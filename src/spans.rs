@@ -0,0 +1,670 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::any::Any;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+use {EditDelta, GapString};
+
+
+/// Values attached to ranges of a buffer's content, such as syntax
+/// highlights, diagnostics or folds, that follow its edits.
+///
+/// Spans may overlap. They are kept in an interval tree: a treap ordered by
+/// start, each node holding the largest end within its subtree and a shift
+/// of its descendants not applied yet. Inserting a span takes O(log n)
+/// expected time, and looking up the spans over a range or adjusting them
+/// to an edit O((k + 1) log n), k being the number of spans found or
+/// touching the edit. Spans past an edit are shifted all at once.
+///
+/// Attached to a buffer with `GapString::attach_spans`, the spans follow
+/// every edit of it, undo and redo included. Spans kept apart from a buffer
+/// must be passed the `EditDelta` of every edit, as returned by the editing
+/// methods and given to `on_change` listeners, in the order the edits were
+/// made.
+#[derive(Clone, Default)]
+pub struct Spans<T> {
+    root: Link<T>,
+    len: usize,
+    /// Number of nodes created, from which their priority is derived.
+    next_id: u64
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+#[derive(Clone)]
+struct Node<T> {
+    range: Range<usize>,
+    value: T,
+    priority: u64,
+    /// Largest end within the subtree, `range` included.
+    max_end: usize,
+    /// Offset to add to every position of the descendants, wrapping.
+    shift: usize,
+    left: Link<T>,
+    right: Link<T>
+}
+
+/// In-order iterator over the spans of a tree.
+struct Iter<'a, T: 'a> {
+    /// Nodes left to visit along with the shift of their ancestors.
+    stack: Vec<(&'a Node<T>, usize)>
+}
+
+/// Handle to spans attached by `GapString::attach_spans`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpansId(usize);
+
+/// Spans attached to a buffer, kept in the order they were attached.
+#[derive(Default)]
+pub(crate) struct AttachedSpans {
+    sets: Vec<(SpansId, Box<dyn AnySpans>)>,
+    next_id: usize
+}
+
+/// `Spans` of any value type, as attached to a buffer.
+trait AnySpans: Send {
+    fn adjust(&mut self, delta: &EditDelta);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn box_clone(&self) -> Box<dyn AnySpans>;
+    fn heap_size(&self) -> usize;
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl GapString {
+    /// Attaches `spans` to the buffer, which moves them along with every
+    /// edit of its content from then on, undo and redo included.
+    ///
+    /// Like marks, the spans are in offsets of the content, preedit text
+    /// included, and they are copied along with the buffer.
+    pub fn attach_spans<T>(&mut self, spans: Spans<T>) -> SpansId
+        where T: Clone + PartialEq + Send + 'static
+    {
+        let id = SpansId(self.spans.next_id);
+        self.spans.next_id += 1;
+        self.spans.sets.push((id, Box::new(spans)));
+        id
+    }
+
+    /// Detaches spans from the buffer and returns them, or `None` if they
+    /// have been detached already or their values are not of type `T`.
+    pub fn detach_spans<T: 'static>(&mut self, id: SpansId) -> Option<Spans<T>> {
+        let index = self.spans.sets.iter().position(|&(other, ref spans)| {
+            other == id && spans.as_any().is::<Spans<T>>()
+        })?;
+        let (_, spans) = self.spans.sets.remove(index);
+        spans.into_any().downcast().ok().map(|spans| *spans)
+    }
+
+    /// Returns attached spans, or `None` if they have been detached or
+    /// their values are not of type `T`.
+    pub fn spans<T: 'static>(&self, id: SpansId) -> Option<&Spans<T>> {
+        self.spans.sets
+            .iter()
+            .find(|&&(other, _)| other == id)
+            .and_then(|(_, spans)| spans.as_any().downcast_ref())
+    }
+
+    /// Returns attached spans to change them, or `None` if they have been
+    /// detached or their values are not of type `T`.
+    pub fn spans_mut<T: 'static>(&mut self, id: SpansId) -> Option<&mut Spans<T>> {
+        self.spans.sets
+            .iter_mut()
+            .find(|&&mut (other, _)| other == id)
+            .and_then(|(_, spans)| spans.as_any_mut().downcast_mut())
+    }
+
+    /// Moves attached spans to account for an edit at `offset`.
+    pub(crate) fn adjust_spans(&mut self,
+                               offset: usize,
+                               removed: usize,
+                               inserted: usize)
+    {
+        let delta = EditDelta { offset, removed_len: removed, inserted_len: inserted };
+        for (_, spans) in &mut self.spans.sets {
+            spans.adjust(&delta);
+        }
+    }
+}
+
+impl<T> Spans<T> {
+    /// Creates an empty set of spans.
+    pub fn new() -> Spans<T> {
+        Spans {
+            root: None,
+            len: 0,
+            next_id: 0
+        }
+    }
+
+    /// Removes every span.
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.len = 0;
+    }
+
+    /// Attaches `value` to `range`. Empty ranges are ignored.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed.
+    pub fn insert(&mut self, range: Range<usize>, value: T) {
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        if range.start == range.end {
+            return;
+        }
+
+        let (left, right) = split(self.root.take(), range.start, true);
+        let node = self.node(range, value);
+        self.root = merge(merge(left, node), right);
+        self.len += 1;
+    }
+
+    /// Returns `true` if there are no spans.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns every span, in order of their start.
+    pub fn iter(&self) -> impl Iterator<Item = (Range<usize>, &T)> + '_ {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left(&self.root, 0);
+        iter
+    }
+
+    /// Returns the number of spans.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the spans overlapping `range`, in order of their start, or
+    /// those containing `range.start` if it is empty.
+    pub fn overlapping(&self, range: Range<usize>)
+        -> impl Iterator<Item = (Range<usize>, &T)> + '_
+    {
+        let limit = if range.start < range.end { range.end } else { range.start + 1 };
+        let mut found = Vec::new();
+        collect_overlapping(&self.root, 0, range.start, limit, &mut found);
+        found.into_iter()
+    }
+
+    /// Returns a tree of the sorted `spans`.
+    fn build(&mut self, spans: Vec<(Range<usize>, T)>) -> Link<T> {
+        let mut link = None;
+        for (range, value) in spans {
+            let node = self.node(range, value);
+            link = merge(link, node);
+        }
+        link
+    }
+
+    fn node(&mut self, range: Range<usize>, value: T) -> Link<T> {
+        self.next_id += 1;
+        Some(Box::new(Node {
+            max_end: range.end,
+            range,
+            value,
+            priority: priority(self.next_id),
+            shift: 0,
+            left: None,
+            right: None
+        }))
+    }
+}
+
+impl<T: Clone + PartialEq> Spans<T> {
+    /// Moves the spans to account for an edit of the content.
+    ///
+    /// Spans after the edit are shifted, and a span the edit lies within
+    /// grows or shrinks with it. Text inserted at either edge of a span ends
+    /// up outside of it, and so does text replacing its edge. Spans whose
+    /// content is removed entirely are dropped, and spans of equal values
+    /// left touching by a removal are merged.
+    pub fn adjust(&mut self, delta: &EditDelta) {
+        let EditDelta { offset, removed_len: removed, inserted_len: inserted } = *delta;
+        if removed == 0 && inserted == 0 {
+            return;
+        }
+
+        let end = offset + removed;
+        let (mut before, rest) = split(self.root.take(), offset, false);
+        let (within, mut after) = split(rest, end, false);
+
+        // Spans starting before the edit keep their start.
+        visit_ends_after(&mut before, offset, &mut |span, _| {
+            span.end = if span.end >= end { span.end - removed + inserted } else { offset };
+        });
+
+        // Spans starting within the removed text now start after the
+        // inserted text, and are dropped if they end before it.
+        let mut moved = Vec::new();
+        drain(within, &mut moved);
+        let count = moved.len();
+        moved = moved.into_iter()
+            .map(|(span, value)| {
+                let span_end = if span.end >= end { span.end - removed + inserted } else { offset };
+                (offset + inserted..span_end, value)
+            })
+            .filter(|(span, _)| span.start < span.end)
+            .collect();
+        self.len -= count - moved.len();
+
+        if let Some(ref mut node) = after {
+            apply(node, inserted.wrapping_sub(removed));
+        }
+        let within = self.build(moved);
+        self.root = merge(before, merge(within, after));
+
+        if removed > 0 && inserted == 0 {
+            self.merge_at(offset);
+        }
+    }
+
+    /// Removes the spans within `range`, splitting those crossing either of
+    /// its edges, such as before highlighting the range again.
+    ///
+    /// # Panics
+    ///
+    /// * If `range` is reversed.
+    pub fn remove(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end, "Invalid range: {:?}", range);
+        if range.start == range.end {
+            return;
+        }
+
+        // Spans starting before the range keep what is before it, the
+        // others are dropped, and what is after the range is added back.
+        let mut tails = Vec::new();
+        let (mut before, rest) = split(self.root.take(), range.start, false);
+        visit_ends_after(&mut before, range.start, &mut |span, value| {
+            if range.end < span.end {
+                tails.push((range.end..span.end, value.clone()));
+            }
+            span.end = range.start;
+        });
+        let (within, after) = split(rest, range.end, false);
+        let mut dropped = Vec::new();
+        drain(within, &mut dropped);
+        self.root = merge(before, after);
+        self.len -= dropped.len();
+
+        for (span, value) in dropped {
+            if range.end < span.end {
+                tails.push((range.end..span.end, value));
+            }
+        }
+        for (span, value) in tails {
+            self.insert(span, value);
+        }
+    }
+
+    /// Merges spans of equal values, one ending and the other starting at
+    /// `offset`.
+    fn merge_at(&mut self, offset: usize) {
+        // No span ends at 0.
+        if offset == 0 {
+            return;
+        }
+
+        let (mut before, rest) = split(self.root.take(), offset, false);
+        let (at, after) = split(rest, offset, true);
+        let mut starting = Vec::new();
+        drain(at, &mut starting);
+
+        // Spans ending at `offset` are extended in place, so that spans
+        // starting at the same offset keep their order.
+        let mut ending = Vec::new();
+        visit_ends_after(&mut before, offset - 1, &mut |span, value| {
+            if span.end == offset {
+                ending.push((None, value.clone()));
+            }
+        });
+        let count = starting.len();
+        starting.retain(|(span, value)| {
+            let prev = ending.iter_mut()
+                .rev()
+                .find(|(end, prev_value)| end.is_none() && prev_value == value);
+            match prev {
+                Some(&mut (ref mut end, _)) => {
+                    *end = Some(span.end);
+                    false
+                }
+                None => true
+            }
+        });
+        self.len -= count - starting.len();
+
+        let mut ending = ending.into_iter();
+        visit_ends_after(&mut before, offset - 1, &mut |span, _| {
+            if span.end == offset {
+                if let Some((Some(end), _)) = ending.next() {
+                    span.end = end;
+                }
+            }
+        });
+        let at = self.build(starting);
+        self.root = merge(before, merge(at, after));
+    }
+}
+
+impl<T: Clone + PartialEq + Send + 'static> AnySpans for Spans<T> {
+    fn adjust(&mut self, delta: &EditDelta) {
+        Spans::adjust(self, delta);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn box_clone(&self) -> Box<dyn AnySpans> {
+        Box::new(self.clone())
+    }
+
+    fn heap_size(&self) -> usize {
+        self.len * mem::size_of::<Node<T>>()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+impl AttachedSpans {
+    /// Returns the number of bytes allocated for the attached spans.
+    pub(crate) fn heap_size(&self) -> usize {
+        let spans: usize = self.sets.iter().map(|(_, spans)| spans.heap_size()).sum();
+        self.sets.capacity() * mem::size_of::<(SpansId, Box<dyn AnySpans>)>() + spans
+    }
+}
+
+impl Clone for AttachedSpans {
+    fn clone(&self) -> AttachedSpans {
+        AttachedSpans {
+            sets: self.sets.iter().map(|&(id, ref spans)| (id, spans.box_clone())).collect(),
+            next_id: self.next_id
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Spans<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spans<T> {
+    fn eq(&self, other: &Spans<T>) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for Spans<T> {}
+
+impl<'a, T> Iter<'a, T> {
+    fn push_left(&mut self, mut link: &'a Link<T>, mut shift: usize) {
+        while let Some(ref node) = *link {
+            self.stack.push((node, shift));
+            shift = shift.wrapping_add(node.shift);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Range<usize>, &'a T);
+
+    fn next(&mut self) -> Option<(Range<usize>, &'a T)> {
+        let (node, shift) = self.stack.pop()?;
+        self.push_left(&node.right, shift.wrapping_add(node.shift));
+        Some((shifted(&node.range, shift), &node.value))
+    }
+}
+
+/// Adds `shift` to every position of the subtree of `node`.
+fn apply<T>(node: &mut Node<T>, shift: usize) {
+    node.range = shifted(&node.range, shift);
+    node.max_end = node.max_end.wrapping_add(shift);
+    node.shift = node.shift.wrapping_add(shift);
+}
+
+/// Appends the spans of the tree overlapping `start..limit` to `found`,
+/// `shift` being that of the ancestors of `link`.
+fn collect_overlapping<'a, T>(link: &'a Link<T>,
+                              shift: usize,
+                              start: usize,
+                              limit: usize,
+                              found: &mut Vec<(Range<usize>, &'a T)>)
+{
+    let node = match *link {
+        Some(ref node) if node.max_end.wrapping_add(shift) > start => node,
+        _ => return
+    };
+
+    let span = shifted(&node.range, shift);
+    let shift = shift.wrapping_add(node.shift);
+    collect_overlapping(&node.left, shift, start, limit, found);
+    if span.start < limit {
+        if span.end > start {
+            found.push((span, &node.value));
+        }
+        collect_overlapping(&node.right, shift, start, limit, found);
+    }
+}
+
+/// Moves every span of the tree to `spans`, in order.
+fn drain<T>(link: Link<T>, spans: &mut Vec<(Range<usize>, T)>) {
+    if let Some(mut node) = link {
+        push_down(&mut node);
+        let Node { range, value, left, right, .. } = *node;
+        drain(left, spans);
+        spans.push((range, value));
+        drain(right, spans);
+    }
+}
+
+/// Joins two trees, every span of `left` starting before or where those of
+/// `right` do.
+fn merge<T>(left: Link<T>, right: Link<T>) -> Link<T> {
+    match (left, right) {
+        (None, link) | (link, None) => link,
+        (Some(mut left), Some(mut right)) => {
+            if left.priority > right.priority {
+                push_down(&mut left);
+                left.right = merge(left.right.take(), Some(right));
+                update(&mut left);
+                Some(left)
+            } else {
+                push_down(&mut right);
+                right.left = merge(Some(left), right.left.take());
+                update(&mut right);
+                Some(right)
+            }
+        }
+    }
+}
+
+/// Returns a pseudo-random priority for the `id`th node, from the
+/// SplitMix64 generator.
+fn priority(id: u64) -> u64 {
+    let mut z = id.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Applies the shift of `node` to its children.
+fn push_down<T>(node: &mut Node<T>) {
+    if node.shift == 0 {
+        return;
+    }
+    if let Some(ref mut left) = node.left {
+        apply(left, node.shift);
+    }
+    if let Some(ref mut right) = node.right {
+        apply(right, node.shift);
+    }
+    node.shift = 0;
+}
+
+fn shifted(range: &Range<usize>, shift: usize) -> Range<usize> {
+    range.start.wrapping_add(shift)..range.end.wrapping_add(shift)
+}
+
+/// Splits the tree into the spans starting before `at`, or at it too if
+/// `inclusive`, and the others.
+fn split<T>(link: Link<T>, at: usize, inclusive: bool) -> (Link<T>, Link<T>) {
+    let mut node = match link {
+        Some(node) => node,
+        None => return (None, None)
+    };
+
+    push_down(&mut node);
+    if node.range.start < at || inclusive && node.range.start == at {
+        let (left, right) = split(node.right.take(), at, inclusive);
+        node.right = left;
+        update(&mut node);
+        (Some(node), right)
+    } else {
+        let (left, right) = split(node.left.take(), at, inclusive);
+        node.left = right;
+        update(&mut node);
+        (left, Some(node))
+    }
+}
+
+/// Recomputes the largest end within the subtree of `node`, whose shift
+/// has been applied to its children.
+fn update<T>(node: &mut Node<T>) {
+    node.max_end = node.range.end;
+    for child in node.left.iter().chain(node.right.iter()) {
+        node.max_end = node.max_end.max(child.max_end);
+    }
+}
+
+/// Calls `f` on the spans of the tree ending after `pos`, in order, letting
+/// it change their end.
+fn visit_ends_after<T, F>(link: &mut Link<T>, pos: usize, f: &mut F)
+    where F: FnMut(&mut Range<usize>, &mut T)
+{
+    if let Some(ref mut node) = *link {
+        if node.max_end <= pos {
+            return;
+        }
+        push_down(node);
+        visit_ends_after(&mut node.left, pos, f);
+        if node.range.end > pos {
+            f(&mut node.range, &mut node.value);
+        }
+        visit_ends_after(&mut node.right, pos, f);
+        update(node);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {EditDelta, GapString};
+    use super::Spans;
+
+
+    #[test]
+    fn adjust_1() {
+        let mut gap_buf = GapString::from("let x = foo(1);");
+        let mut spans = Spans::new();
+        spans.insert(0..3, "keyword");
+        spans.insert(8..11, "call");
+        spans.insert(12..13, "number");
+
+        spans.adjust(&gap_buf.insert_str(0, "  "));
+        spans.adjust(&gap_buf.insert_str(12, "ba"));
+        spans.adjust(&gap_buf.insert_str(5, "!"));
+        spans.adjust(&gap_buf.remove(17..18));
+        assert!(gap_buf == "  let! x = fobao();");
+        let found: Vec<_> = spans.iter().collect();
+        assert!(found == [(2..5, &"keyword"), (11..16, &"call")]);
+    }
+
+    #[test]
+    fn adjust_2() {
+        let mut spans = Spans::new();
+        for i in 0..1000 {
+            spans.insert(i * 10..i * 10 + 5, i);
+        }
+
+        spans.adjust(&EditDelta { offset: 0, removed_len: 0, inserted_len: 3 });
+        spans.adjust(&EditDelta { offset: 5004, removed_len: 10, inserted_len: 0 });
+        assert!(spans.len() == 1000);
+        let found: Vec<_> = spans.overlapping(5000..5010).collect();
+        assert!(found == [(5003..5004, &500), (5004..5008, &501)]);
+        assert!(spans.iter().last() == Some((9983..9988, &999)));
+    }
+
+    #[test]
+    fn attach_spans_1() {
+        let mut gap_buf = GapString::from("fn main() {}");
+        gap_buf.enable_history(10);
+        let mut spans = Spans::new();
+        spans.insert(0..2, "keyword");
+        spans.insert(3..7, "function");
+        let id = gap_buf.attach_spans(spans);
+        assert!(gap_buf.spans::<char>(id).is_none());
+
+        gap_buf.insert_str(0, "pub ");
+        gap_buf.set_preedit(0, "x");
+        gap_buf.remove(1..5);
+        gap_buf.undo();
+        gap_buf.undo();
+        gap_buf.redo();
+        assert!(gap_buf == "pub fn main() {}");
+        let found: Vec<_> = gap_buf.spans::<&str>(id).unwrap().iter().collect();
+        assert!(found == [(4..6, &"keyword"), (7..11, &"function")]);
+
+        gap_buf.spans_mut::<&str>(id).unwrap().remove(7..11);
+        let copy = gap_buf.clone();
+        let spans = gap_buf.detach_spans::<&str>(id).unwrap();
+        assert!(spans.len() == 1 && gap_buf.spans::<&str>(id).is_none());
+        assert!(copy.spans::<&str>(id) == Some(&spans));
+    }
+
+    #[test]
+    fn remove_1() {
+        let mut gap_buf = GapString::from("aaa bbb aaa");
+        let mut spans = Spans::new();
+        spans.insert(0..11, "text");
+        spans.insert(4..7, "word");
+        spans.remove(3..8);
+        assert!(spans.len() == 2 && spans.overlapping(3..8).next().is_none());
+
+        spans.adjust(&gap_buf.remove(3..8));
+        assert!(gap_buf == "aaaaaa");
+        let found: Vec<_> = spans.iter().collect();
+        assert!(found == [(0..6, &"text")]);
+    }
+
+    #[test]
+    fn overlapping_1() {
+        let mut spans = Spans::new();
+        spans.insert(0..100, 'a');
+        spans.insert(10..20, 'b');
+        spans.insert(15..16, 'c');
+        spans.insert(30..40, 'd');
+
+        let values = |range| spans.overlapping(range).map(|(_, &v)| v).collect::<String>();
+        assert!(values(12..15) == "ab");
+        assert!(values(15..15) == "abc");
+        assert!(values(20..30) == "a");
+        assert!(values(39..200) == "ad");
+        assert!(values(100..100).is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use {ChunkedString, GapString};
+
+
+/// Editing operations shared by the text buffers of this crate, for editors
+/// to be written against any of them.
+///
+/// Offsets are byte offsets into the content, and every method panics like
+/// its `GapString` counterpart on offsets that are out of bounds or not on a
+/// char boundary. The trait is object safe, so the backend can also be picked
+/// at runtime as a `Box<dyn TextBuffer>`.
+pub trait TextBuffer {
+    /// Returns an iterator over the content as `&str` pieces, in order.
+    fn chunks(&self) -> Box<dyn Iterator<Item = &str> + '_>;
+
+    /// Inserts `s` at `offset`.
+    fn insert_str(&mut self, offset: usize, s: &str);
+
+    /// Returns the length of the content in bytes.
+    fn len(&self) -> usize;
+
+    /// Removes `range`.
+    fn remove(&mut self, range: Range<usize>);
+
+    /// Returns the content within `range`, borrowed where possible.
+    fn slice(&self, range: Range<usize>) -> Cow<'_, str>;
+
+    /// Returns an iterator over the chars of the content.
+    fn chars(&self) -> Box<dyn Iterator<Item = char> + '_> {
+        Box::new(self.chunks().flat_map(str::chars))
+    }
+
+    /// Returns `true` if the content is empty.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl TextBuffer for GapString {
+    fn chunks(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(GapString::chunks(self))
+    }
+
+    fn insert_str(&mut self, offset: usize, s: &str) {
+        GapString::insert_str(self, offset, s);
+    }
+
+    fn len(&self) -> usize {
+        GapString::len(self)
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        // The trait allows empty ranges, as `try_remove` does.
+        if let Err(e) = self.try_remove(range) {
+            panic!("{}", e);
+        }
+    }
+
+    fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        GapString::slice(self, range)
+    }
+}
+
+impl TextBuffer for ChunkedString {
+    fn chunks(&self) -> Box<dyn Iterator<Item = &str> + '_> {
+        Box::new(ChunkedString::chunks(self))
+    }
+
+    fn insert_str(&mut self, offset: usize, s: &str) {
+        ChunkedString::insert_str(self, offset, s);
+    }
+
+    fn len(&self) -> usize {
+        ChunkedString::len(self)
+    }
+
+    fn remove(&mut self, range: Range<usize>) {
+        ChunkedString::remove(self, range);
+    }
+
+    fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        ChunkedString::slice(self, range)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use {ChunkedString, GapString, PieceTable};
+    use super::TextBuffer;
+
+
+    #[test]
+    fn text_buffer_1() {
+        let mut buffers: Vec<Box<dyn TextBuffer>> = vec![
+            Box::new(GapString::new()),
+            Box::new(ChunkedString::with_chunk_size(4)),
+            Box::new(PieceTable::new())
+        ];
+        for buf in &mut buffers {
+            edit(&mut **buf);
+            assert!(buf.chunks().collect::<String>() == "héllo, world");
+            assert!(buf.slice(0..3) == "hé");
+            assert!(buf.chars().nth(1) == Some('é'));
+            assert!(buf.len() == 13 && !buf.is_empty());
+        }
+    }
+
+    fn edit<B: TextBuffer + ?Sized>(buf: &mut B) {
+        buf.insert_str(0, "world");
+        buf.insert_str(0, "héllo there, ");
+        buf.remove(6..12);
+        buf.remove(6..6);
+    }
+}
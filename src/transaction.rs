@@ -0,0 +1,150 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::{Deref, DerefMut};
+
+use GapString;
+use history::Change;
+
+
+/// Group of edits that is kept or rolled back as a whole.
+///
+/// Created by [`GapString::begin_transaction`](struct.GapString.html#method.begin_transaction).
+/// Edits are made to the buffer through `DerefMut` as usual and recorded as
+/// they go. `commit` keeps them as a single undo step, while `rollback` or
+/// dropping the transaction reverts them. Transactions can be nested, the
+/// edits of a committed inner transaction become part of the outer one.
+pub struct Transaction<'a> {
+    buf: &'a mut GapString,
+    outer: Option<Vec<Change>>,
+    finished: bool
+}
+
+impl GapString {
+    /// Opens a transaction on the buffer.
+    pub fn begin_transaction(&mut self) -> Transaction<'_> {
+        let outer = self.history.transaction.replace(Vec::new());
+        Transaction {
+            buf: self,
+            outer,
+            finished: false
+        }
+    }
+}
+
+impl<'a> Transaction<'a> {
+    /// Keeps the edits, recording them as a single undo step.
+    pub fn commit(mut self) {
+        self.finish(true);
+    }
+
+    /// Reverts the edits made since the transaction was opened.
+    ///
+    /// The edits already passed the read-only and protection checks, so
+    /// reverting them is not checked again. Any preedit text is cleared.
+    pub fn rollback(mut self) {
+        self.finish(false);
+    }
+
+    fn finish(&mut self, commit: bool) {
+        self.finished = true;
+        let edits = self.buf.history.transaction.take().unwrap_or_default();
+        if !commit {
+            self.buf.revert_unchecked(&edits);
+            self.buf.history.transaction = self.outer.take();
+            return;
+        }
+
+        match self.outer.take() {
+            Some(mut outer) => {
+                outer.extend(edits);
+                self.buf.history.transaction = Some(outer);
+            }
+            None => self.buf.history.push(edits)
+        }
+    }
+}
+
+impl<'a> Deref for Transaction<'a> {
+    type Target = GapString;
+
+    fn deref(&self) -> &GapString {
+        self.buf
+    }
+}
+
+impl<'a> DerefMut for Transaction<'a> {
+    fn deref_mut(&mut self) -> &mut GapString {
+        self.buf
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    /// Rolls back a transaction that was not finished.
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(false);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+
+
+    #[test]
+    fn transaction_1() {
+        let mut gap_buf = buf_from_str("12345678");
+        gap_buf.enable_history(10);
+        {
+            let mut transaction = gap_buf.begin_transaction();
+            transaction.insert_str(4, "-");
+            transaction.remove(0..2);
+            transaction.commit();
+        }
+        assert!(gap_buf == "34-5678");
+
+        assert!(gap_buf.undo());
+        assert!(gap_buf == "12345678");
+        assert!(!gap_buf.can_undo());
+        assert!(gap_buf.redo());
+        assert!(gap_buf == "34-5678");
+    }
+
+    #[test]
+    fn transaction_2() {
+        let mut gap_buf = buf_from_str("12345678");
+        let id = {
+            let mut transaction = gap_buf.begin_transaction();
+            transaction.replace_range(0..4, "abc");
+            let id = transaction.protect(0..3);
+            assert!(transaction.try_remove(1..2).is_err());
+            transaction.rollback();
+            id
+        };
+        assert!(gap_buf == "12345678");
+        gap_buf.unprotect(id);
+
+        {
+            let mut transaction = gap_buf.begin_transaction();
+            transaction.push_str("9");
+            {
+                let mut inner = transaction.begin_transaction();
+                inner.insert_str(0, "0");
+                inner.commit();
+            }
+            {
+                let mut inner = transaction.begin_transaction();
+                inner.clear();
+            }
+            assert!(transaction.to_string() == "0123456789");
+        }
+        assert!(gap_buf == "12345678");
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use {GapString, GapBufferError};
+
+
+/// `GapString` exported to JavaScript as `GapBuffer`.
+///
+/// Indices count UTF-16 code units like those of JavaScript strings, and
+/// are converted to byte offsets through the line index in O(log n) time.
+/// Invalid indices, including ones splitting a surrogate pair, throw an
+/// `Error`.
+#[wasm_bindgen(js_name = GapBuffer)]
+pub struct JsGapBuffer {
+    buf: GapString
+}
+
+#[wasm_bindgen(js_class = GapBuffer)]
+impl JsGapBuffer {
+    /// Creates a buffer holding `text`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> JsGapBuffer {
+        let mut buf = GapString::from(text);
+        buf.enable_line_index();
+        JsGapBuffer { buf }
+    }
+
+    /// Inserts `text` at `index`.
+    pub fn insert(&mut self, index: usize, text: &str) -> Result<(), JsError> {
+        let offset = self.byte_offset(index)?;
+        self.buf.try_insert_str(offset, text)?;
+        Ok(())
+    }
+
+    /// Returns the length of the content in UTF-16 code units, like
+    /// `String.prototype.length`.
+    #[wasm_bindgen(getter)]
+    pub fn length(&self) -> usize {
+        self.buf.byte_to_utf16_cu(self.buf.len())
+    }
+
+    /// Removes the content from `start` to `end`.
+    pub fn remove(&mut self, start: usize, end: usize) -> Result<(), JsError> {
+        let range = self.byte_range(start, end)?;
+        self.buf.try_remove(range)?;
+        Ok(())
+    }
+
+    /// Returns the content from `start` to `end`.
+    pub fn slice(&self, start: usize, end: usize) -> Result<String, JsError> {
+        let range = self.byte_range(start, end)?;
+        Ok(self.buf.slice(range).into_owned())
+    }
+
+    /// Returns the whole content.
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_js_string(&self) -> String {
+        self.buf.to_string()
+    }
+}
+
+impl JsGapBuffer {
+    /// Returns the byte offset of the UTF-16 index `index`.
+    fn byte_offset(&self, index: usize) -> Result<usize, GapBufferError> {
+        match self.buf.utf16_cu_to_byte(index) {
+            Some(offset) => Ok(offset),
+            None if index > self.length() => Err(GapBufferError::OutOfBounds),
+            None => Err(GapBufferError::NotCharBoundary)
+        }
+    }
+
+    fn byte_range(&self, start: usize, end: usize) -> Result<Range<usize>, GapBufferError> {
+        if start > end {
+            return Err(GapBufferError::OutOfBounds);
+        }
+        Ok(self.byte_offset(start)?..self.byte_offset(end)?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use GapBufferError;
+    use super::JsGapBuffer;
+
+
+    #[test]
+    fn insert_1() {
+        let mut buf = JsGapBuffer::new("a😀b");
+        assert!(buf.length() == 4);
+        buf.insert(3, "é").ok().unwrap();
+        buf.remove(0, 1).ok().unwrap();
+        assert!(buf.slice(0, 3).ok().unwrap() == "😀é");
+        assert!(buf.to_js_string() == "😀éb");
+    }
+
+    #[test]
+    fn byte_range_1() {
+        let buf = JsGapBuffer::new("a😀b");
+        assert!(buf.byte_range(1, 3) == Ok(1..5));
+        assert!(buf.byte_range(2, 3) == Err(GapBufferError::NotCharBoundary));
+        assert!(buf.byte_range(0, 5) == Err(GapBufferError::OutOfBounds));
+        assert!(buf.byte_range(3, 1) == Err(GapBufferError::OutOfBounds));
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2017 Nathan Sizemore <nathanrsizemore@gmail.com>
+//
+// This Source Code Form is subject to the terms of the Mozilla Public License,
+// v. 2.0. If a copy of the MPL was not distributed with this file, you can
+// obtain one at http://mozilla.org/MPL/2.0/.
+
+
+#[cfg(feature = "unicode")]
+use unicode_segmentation::UnicodeSegmentation;
+
+use GapString;
+
+
+/// Kind of char, word boundaries being between chars of different kinds when
+/// the `unicode` feature is disabled.
+#[cfg(not(feature = "unicode"))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Space,
+    Newline,
+    Punctuation
+}
+
+impl GapString {
+    /// Returns the first word boundary after `offset`, or the end of the
+    /// content if there is none, such as for moving the cursor a word to the
+    /// right.
+    ///
+    /// With the `unicode` feature, boundaries are those of Unicode word
+    /// segmentation, and only the line holding `offset` is segmented. They
+    /// are between runs of word chars, whitespace and other chars otherwise,
+    /// with every line break on its own.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn next_word_boundary(&self, offset: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        if offset == self.len() {
+            return offset;
+        }
+        self.next_word_boundary_unchecked(offset)
+    }
+
+    /// Returns the last word boundary before `offset`, or 0 if there is
+    /// none, such as for moving the cursor a word to the left.
+    ///
+    /// Boundaries are found like `next_word_boundary`.
+    ///
+    /// # Panics
+    ///
+    /// * If `offset` is out of bounds or not on a char boundary.
+    pub fn prev_word_boundary(&self, offset: usize) -> usize {
+        if let Err(e) = self.check_offset(offset) {
+            panic!("{}", e);
+        }
+        if offset == 0 {
+            return offset;
+        }
+        self.prev_word_boundary_unchecked(offset)
+    }
+
+    #[cfg(feature = "unicode")]
+    fn next_word_boundary_unchecked(&self, offset: usize) -> usize {
+        let start = self.rfind("\n", offset).map_or(0, |i| i + 1);
+        let end = self.find("\n", offset).map_or(self.len(), |i| i + 1);
+        let line = self.slice(start..end);
+        line.split_word_bound_indices()
+            .map(|(i, _)| start + i)
+            .find(|&boundary| boundary > offset)
+            .unwrap_or(end)
+    }
+
+    #[cfg(feature = "unicode")]
+    fn prev_word_boundary_unchecked(&self, offset: usize) -> usize {
+        let prev = self.chars_before(offset).next().map_or(0, |ch| offset - ch.len_utf8());
+        let start = self.rfind("\n", prev).map_or(0, |i| i + 1);
+        let line = self.slice(start..offset);
+        line.split_word_bound_indices()
+            .map(|(i, _)| start + i)
+            .next_back()
+            .unwrap_or(start)
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    fn next_word_boundary_unchecked(&self, offset: usize) -> usize {
+        let mut chars = self.chars_at(offset);
+        let first = chars.next().unwrap();
+        let mut boundary = offset + first.len_utf8();
+        if class(first) != CharClass::Newline {
+            for ch in chars.take_while(|&ch| class(ch) == class(first)) {
+                boundary += ch.len_utf8();
+            }
+        }
+        boundary
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    fn prev_word_boundary_unchecked(&self, offset: usize) -> usize {
+        let mut chars = self.chars_before(offset);
+        let first = chars.next().unwrap();
+        let mut boundary = offset - first.len_utf8();
+        if class(first) != CharClass::Newline {
+            for ch in chars.take_while(|&ch| class(ch) == class(first)) {
+                boundary -= ch.len_utf8();
+            }
+        }
+        boundary
+    }
+}
+
+#[cfg(not(feature = "unicode"))]
+fn class(ch: char) -> CharClass {
+    if ch == '\n' || ch == '\r' {
+        CharClass::Newline
+    } else if ch.is_whitespace() {
+        CharClass::Space
+    } else if ch.is_alphanumeric() || ch == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use buf_from_str;
+
+
+    #[test]
+    fn next_word_boundary_1() {
+        let mut gap_buf = buf_from_str("foo  bär\nbaz");
+        gap_buf.cursor(8);
+        let mut offset = 0;
+        let mut boundaries = Vec::new();
+        while offset < gap_buf.len() {
+            offset = gap_buf.next_word_boundary(offset);
+            boundaries.push(offset);
+        }
+        assert!(boundaries == [3, 5, 9, 10, 13]);
+        assert!(gap_buf.next_word_boundary(13) == 13);
+    }
+
+    #[test]
+    fn prev_word_boundary_1() {
+        let mut gap_buf = buf_from_str("foo  bär\nbaz");
+        gap_buf.cursor(4);
+        let mut offset = gap_buf.len();
+        let mut boundaries = Vec::new();
+        while offset > 0 {
+            offset = gap_buf.prev_word_boundary(offset);
+            boundaries.push(offset);
+        }
+        assert!(boundaries == [10, 9, 5, 3, 0]);
+        assert!(gap_buf.prev_word_boundary(4) == 3);
+    }
+}